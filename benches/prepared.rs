@@ -0,0 +1,46 @@
+//! Compare formatting a prepared format string against parsing it each time.
+#![feature(test)]
+
+extern crate test;
+#[macro_use] extern crate runtime_fmt;
+#[macro_use] extern crate runtime_fmt_derive;
+
+use runtime_fmt::PreparedFormat;
+use test::Bencher;
+
+#[derive(FormatArgs)]
+struct Entry {
+    name: &'static str,
+    value: u32,
+}
+
+const SPEC: &'static str = "{name}: {value:>8} ({value:#x})";
+
+#[bench]
+fn parse_each_time(b: &mut Bencher) {
+    b.iter(|| rt_format!(SPEC, name="entry", value=1234).unwrap())
+}
+
+#[bench]
+fn prepare_each_time(b: &mut Bencher) {
+    let entry = Entry { name: "entry", value: 1234 };
+    b.iter(|| PreparedFormat::prepare(SPEC).unwrap().format(&entry))
+}
+
+#[bench]
+fn prepare_once(b: &mut Bencher) {
+    let entry = Entry { name: "entry", value: 1234 };
+    let prepared = PreparedFormat::prepare(SPEC).unwrap();
+    b.iter(|| prepared.format(&entry))
+}
+
+#[bench]
+fn render_into_reused(b: &mut Bencher) {
+    let entry = Entry { name: "entry", value: 1234 };
+    let prepared = PreparedFormat::prepare(SPEC).unwrap();
+    let mut buf = String::new();
+    b.iter(|| {
+        prepared.render_into(&mut buf, &entry).unwrap();
+        buf.len()
+    })
+}
@@ -0,0 +1,45 @@
+//! Compare templates of increasing complexity against `str::to_owned` and
+//! `format!`.
+#![feature(test)]
+
+extern crate test;
+#[macro_use] extern crate runtime_fmt;
+
+use test::{black_box, Bencher};
+
+#[bench]
+fn to_owned(b: &mut Bencher) {
+    let spec = black_box("[server] starting up");
+    b.iter(|| spec.to_owned())
+}
+
+#[bench]
+fn literal_only(b: &mut Bencher) {
+    let spec = black_box("[server] starting up");
+    b.iter(|| rt_format!(spec).unwrap())
+}
+
+#[bench]
+fn literal_escaped(b: &mut Bencher) {
+    let spec = black_box("[server] {{starting}} up");
+    b.iter(|| rt_format!(spec).unwrap())
+}
+
+#[bench]
+fn single_arg(b: &mut Bencher) {
+    let spec = black_box("[server] starting {}");
+    b.iter(|| rt_format!(spec, "up").unwrap())
+}
+
+#[bench]
+fn many_args(b: &mut Bencher) {
+    let spec = black_box("[{}] {} {:>4} of {:<4} ({:.2}%) {name}");
+    b.iter(|| rt_format!(spec, "server", "step", 3, 12, 25.0, name = "ok").unwrap())
+}
+
+#[bench]
+fn many_args_std(b: &mut Bencher) {
+    let (server, step) = black_box(("server", "step"));
+    b.iter(|| format!("[{}] {} {:>4} of {:<4} ({:.2}%) {name}", server, step, 3, 12, 25.0,
+                      name = "ok"))
+}
@@ -1,6 +1,6 @@
 
 use std::borrow::{Borrow, Cow};
-use std::collections::HashSet;
+use std::collections::HashMap;
 use syn;
 use syn::Lit::Str;
 use syn::MetaItem::{List, NameValue, Word};
@@ -71,22 +71,49 @@ impl<'a> Container<'a> {
     pub fn from_ast(ast: &'a syn::DeriveInput) -> Result<Self, String> {
         let ctx = Context::new();
 
-        let variant = match ast.body {
-            syn::Body::Struct(ref variant) => variant,
-            _ => return Err("#[derive(FormatArgs)] is not implemented for enums".to_string())
+        let variants = match ast.body {
+            syn::Body::Struct(ref variant) => vec![(None, variant)],
+            syn::Body::Enum(ref variants) => {
+                variants.iter().map(|v| (Some(&v.ident), &v.data)).collect()
+            }
         };
 
         let fields = {
             let mut fields = Vec::new();
-            let mut field_names = HashSet::new();
-            for (tuple_index, field) in variant.fields().iter().enumerate() {
-                if let Some(field) = Field::from_ast(&ctx, field, fields.len(), tuple_index) {
-                    for alias in field.aliases() {
-                        if !field_names.insert(*alias) {
-                            ctx.error(&format!("Duplicate field alias: {}", alias));
+            let mut field_names = HashMap::new();
+            for (variant, data) in variants {
+                for (tuple_index, field) in data.fields().iter().enumerate() {
+                    if let Some(field) = Field::from_ast(&ctx, field, variant, fields.len(), tuple_index) {
+                        // A field of another variant with the same names and
+                        // type is the same argument.
+                        let shared = field.name.and_then(|name| field_names.get(name))
+                            .and_then(|&(index, _)| if fields[index].shares_with(&field) {
+                                Some(index)
+                            } else {
+                                None
+                            });
+                        if let Some(index) = shared {
+                            fields[index].places.extend(field.places);
+                            continue;
+                        }
+
+                        let description = field.description();
+                        for alias in field.aliases() {
+                            match field_names.insert(*alias, (field.index(), description.clone())) {
+                                Some((index, _)) if index == field.index() => {
+                                    ctx.error(&format!("Duplicate field alias `{}` on field `{}`",
+                                                       alias, description));
+                                }
+                                Some((_, other)) => {
+                                    ctx.error(&format!("Field `{}` is exposed as `{}`, which \
+                                                        field `{}` already uses",
+                                                       description, alias, other));
+                                }
+                                None => {}
+                            }
                         }
+                        fields.push(field);
                     }
-                    fields.push(field);
                 }
             }
 
@@ -118,8 +145,11 @@ impl<'a> Container<'a> {
 
 pub struct Field<'a> {
     field_index: usize,
+    // The variant, if any, and identifier of each field this one stands for.
+    // Only the fields of an enum's variants may stand for more than one.
+    places: Vec<(Option<&'a syn::Ident>, Cow<'a, syn::Ident>)>,
 
-    ident: Cow<'a, syn::Ident>,
+    name: Option<&'a str>,
     aliases: Vec<&'a str>,
 
     ty: &'a syn::Ty
@@ -127,7 +157,8 @@ pub struct Field<'a> {
 
 impl<'a> Field<'a> {
 
-    pub fn from_ast(ctx: &Context, ast: &'a syn::Field, field_index: usize, tuple_index: usize) -> Option<Self> {
+    pub fn from_ast(ctx: &Context, ast: &'a syn::Field, variant: Option<&'a syn::Ident>,
+                    field_index: usize, tuple_index: usize) -> Option<Self> {
         let ident = match ast.ident {
             Some(ref ident) => Cow::Borrowed(ident),
             None => Cow::Owned(syn::Ident::from(tuple_index))
@@ -146,7 +177,7 @@ impl<'a> Field<'a> {
                     MetaItem(NameValue(ref ident, Str(ref value, _))) if ident == "rename" => {
                         name.set(value.as_ref());
                     }
-                    MetaItem(Word(ref ident)) if ident == "ignore" => {
+                    MetaItem(Word(ref ident)) if ident == "ignore" || ident == "skip" => {
                         ignored.set();
                     }
                     _ => ctx.error(&format!("Unrecognized attribute: {:?}", attribute))
@@ -155,15 +186,17 @@ impl<'a> Field<'a> {
         }
 
         let mut aliases = aliases.into().unwrap_or_else(Vec::new);
-        if let Some(name) = name.into().or(ast.ident.as_ref().map(|ident| ident.as_ref())) {
+        let name = name.into().or(ast.ident.as_ref().map(|ident| ident.as_ref()));
+        if let Some(name) = name {
             aliases.push(name);
         }
 
         if !ignored.into() {
             Some(Field {
                 field_index: field_index,
+                places: vec![(variant, ident)],
 
-                ident: ident,
+                name: name,
                 aliases: aliases,
 
                 ty: &ast.ty
@@ -178,8 +211,13 @@ impl<'a> Field<'a> {
         self.field_index
     }
 
-    pub fn ident(&self) -> &syn::Ident {
-        self.ident.borrow()
+    // The variant, if any, and identifier of each field this one stands for.
+    pub fn places(&self) -> Vec<(Option<&'a syn::Ident>, &syn::Ident)> {
+        self.places.iter().map(|&(variant, ref ident)| (variant, ident.borrow())).collect()
+    }
+
+    pub fn name(&self) -> Option<&'a str> {
+        self.name
     }
 
     pub fn aliases(&self) -> &[&'a str] {
@@ -190,6 +228,23 @@ impl<'a> Field<'a> {
         self.ty
     }
 
+    // The field's identifier, qualified by its variant if it has one.
+    fn description(&self) -> String {
+        match self.places[0] {
+            (Some(variant), ref ident) => format!("{}::{}", variant, ident),
+            (None, ref ident) => ident.to_string(),
+        }
+    }
+
+    // Whether `other`, of another variant, may stand for the same argument:
+    // it must have the same type and be exposed under the same names.
+    fn shares_with(&self, other: &Field) -> bool {
+        let variant = other.places[0].0;
+        variant.is_some() && self.places.iter().all(|place| place.0 != variant) &&
+            self.ty == other.ty && self.aliases.len() == other.aliases.len() &&
+            self.aliases.iter().all(|alias| other.aliases.contains(alias))
+    }
+
 }
 
 fn filter_format_attributes(attr: &syn::Attribute) -> Option<&Vec<syn::NestedMetaItem>> {
@@ -199,4 +254,51 @@ fn filter_format_attributes(attr: &syn::Attribute) -> Option<&Vec<syn::NestedMet
         }
         _ => None
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use syn;
+    use super::Container;
+
+    fn error(input: &str) -> Option<String> {
+        let ast = syn::parse_derive_input(input).unwrap();
+        Container::from_ast(&ast).err()
+    }
+
+    #[test]
+    fn unique_names() {
+        assert_eq!(error("struct S { a: u8, #[format_args(rename = \"c\")] b: u8 }"), None);
+        assert_eq!(error("struct S { a: u8, #[format_args(skip)] b: u8, \
+                          #[format_args(rename = \"b\")] c: u8 }"), None);
+    }
+
+    #[test]
+    fn rename_collides_with_identifier() {
+        let err = error("struct S { a: u8, #[format_args(rename = \"a\")] b: u8 }").unwrap();
+        assert_eq!(err, "Field `b` is exposed as `a`, which field `a` already uses");
+        let err = error("struct S { #[format_args(rename = \"b\")] a: u8, b: u8 }").unwrap();
+        assert_eq!(err, "Field `b` is exposed as `b`, which field `a` already uses");
+    }
+
+    #[test]
+    fn alias_collides() {
+        let err = error("struct S { a: u8, #[format_args(aliases = \"x,a\")] b: u8 }").unwrap();
+        assert_eq!(err, "Field `b` is exposed as `a`, which field `a` already uses");
+        let err = error("struct S { #[format_args(aliases = \"a\")] a: u8 }").unwrap();
+        assert_eq!(err, "Duplicate field alias `a` on field `a`");
+        let err = error("enum E { A { x: u8 }, B { x: u16 } }").unwrap();
+        assert_eq!(err, "Field `B::x` is exposed as `x`, which field `A::x` already uses");
+    }
+
+    #[test]
+    fn shared_across_variants() {
+        assert_eq!(error("enum E { A { x: u8 }, B { y: u16, x: u8 }, C { x: u8 } }"), None);
+        assert_eq!(error("enum E { A { #[format_args(aliases = \"z\")] x: u8 }, \
+                          B { #[format_args(aliases = \"z\")] x: u8 } }"), None);
+        let err = error("enum E { A { #[format_args(aliases = \"z\")] x: u8 }, B { x: u8 } }")
+            .unwrap();
+        assert_eq!(err, "Field `B::x` is exposed as `x`, which field `A::x` already uses");
+        let err = error("enum E { A { x: u8, #[format_args(rename = \"x\")] y: u8 } }").unwrap();
+        assert_eq!(err, "Field `A::y` is exposed as `x`, which field `A::x` already uses");
+    }
+}
@@ -1,5 +1,5 @@
 //! A custom-derive implementation for the `FormatArgs` trait.
-#![recursion_limit="128"]
+#![recursion_limit="256"]
 #![feature(option_entry)]
 
 extern crate proc_macro;
@@ -8,19 +8,42 @@ extern crate syn;
 
 use proc_macro::TokenStream;
 
-use ast::Container;
+use ast::{Container, Field};
 
 mod ast;
 mod context;
 
-/// Derive a `FormatArgs` implementation for the provided input struct.
+/// Derive a `FormatArgs` implementation for the provided input struct or enum.
+///
+/// Fields accept the following attributes under `#[format_args(...)]`:
+///
+/// * `rename = "name"` - expose the field under `name` instead of its identifier.
+/// * `aliases = "a,b"` - additional names under which the field is exposed.
+/// * `skip` (or `ignore`) - hide the field from templates entirely. Skipped
+///   fields take no position, so the fields after them are numbered as though
+///   the skipped field were not there.
+///
+/// The fields of an enum are numbered across all of its variants in
+/// declaration order. Formatting a field belonging to a variant other than the
+/// active one produces no output; used as a count, it counts as zero, and as a
+/// fill, it is a space. Fields of different variants with the same type and
+/// names are one argument, taking a single index, which refers to whichever
+/// of them is active. Otherwise names must be unique across all variants; use
+/// `rename` to tell such fields apart.
+///
+/// A name exposed by more than one field, whether through its identifier,
+/// `rename`, or `aliases`, is reported with `compile_error!`, naming both
+/// fields.
+///
+/// Dotted names such as `{user.address.city}` descend into any field whose
+/// type itself implements `FormatArgs`.
 #[proc_macro_derive(FormatArgs, attributes(format_args))]
 pub fn derive_format_args(input: TokenStream) -> TokenStream {
     let string = input.to_string();
     let ast = syn::parse_derive_input(&string).unwrap();
     match implement_format_trait(&ast) {
         Ok(tokens) => tokens.parse().unwrap(),
-        Err(error) => panic!(error)
+        Err(error) => quote!(compile_error!(#error);).parse().unwrap(),
     }
 }
 
@@ -28,12 +51,22 @@ fn implement_format_trait(ast: &syn::DeriveInput) -> Result<quote::Tokens, Strin
     let container = Container::from_ast(ast)?;
 
     let validate_name = build_validate_name(&container);
-    let validate_index = {
-        let max_index = container.fields().len();
-        quote! { index < #max_index }
-    };
+    let arg_count = container.fields().len();
+    let arg_names: Vec<_> = container.fields().iter()
+        .map(|field| field.name().unwrap_or(""))
+        .collect();
     let get_child = build_get_child(&container);
-    let as_usize = build_as_usize(&container);
+    let as_usize = build_view(&container, "is_usize", "usize_of", |lt| quote! { &#lt usize },
+                              quote! {{ static __ABSENT: usize = 0; &__ABSENT }});
+    let as_char = build_view(&container, "is_char", "char_of", |lt| quote! { &#lt char },
+                             quote! {{ static __ABSENT: char = ' '; &__ABSENT }});
+    let as_str = build_view(&container, "is_str", "as_str", |lt| quote! { &#lt str },
+                            quote! { "" });
+    let as_f64 = build_view(&container, "is_number", "as_f64", |_| quote! { f64 },
+                            quote! { 0.0 });
+    let get_nested = build_get_nested(&container);
+    let get_join = build_get_join(&container);
+    let is_float = build_is_float(&container);
 
     let ident = container.ident();
     let dummy_ident = syn::Ident::new(format!("_IMPL_FORMAT_ARGS_FOR_{}", ident));
@@ -41,7 +74,7 @@ fn implement_format_trait(ast: &syn::DeriveInput) -> Result<quote::Tokens, Strin
 
     Ok(quote! {
         #[allow(non_upper_case_globals, unused_attributes)]
-        #[allow(unused_variables, unused_qualifications)]
+        #[allow(unused_variables, unused_qualifications, unreachable_patterns)]
         const #dummy_ident: () = {
             extern crate runtime_fmt as _runtime_fmt;
             use std::fmt::{Formatter as _Formatter, Result as _Result};
@@ -53,7 +86,18 @@ fn implement_format_trait(ast: &syn::DeriveInput) -> Result<quote::Tokens, Strin
                 }
                 #[allow(unused_comparisons)]
                 fn validate_index(index: usize) -> bool {
-                    #validate_index
+                    index < #arg_count
+                }
+                #[inline]
+                fn arg_count() -> usize {
+                    #arg_count
+                }
+                fn is_float(index: usize) -> bool {
+                    #is_float
+                }
+                fn arg_names() -> &'static [&'static str] {
+                    const NAMES: &'static [&'static str] = &[#(#arg_names),*];
+                    NAMES
                 }
                 fn get_child<__F>(index: usize) -> _Option<fn(&Self, &mut _Formatter) -> _Result>
                     where __F: _runtime_fmt::codegen::FormatTrait + ?Sized
@@ -63,6 +107,25 @@ fn implement_format_trait(ast: &syn::DeriveInput) -> Result<quote::Tokens, Strin
                 fn as_usize(index: usize) -> Option<fn(&Self) -> &usize> {
                     #as_usize
                 }
+                fn as_char(index: usize) -> Option<fn(&Self) -> &char> {
+                    #as_char
+                }
+                fn as_str(index: usize) -> Option<fn(&Self) -> &str> {
+                    #as_str
+                }
+                fn as_f64(index: usize) -> Option<fn(&Self) -> f64> {
+                    #as_f64
+                }
+                fn get_nested(index: usize)
+                    -> _Option<fn(&Self) -> _Option<&_runtime_fmt::DynFormatArgs>>
+                {
+                    #get_nested
+                }
+                fn get_join(index: usize)
+                    -> _Option<fn(&Self) -> _Option<&_runtime_fmt::codegen::Join>>
+                {
+                    #get_join
+                }
             }
         };
     })
@@ -86,16 +149,29 @@ fn build_validate_name<'a>(container: &Container<'a>) -> quote::Tokens {
 }
 
 fn build_get_child<'a>(container: &Container<'a>) -> quote::Tokens {
+    let self_ = container.ident();
+    let (impl_generics, ty_generics, where_clause) = container.generics().split_for_impl();
     let mut matches = quote::Tokens::new();
     for field in container.fields() {
         let index = field.index();
         let ty = field.ty();
-        let ident = field.ident();
-
+        let body = access(container, field, quote! { _Option::Some(__field) });
+        // Each field is named by its own `Child`, so that no closure need be
+        // conjured to reach it.
         matches.append(quote! {
-            #index => _runtime_fmt::codegen::combine::<__F, Self, #ty, _>(
-                |this| &this.#ident
-            ),
+            #index => {
+                struct __Child;
+                impl #impl_generics _runtime_fmt::codegen::Child<#self_ #ty_generics>
+                    for __Child #where_clause
+                {
+                    type Target = #ty;
+                    #[inline]
+                    fn get(this: &#self_ #ty_generics) -> _Option<&#ty> {
+                        #body
+                    }
+                }
+                _runtime_fmt::codegen::combine::<__F, Self, __Child>()
+            },
         });
     }
     quote! {
@@ -106,33 +182,154 @@ fn build_get_child<'a>(container: &Container<'a>) -> quote::Tokens {
     }
 }
 
-fn build_as_usize<'a>(container: &'a Container) -> quote::Tokens {
+// An expression evaluating `found`, an `Option`, with `__field` bound to the
+// field of `this` which `field` stands for, or `None` if no variant holding
+// one is active.
+fn access<'a>(container: &Container<'a>, field: &Field<'a>, found: quote::Tokens)
+    -> quote::Tokens
+{
     let self_ = container.ident();
-    let (_, ty_generics, where_clause) = container.generics().split_for_impl();
+    let mut arms = quote::Tokens::new();
+    for (variant, ident) in field.places() {
+        match variant {
+            None => return quote! {{ let __field = &this.#ident; #found }},
+            Some(variant) => arms.append(quote! {
+                #self_::#variant { #ident: ref __field, .. } => #found,
+            }),
+        }
+    }
+    quote! {
+        match *this {
+            #arms
+            _ => _Option::None,
+        }
+    }
+}
 
-    // To avoid causing trouble with lifetime elision rules, an explicit
-    // lifetime for the input and output is used.
+// To avoid causing trouble with lifetime elision rules, the inner functions
+// generated for `as_usize`, `as_str`, and their kin use an explicit lifetime for their
+// input and output. Returns that lifetime and the generics to declare.
+fn inner_generics<'a>(container: &'a Container) -> (syn::Ident, syn::Generics) {
     let lifetime = syn::Ident::new("'__as_usize_inner");
-    let mut generics2 = container.generics().clone();
-    generics2.lifetimes.insert(0, syn::LifetimeDef {
+    let mut generics = container.generics().clone();
+    generics.lifetimes.insert(0, syn::LifetimeDef {
         attrs: vec![],
         lifetime: syn::Lifetime { ident: lifetime.clone() },
         bounds: vec![],
     });
+    (lifetime, generics)
+}
+
+// Build the body of an accessor such as `as_str`, which views a field through
+// the function `view` in `runtime_fmt::codegen` if `check` accepts its type.
+// The type of the view is given by `out`, in terms of the field's lifetime,
+// and `absent` is the view of a field of an inactive variant.
+fn build_view<'a, F>(container: &'a Container, check: &str, view: &str, out: F,
+                     absent: quote::Tokens)
+    -> quote::Tokens
+    where F: Fn(&syn::Ident) -> quote::Tokens
+{
+    let (check, view) = (syn::Ident::new(check), syn::Ident::new(view));
+    let self_ = container.ident();
+    let (_, ty_generics, where_clause) = container.generics().split_for_impl();
+    let (lifetime, generics2) = inner_generics(container);
+    let (impl_generics, _, _) = generics2.split_for_impl();
+    let out = out(&lifetime);
+
+    let mut matches = quote::Tokens::new();
+    for field in container.fields() {
+        let index = field.index();
+        let ty = field.ty();
+        let body = access(container, field, quote! { _runtime_fmt::codegen::#view(__field) });
+
+        matches.append(quote! {
+            #index => {
+                fn inner #impl_generics (this: &#lifetime #self_ #ty_generics)
+                    -> #out
+                    #where_clause { (#body).unwrap_or(#absent) }
+                if _runtime_fmt::codegen::#check::<#ty>() {
+                    _Option::Some(inner)
+                } else {
+                    _Option::None
+                }
+            },
+        });
+    }
+
+    quote! {
+        match index {
+            #matches
+            _ => panic!("bad index {}", index)
+        }
+    }
+}
+
+fn build_is_float<'a>(container: &Container<'a>) -> quote::Tokens {
+    let mut matches = quote::Tokens::new();
+    for field in container.fields() {
+        let index = field.index();
+        let ty = field.ty();
+        matches.append(quote! {
+            #index => _runtime_fmt::codegen::is_float::<#ty>(),
+        });
+    }
+    quote! {
+        match index {
+            #matches
+            _ => panic!("bad index {}", index)
+        }
+    }
+}
+
+fn build_get_join<'a>(container: &'a Container) -> quote::Tokens {
+    let self_ = container.ident();
+    let (_, ty_generics, where_clause) = container.generics().split_for_impl();
+    let (lifetime, generics2) = inner_generics(container);
     let (impl_generics, _, _) = generics2.split_for_impl();
 
     let mut matches = quote::Tokens::new();
     for field in container.fields() {
         let index = field.index();
         let ty = field.ty();
-        let ident = field.ident();
+        let body = access(container, field, quote! { _runtime_fmt::codegen::as_join(__field) });
+        matches.append(quote! {
+            #index => {
+                fn inner #impl_generics (this: &#lifetime #self_ #ty_generics)
+                    -> _Option<&#lifetime _runtime_fmt::codegen::Join>
+                    #where_clause { #body }
+                if _runtime_fmt::codegen::is_join::<#ty>() {
+                    _Option::Some(inner)
+                } else {
+                    _Option::None
+                }
+            },
+        });
+    }
+
+    quote! {
+        match index {
+            #matches
+            _ => panic!("bad index {}", index)
+        }
+    }
+}
 
+fn build_get_nested<'a>(container: &'a Container) -> quote::Tokens {
+    let self_ = container.ident();
+    let (_, ty_generics, where_clause) = container.generics().split_for_impl();
+    let (lifetime, generics2) = inner_generics(container);
+    let (impl_generics, _, _) = generics2.split_for_impl();
+
+    let mut matches = quote::Tokens::new();
+    for field in container.fields() {
+        let index = field.index();
+        let body = access(container, field, quote! { _runtime_fmt::codegen::as_dyn(__field) });
         matches.append(quote! {
             #index => {
                 fn inner #impl_generics (this: &#lifetime #self_ #ty_generics)
-                    -> &#lifetime #ty
-                    #where_clause { &this.#ident }
-                _runtime_fmt::codegen::as_usize(inner)
+                    -> _Option<&#lifetime _runtime_fmt::DynFormatArgs>
+                    #where_clause { #body }
+                _Option::Some(inner)
             },
         });
     }
@@ -143,4 +340,4 @@ fn build_as_usize<'a>(container: &'a Container) -> quote::Tokens {
             _ => panic!("bad index {}", index)
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,539 @@
+//! Inspection of format strings.
+//!
+//! The `parse` function exposes the structure of a format string without
+//! requiring any values to format against it. This is useful for tools which
+//! wish to check which arguments a format string refers to and how it would
+//! format them, such as linting templates against a schema. The `pieces`
+//! function does the same lazily, for format strings too large to parse all
+//! at once, and `referenced_names` lists just the names a format string uses.
+//!
+//! With the `serialize` feature, these types implement serde's `Serialize`
+//! and `Deserialize`, so that a format string may be parsed ahead of time and
+//! later prepared from its pieces with `PreparedFormat::from_pieces`. A
+//! `Piece` borrows its text from the serialized data, which is not possible
+//! for text containing escapes such as `\n`; an `OwnedPiece` holds its own.
+use std::mem;
+use std::string::String;
+use std::vec::Vec;
+use fmt_macros as p;
+use erase;
+use transform;
+use Error;
+
+/// A portion of a format string.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum Piece<'a> {
+    /// Literal text, to be emitted as-is.
+    Literal(&'a str),
+    /// A reference to an argument, and how it is to be formatted.
+    Argument {
+        /// The argument being referred to.
+        #[cfg_attr(feature = "serialize", serde(borrow))]
+        position: Position<'a>,
+        /// The formatting options applied to the argument.
+        #[cfg_attr(feature = "serialize", serde(borrow))]
+        format: FormatSpec<'a>,
+    },
+}
+
+/// The manner in which an argument is referred to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum Position<'a> {
+    /// The next argument in sequence, as with `{}`, resolved to its index.
+    Next(usize),
+    /// An explicit index, as with `{0}`.
+    Index(usize),
+    /// An index counted back from the end, as with `{-1}` for the last
+    /// argument.
+    FromEnd(usize),
+    /// A name, as with `{name}`.
+    Named(&'a str),
+    /// A name in double quotes, as with `{"a:b"}`, taken literally rather
+    /// than as a dotted path. This is the text between the quotes, in which
+    /// the escapes `\"` and `\\` have not been resolved.
+    Quoted(&'a str),
+}
+
+/// The alignment requested for an argument.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum Alignment {
+    /// Left alignment, as with `{:<}`.
+    Left,
+    /// Right alignment, as with `{:>}`.
+    Right,
+    /// Center alignment, as with `{:^}`.
+    Center,
+    /// No alignment was specified.
+    Unknown,
+}
+
+/// A width or precision.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum Count<'a> {
+    /// A literal value, as with `{:5}`.
+    Is(usize),
+    /// The argument at the given index, as with `{:1$}` or `{:.*}`.
+    Param(usize),
+    /// The argument with the given name, as with `{:width$}`.
+    Named(&'a str),
+    /// No count was specified.
+    Implied,
+}
+
+/// One of the standard formatting traits, as requested by a specifier.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum FormatTraitKind {
+    /// `Display`, as with `{}`.
+    Display,
+    /// `Debug`, as with `{:?}`, `{:x?}`, or `{:X?}`.
+    Debug,
+    /// `LowerExp`, as with `{:e}`.
+    LowerExp,
+    /// `UpperExp`, as with `{:E}`.
+    UpperExp,
+    /// `Octal`, as with `{:o}`.
+    Octal,
+    /// `Pointer`, as with `{:p}`.
+    Pointer,
+    /// `Binary`, as with `{:b}`.
+    Binary,
+    /// `LowerHex`, as with `{:x}`.
+    LowerHex,
+    /// `UpperHex`, as with `{:X}`.
+    UpperHex,
+}
+
+/// The formatting options applied to an argument.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct FormatSpec<'a> {
+    /// The fill character, if one was specified.
+    pub fill: Option<char>,
+    /// The requested alignment.
+    pub align: Alignment,
+    /// The requested width.
+    #[cfg_attr(feature = "serialize", serde(borrow))]
+    pub width: Count<'a>,
+    /// The requested precision.
+    #[cfg_attr(feature = "serialize", serde(borrow))]
+    pub precision: Count<'a>,
+    /// The formatting specifier, such as `"?"` or `"x"`. Empty for `Display`.
+    pub ty: &'a str,
+    /// The standard formatting trait requested, for matching exhaustively.
+    ///
+    /// This is `None` for the extension types, such as `%` or `html`, for
+    /// specifiers which do not name a known trait, and where a `join(...)`
+    /// directive overrides the trait.
+    pub trait_: Option<FormatTraitKind>,
+    flags: u32,
+    grouping: bool,
+    #[cfg_attr(feature = "serialize", serde(borrow))]
+    directive: Option<(&'a str, &'a str)>,
+    #[cfg_attr(feature = "serialize", serde(borrow))]
+    fill_name: Option<&'a str>,
+}
+
+impl<'a> FormatSpec<'a> {
+    /// Whether the `+` flag was specified.
+    #[inline]
+    pub fn sign_plus(&self) -> bool {
+        self.flags & (1 << p::FlagSignPlus as u32) != 0
+    }
+
+    /// Whether the `-` flag was specified.
+    #[inline]
+    pub fn sign_minus(&self) -> bool {
+        self.flags & (1 << p::FlagSignMinus as u32) != 0
+    }
+
+    /// Whether the `#` flag was specified.
+    #[inline]
+    pub fn alternate(&self) -> bool {
+        self.flags & (1 << p::FlagAlternate as u32) != 0
+    }
+
+    /// Whether the `0` flag was specified.
+    #[inline]
+    pub fn sign_aware_zero_pad(&self) -> bool {
+        self.flags & (1 << p::FlagSignAwareZeroPad as u32) != 0
+    }
+
+    /// Whether `Debug` output should use lower-case hexadecimal, as with `x?`.
+    #[inline]
+    pub fn debug_lower_hex(&self) -> bool {
+        self.flags & (1 << p::FlagDebugLowerHex as u32) != 0
+    }
+
+    /// Whether `Debug` output should use upper-case hexadecimal, as with `X?`.
+    #[inline]
+    pub fn debug_upper_hex(&self) -> bool {
+        self.flags & (1 << p::FlagDebugUpperHex as u32) != 0
+    }
+
+    /// Whether the `_` flag, requesting digit grouping, was specified.
+    #[inline]
+    pub fn grouping(&self) -> bool {
+        self.grouping
+    }
+
+    /// The extension directive, such as `style(red)`, as its name and its
+    /// unparsed arguments.
+    #[inline]
+    pub fn directive(&self) -> Option<(&'a str, &'a str)> {
+        self.directive
+    }
+
+    /// The name of the argument supplying the fill character, as with
+    /// `{:pad$>5}`.
+    #[inline]
+    pub fn fill_name(&self) -> Option<&'a str> {
+        self.fill_name
+    }
+
+    /// The name of the formatting trait requested, such as `"Debug"`.
+    ///
+    /// Returns `None` if the specifier does not name a known trait. A
+    /// `join(...)` directive requires `Join` in place of `Display`.
+    #[inline]
+    pub fn format_trait(&self) -> Option<&'static str> {
+        match (self.trait_, self.directive) {
+            (Some(kind), _) => Some(erase::kind_spec(kind).1),
+            (None, Some(("join", _))) if self.ty.is_empty() => Some(transform::JOIN_TRAIT),
+            (None, _) => erase::trait_name(self.ty),
+        }
+    }
+}
+
+/// A `Piece` which owns its text, and so may outlive the format string.
+///
+/// Unlike a `Piece`, an `OwnedPiece` may always be deserialized, including
+/// from text containing escapes. Use `as_piece` to view it as a `Piece`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct OwnedPiece(OwnedRepr);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+enum OwnedRepr {
+    Literal(String),
+    Argument {
+        position: OwnedPosition,
+        format: OwnedFormatSpec,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+enum OwnedPosition {
+    Next(usize),
+    Index(usize),
+    FromEnd(usize),
+    Named(String),
+    Quoted(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+enum OwnedCount {
+    Is(usize),
+    Param(usize),
+    Named(String),
+    Implied,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+struct OwnedFormatSpec {
+    fill: Option<char>,
+    align: Alignment,
+    width: OwnedCount,
+    precision: OwnedCount,
+    ty: String,
+    trait_: Option<FormatTraitKind>,
+    flags: u32,
+    grouping: bool,
+    directive: Option<(String, String)>,
+    fill_name: Option<String>,
+}
+
+impl OwnedPiece {
+    /// View this piece as a `Piece` borrowing its text.
+    pub fn as_piece(&self) -> Piece {
+        match self.0 {
+            OwnedRepr::Literal(ref text) => Piece::Literal(text),
+            OwnedRepr::Argument { ref position, ref format } => Piece::Argument {
+                position: match *position {
+                    OwnedPosition::Next(idx) => Position::Next(idx),
+                    OwnedPosition::Index(idx) => Position::Index(idx),
+                    OwnedPosition::FromEnd(back) => Position::FromEnd(back),
+                    OwnedPosition::Named(ref name) => Position::Named(name),
+                    OwnedPosition::Quoted(ref name) => Position::Quoted(name),
+                },
+                format: FormatSpec {
+                    fill: format.fill,
+                    align: format.align,
+                    width: format.width.as_count(),
+                    precision: format.precision.as_count(),
+                    ty: &format.ty,
+                    trait_: format.trait_,
+                    flags: format.flags,
+                    grouping: format.grouping,
+                    directive: format.directive.as_ref().map(|&(ref name, ref args)| {
+                        (&name[..], &args[..])
+                    }),
+                    fill_name: format.fill_name.as_ref().map(|name| &name[..]),
+                },
+            },
+        }
+    }
+}
+
+impl<'a> From<Piece<'a>> for OwnedPiece {
+    fn from(piece: Piece<'a>) -> OwnedPiece {
+        OwnedPiece(match piece {
+            Piece::Literal(text) => OwnedRepr::Literal(text.into()),
+            Piece::Argument { position, format } => OwnedRepr::Argument {
+                position: match position {
+                    Position::Next(idx) => OwnedPosition::Next(idx),
+                    Position::Index(idx) => OwnedPosition::Index(idx),
+                    Position::FromEnd(back) => OwnedPosition::FromEnd(back),
+                    Position::Named(name) => OwnedPosition::Named(name.into()),
+                    Position::Quoted(name) => OwnedPosition::Quoted(name.into()),
+                },
+                format: OwnedFormatSpec {
+                    fill: format.fill,
+                    align: format.align,
+                    width: OwnedCount::from(format.width),
+                    precision: OwnedCount::from(format.precision),
+                    ty: format.ty.into(),
+                    trait_: format.trait_,
+                    flags: format.flags,
+                    grouping: format.grouping,
+                    directive: format.directive.map(|(name, args)| (name.into(), args.into())),
+                    fill_name: format.fill_name.map(String::from),
+                },
+            },
+        })
+    }
+}
+
+impl OwnedCount {
+    fn as_count(&self) -> Count {
+        match *self {
+            OwnedCount::Is(val) => Count::Is(val),
+            OwnedCount::Param(idx) => Count::Param(idx),
+            OwnedCount::Named(ref name) => Count::Named(name),
+            OwnedCount::Implied => Count::Implied,
+        }
+    }
+}
+
+impl<'a> From<Count<'a>> for OwnedCount {
+    fn from(count: Count<'a>) -> OwnedCount {
+        match count {
+            Count::Is(val) => OwnedCount::Is(val),
+            Count::Param(idx) => OwnedCount::Param(idx),
+            Count::Named(name) => OwnedCount::Named(name.into()),
+            Count::Implied => OwnedCount::Implied,
+        }
+    }
+}
+
+/// Parse a format string into its constituent pieces.
+///
+/// Only the syntax of the format string is checked; since no arguments are
+/// provided, references to them are not validated.
+pub fn parse(spec: &str) -> Result<Vec<Piece>, Error> {
+    let mut parser = p::Parser::new(spec);
+    let pieces = parser.by_ref().map(piece).collect();
+    if parser.errors.is_empty() {
+        Ok(pieces)
+    } else {
+        Err(Error::BadSyntax(parser.errors))
+    }
+}
+
+/// Lazily parse a format string into its constituent pieces.
+///
+/// Unlike `parse`, no `Vec` is built, and the format string is scanned only as
+/// far as the pieces are consumed. Upon finding invalid syntax, the iterator
+/// yields a `BadSyntax` error and then ends.
+pub fn pieces(spec: &str) -> Pieces {
+    Pieces {
+        parser: p::Parser::new(spec),
+        done: false,
+    }
+}
+
+/// An iterator over the pieces of a format string, created by `pieces`.
+pub struct Pieces<'a> {
+    parser: p::Parser<'a>,
+    done: bool,
+}
+
+impl<'a> Iterator for Pieces<'a> {
+    type Item = Result<Piece<'a>, Error<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let next = self.parser.next();
+        if !self.parser.errors.is_empty() {
+            self.done = true;
+            let errors = mem::replace(&mut self.parser.errors, Vec::new());
+            return Some(Err(Error::BadSyntax(errors)));
+        }
+        next.map(|p| Ok(piece(p)))
+    }
+}
+
+/// List the formatting trait required by each argument reference in a format
+/// string, in order of appearance.
+///
+/// Together with `FormatArgs::get_child`, this allows every unsatisfied trait
+/// in a format string to be reported at once. Widths and precisions are not
+/// included, since they require a `usize` rather than a trait. Fails with
+/// `NoSuchFormat` if a specifier does not name a known trait.
+pub fn required_traits(spec: &str) -> Result<Vec<(Position, &'static str)>, Error> {
+    let mut required = Vec::new();
+    for piece in parse(spec)? {
+        if let Piece::Argument { position, format } = piece {
+            match format.format_trait() {
+                Some(name) => required.push((position, name)),
+                None => return Err(Error::NoSuchFormat(format.ty)),
+            }
+        }
+    }
+    Ok(required)
+}
+
+/// List every name a format string refers to, in order of first appearance
+/// and without repeats.
+///
+/// Names used as fills, widths, and precisions are included along with those
+/// of the arguments themselves, and dotted paths such as `{user.name}` are
+/// listed whole. Positional references are ignored. A quoted name is listed
+/// as the text between its quotes, in which escapes have not been resolved.
+pub fn referenced_names(spec: &str) -> Result<Vec<&str>, Error> {
+    let mut names = Vec::new();
+    {
+        let mut push = |name| {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        };
+        for piece in parse(spec)? {
+            if let Piece::Argument { position, format } = piece {
+                match position {
+                    Position::Named(name) | Position::Quoted(name) => push(name),
+                    _ => {}
+                }
+                if let Some(name) = format.fill_name {
+                    push(name);
+                }
+                for count in &[format.width, format.precision] {
+                    if let Count::Named(name) = *count {
+                        push(name);
+                    }
+                }
+            }
+        }
+    }
+    Ok(names)
+}
+
+fn piece(piece: p::Piece) -> Piece {
+    match piece {
+        p::Piece::String(text) => Piece::Literal(text),
+        p::Piece::NextArgument(arg) => Piece::Argument {
+            position: arg.position.into(),
+            format: FormatSpec {
+                fill: arg.format.fill,
+                align: match arg.format.align {
+                    p::AlignLeft => Alignment::Left,
+                    p::AlignRight => Alignment::Right,
+                    p::AlignCenter => Alignment::Center,
+                    p::AlignUnknown => Alignment::Unknown,
+                },
+                width: count(arg.format.width),
+                precision: count(arg.format.precision),
+                ty: arg.format.ty,
+                trait_: arg.format.trait_,
+                flags: arg.format.flags,
+                grouping: arg.format.grouping,
+                directive: arg.format.directive,
+                fill_name: arg.format.fill_name,
+            },
+        },
+    }
+}
+
+impl<'a> From<p::Position<'a>> for Position<'a> {
+    fn from(position: p::Position<'a>) -> Position<'a> {
+        match position {
+            p::Position::ArgumentImplicitlyIs(idx) => Position::Next(idx),
+            p::Position::ArgumentIs(idx) => Position::Index(idx),
+            p::Position::ArgumentFromEnd(back) => Position::FromEnd(back),
+            p::Position::ArgumentNamed(name) => Position::Named(name),
+            p::Position::ArgumentQuoted(name) => Position::Quoted(name),
+        }
+    }
+}
+
+fn count(count: p::Count) -> Count {
+    match count {
+        p::CountIs(val) => Count::Is(val),
+        p::CountIsParam(idx) => Count::Param(idx),
+        p::CountIsName(name) => Count::Named(name),
+        p::CountImplied => Count::Implied,
+    }
+}
+
+impl<'a> From<Piece<'a>> for p::Piece<'a> {
+    fn from(piece: Piece<'a>) -> p::Piece<'a> {
+        match piece {
+            Piece::Literal(text) => p::Piece::String(text),
+            Piece::Argument { position, format } => p::Piece::NextArgument(p::Argument {
+                position: match position {
+                    Position::Next(idx) => p::Position::ArgumentImplicitlyIs(idx),
+                    Position::Index(idx) => p::Position::ArgumentIs(idx),
+                    Position::FromEnd(back) => p::Position::ArgumentFromEnd(back),
+                    Position::Named(name) => p::Position::ArgumentNamed(name),
+                    Position::Quoted(name) => p::Position::ArgumentQuoted(name),
+                },
+                format: p::FormatSpec {
+                    fill: format.fill,
+                    fill_name: format.fill_name,
+                    align: match format.align {
+                        Alignment::Left => p::AlignLeft,
+                        Alignment::Right => p::AlignRight,
+                        Alignment::Center => p::AlignCenter,
+                        Alignment::Unknown => p::AlignUnknown,
+                    },
+                    flags: format.flags,
+                    precision: count_back(format.precision),
+                    width: count_back(format.width),
+                    grouping: format.grouping,
+                    directive: format.directive,
+                    ty: format.ty,
+                    trait_: format.trait_,
+                },
+            }),
+        }
+    }
+}
+
+fn count_back(count: Count) -> p::Count {
+    match count {
+        Count::Is(val) => p::CountIs(val),
+        Count::Param(idx) => p::CountIsParam(idx),
+        Count::Named(name) => p::CountIsName(name),
+        Count::Implied => p::CountImplied,
+    }
+}
@@ -0,0 +1,131 @@
+//! A cache of prepared format strings.
+use std::collections::HashMap;
+use std::string::String;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use {Error, FormatArgs, PreparedFormat};
+
+struct Entry<T: FormatArgs> {
+    prepared: Arc<PreparedFormat<'static, T>>,
+    // The value of `FormatCache::clock` when this entry was last used.
+    used: usize,
+}
+
+/// A cache of `PreparedFormat`s, keyed by name and prepared on first use.
+///
+/// The cache may be shared between threads, such as behind an `Arc`. It is
+/// unbounded unless created with `with_limit`, in which case the least
+/// recently used entry is evicted to make room for a new one.
+pub struct FormatCache<T: FormatArgs> {
+    entries: Mutex<HashMap<String, Entry<T>>>,
+    limit: Option<usize>,
+    clock: AtomicUsize,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+/// Counts of the lookups made in a `FormatCache`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub struct CacheStats {
+    /// Lookups which found a prepared format.
+    pub hits: usize,
+    /// Lookups which had to prepare one.
+    pub misses: usize,
+}
+
+impl<T: FormatArgs> FormatCache<T> {
+    /// Create an empty, unbounded cache.
+    pub fn new() -> Self {
+        FormatCache {
+            entries: Mutex::new(HashMap::new()),
+            limit: None,
+            clock: AtomicUsize::new(0),
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+        }
+    }
+
+    /// Create an empty cache which holds at most `limit` entries.
+    ///
+    /// Panics if `limit` is zero.
+    pub fn with_limit(limit: usize) -> Self {
+        assert!(limit > 0, "a FormatCache must be able to hold an entry");
+        FormatCache { limit: Some(limit), ..FormatCache::new() }
+    }
+
+    /// Get the format cached under `key`, first preparing `spec` and caching
+    /// the result if there is none.
+    ///
+    /// `spec` is only consulted on a miss, so it should be the same whenever
+    /// the same key is used. If several threads miss on the same key at
+    /// once, each prepares `spec` but only the first result is kept.
+    pub fn get<'s>(&self, key: &str, spec: &'s str)
+        -> Result<Arc<PreparedFormat<'static, T>>, Error<'s>>
+    {
+        let now = self.clock.fetch_add(1, Ordering::Relaxed);
+        if let Some(entry) = self.entries.lock().unwrap().get_mut(key) {
+            entry.used = now;
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(entry.prepared.clone());
+        }
+
+        // Prepare without holding the lock, so that other keys are not held
+        // up by a slow or large format string.
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let prepared = Arc::new(PreparedFormat::<T>::prepare(spec)?.into_owned());
+
+        let mut entries = self.entries.lock().unwrap();
+        if !entries.contains_key(key) {
+            if let Some(limit) = self.limit {
+                if entries.len() >= limit {
+                    let oldest = entries.iter()
+                        .min_by_key(|&(_, entry)| entry.used)
+                        .map(|(key, _)| key.clone());
+                    if let Some(oldest) = oldest {
+                        entries.remove(&oldest);
+                    }
+                }
+            }
+        }
+        let entry = entries.entry(String::from(key)).or_insert(Entry {
+            prepared: prepared,
+            used: now,
+        });
+        Ok(entry.prepared.clone())
+    }
+
+    /// Format a value with the format cached under `key`, preparing `spec`
+    /// if there is none.
+    pub fn format<'s>(&self, key: &str, spec: &'s str, t: &T) -> Result<String, Error<'s>> {
+        self.get(key, spec).map(|prepared| prepared.format(t))
+    }
+
+    /// The number of entries currently cached.
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    /// Whether no entries are currently cached.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Remove every entry from the cache. The statistics are kept.
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear()
+    }
+
+    /// The number of hits and misses so far.
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl<T: FormatArgs> Default for FormatCache<T> {
+    fn default() -> Self {
+        FormatCache::new()
+    }
+}
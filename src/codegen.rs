@@ -1,10 +1,16 @@
 //! Support for the codegen module.
 #![doc(hidden)]
 
-use std::mem::{size_of, zeroed};
 use std::fmt::*;
+use std::boxed::Box;
+use std::string::String;
+use std::vec::Vec;
 
 /// Implementors correspond to formatting traits which may apply to values.
+///
+/// Implemented here for each of the standard formatting traits. Other
+/// implementations may be registered under a name with
+/// `PrepareOptions::custom_trait`.
 pub trait FormatTrait {
     /// Return whether this format trait is applicable to a type.
     #[inline]
@@ -57,80 +63,332 @@ macro_rules! impl_format_trait {
     }
 }
 
-impl_format_trait! {
-    Display, Debug, LowerExp, UpperExp, Octal, Pointer, Binary, LowerHex,
-    UpperHex,
+/// Sequences whose elements may be formatted in turn by `join(...)`.
+///
+/// Elements are formatted with `Display`, separated by the text which the
+/// directive requests.
+pub trait Join {
+    /// Format each element, writing `separator` between them.
+    fn fmt(&self, separator: &str, f: &mut Formatter) -> Result;
 }
 
-#[inline]
-fn get_formatter<T, F: FormatTrait + ?Sized>()
-    -> Option<impl Fn(&T, &mut Formatter) -> Result>
+impl<T: Display> Join for [T] {
+    fn fmt(&self, separator: &str, f: &mut Formatter) -> Result {
+        for (i, element) in self.iter().enumerate() {
+            if i > 0 {
+                f.write_str(separator)?;
+            }
+            Display::fmt(element, f)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: Display> Join for Vec<T> {
+    #[inline]
+    fn fmt(&self, separator: &str, f: &mut Formatter) -> Result {
+        Join::fmt(&self[..], separator, f)
+    }
+}
+
+impl<'a, T: Join + ?Sized> Join for &'a T {
+    #[inline]
+    fn fmt(&self, separator: &str, f: &mut Formatter) -> Result {
+        Join::fmt(&**self, separator, f)
+    }
+}
+
+macro_rules! join_arrays {
+    ($($len:expr,)*) => {
+        $(
+            impl<T: Display> Join for [T; $len] {
+                #[inline]
+                fn fmt(&self, separator: &str, f: &mut Formatter) -> Result {
+                    Join::fmt(&self[..], separator, f)
+                }
+            }
+        )*
+    }
+}
+
+join_arrays! {
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16,
+    17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31, 32,
+}
+
+/// Numbers which may be formatted as a percentage by `{:%}`.
+///
+/// The value is multiplied by 100 and followed by `%`. The precision applies
+/// to the scaled value, so that `{:.1%}` formats 0.1234 as `12.3%`.
+pub trait Percent {
+    /// Format the value multiplied by 100, followed by `%`.
+    fn fmt(&self, f: &mut Formatter) -> Result;
+}
+
+/// Numbers which may be formatted in per mille by `{:permille}`.
+///
+/// The value is multiplied by 1000 and followed by `‰`, with the precision
+/// applying to the scaled value as for `Percent`.
+pub trait Permille {
+    /// Format the value multiplied by 1000, followed by `‰`.
+    fn fmt(&self, f: &mut Formatter) -> Result;
+}
+
+// Write the magnitude of a scaled value followed by `suffix`, padded as an
+// integer would be so that the sign and `0` flags behave as they do for
+// numbers. The magnitude is formatted in the type it was scaled in, so that
+// an `f32` is not widened to an `f64` and printed with spurious digits.
+fn scaled<N: Display>(magnitude: N, is_nonnegative: bool, suffix: &str, f: &mut Formatter)
+    -> Result
 {
-    if F::allowed::<T>() {
-        Some(F::perform::<T>)
-    } else {
-        None
+    let mut buf = String::new();
+    match f.precision() {
+        Some(precision) => write!(buf, "{:.*}", precision, magnitude)?,
+        None => write!(buf, "{}", magnitude)?,
+    }
+    buf.push_str(suffix);
+    f.pad_integral(is_nonnegative, "", &buf)
+}
+
+// Specialization abuse to select only numeric primitives and references to
+// them.
+trait SpecF64 {
+    #[inline]
+    fn is_number() -> bool;
+    #[inline]
+    fn as_f64(&self) -> Option<f64>;
+}
+
+impl<T: ?Sized> SpecF64 for T {
+    #[inline]
+    default fn is_number() -> bool { false }
+    #[inline]
+    default fn as_f64(&self) -> Option<f64> { None }
+}
+
+impl<'a, T: ?Sized> SpecF64 for &'a T {
+    #[inline]
+    fn is_number() -> bool { <T as SpecF64>::is_number() }
+    #[inline]
+    fn as_f64(&self) -> Option<f64> { <T as SpecF64>::as_f64(*self) }
+}
+
+// The numeric primitives, which convert to `f64` and may be scaled in the
+// given floating-point type.
+macro_rules! numbers {
+    ($($ty:ty => $scale:ty,)*) => {
+        $(
+            impl SpecF64 for $ty {
+                #[inline]
+                fn is_number() -> bool { true }
+                #[inline]
+                fn as_f64(&self) -> Option<f64> { Some(*self as f64) }
+            }
+
+            impl Percent for $ty {
+                #[inline]
+                fn fmt(&self, f: &mut Formatter) -> Result {
+                    let value = *self as $scale * 100.0;
+                    scaled(value.abs(), !(value < 0.0), "%", f)
+                }
+            }
+
+            impl Permille for $ty {
+                #[inline]
+                fn fmt(&self, f: &mut Formatter) -> Result {
+                    let value = *self as $scale * 1000.0;
+                    scaled(value.abs(), !(value < 0.0), "\u{2030}", f)
+                }
+            }
+        )*
+    }
+}
+
+numbers! {
+    f32 => f32, f64 => f64, i8 => f64, i16 => f64, i32 => f64, i64 => f64, isize => f64,
+    u8 => f64, u16 => f64, u32 => f64, u64 => f64, usize => f64,
+}
+
+/// Return whether `T` is a numeric primitive, or a reference to one, and so
+/// may be viewed with `as_f64`.
+#[inline]
+pub fn is_number<T: ?Sized>() -> bool {
+    <T as SpecF64>::is_number()
+}
+
+/// Convert a number to an `f64`, rounding if it cannot be represented
+/// exactly. Returns `Some` only when `is_number::<T>()`.
+#[inline]
+pub fn as_f64<T: ?Sized>(t: &T) -> Option<f64> {
+    <T as SpecF64>::as_f64(t)
+}
+
+impl<'a, T: Percent + ?Sized> Percent for &'a T {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        Percent::fmt(&**self, f)
+    }
+}
+
+impl<'a, T: Permille + ?Sized> Permille for &'a T {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        Permille::fmt(&**self, f)
+    }
+}
+
+/// Byte sequences which may be formatted as a hex dump by `{:hexdump}`.
+///
+/// Each line holds an offset, the bytes in hexadecimal, and the bytes again
+/// as ASCII, with `.` standing in for those which are not printable. The
+/// precision sets the number of bytes per line, which is 16 by default.
+pub trait HexDump {
+    /// Format the bytes as a hex dump.
+    fn fmt(&self, f: &mut Formatter) -> Result;
+}
+
+impl<T: AsRef<[u8]> + ?Sized> HexDump for T {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let bytes = self.as_ref();
+        let per_line = match f.precision() {
+            None | Some(0) => 16,
+            Some(n) => n,
+        };
+        for (i, line) in bytes.chunks(per_line).enumerate() {
+            if i > 0 {
+                f.write_str("\n")?;
+            }
+            write!(f, "{:08x} ", i * per_line)?;
+            for col in 0..per_line {
+                match line.get(col) {
+                    Some(byte) => write!(f, " {:02x}", byte)?,
+                    None => f.write_str("   ")?,
+                }
+            }
+            f.write_str("  |")?;
+            for &byte in line {
+                f.write_char(if byte >= 0x20 && byte < 0x7f { byte as char } else { '.' })?;
+            }
+            f.write_str("|")?;
+        }
+        Ok(())
     }
 }
 
+/// Text which may be written verbatim by `{:raw}`.
+///
+/// The text is padded and truncated as a `str` is, but is otherwise written
+/// unchanged, without passing through `Display`.
+pub trait Raw {
+    /// Write the text.
+    fn fmt(&self, f: &mut Formatter) -> Result;
+}
+
+impl<T: AsRef<str> + ?Sized> Raw for T {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        f.pad(self.as_ref())
+    }
+}
+
+impl_format_trait! {
+    Display, Debug, LowerExp, UpperExp, Octal, Pointer, Binary, LowerHex,
+    UpperHex, Percent, Permille, HexDump, Raw,
+}
+
+/// A child of values of type `A`, such as a field, named by a type rather
+/// than by a closure.
+///
+/// As the child is reached through an associated function, `combine` can
+/// build a function pointer to format it without needing any value of the
+/// implementing type, which is typically an empty struct local to the
+/// `get_child` arm that uses it.
+pub trait Child<A> {
+    /// The type of the child.
+    type Target;
+
+    /// Return the child of `a`, or `None` if `a` has none at present, as for
+    /// an enum variant other than the one the child belongs to.
+    fn get(a: &A) -> Option<&Self::Target>;
+}
+
+// The combined function which will be returned by `combine`.
 #[inline]
-// The combined function which will be returned by `make_combined`.
-fn combined<A, B, LHS, RHS>(a: &A, f: &mut Formatter) -> Result
-    where LHS: Fn(&A) -> &B, RHS: Fn(&B, &mut Formatter) -> Result
+fn combined<A, C, F>(a: &A, f: &mut Formatter) -> Result
+    where C: Child<A>, F: FormatTrait + ?Sized
 {
-    let lhs = unsafe { zeroed::<LHS>() };
-    let rhs = unsafe { zeroed::<RHS>() };
-    rhs(lhs(a), f)
+    match C::get(a) {
+        Some(b) => F::perform::<C::Target>(b, f),
+        None => Ok(()),
+    }
 }
 
 // Local type alias for the formatting function pointer type.
 type FormatFn<T> = fn(&T, &mut Formatter) -> Result;
 
-// Accepts dummy arguments to allow type parameter inference, and returns
-// `combined` instantiated with those arguments.
+/// Return a function pointer which will find the child `C` of a `&A` and
+/// format it with the given trait. A missing child formats as nothing.
+///
+/// Returns `None` if the formatting trait is not applicable to the child.
 #[inline]
-fn make_combined<A, B, LHS, RHS>(_: LHS, _: RHS) -> FormatFn<A>
-    where LHS: Fn(&A) -> &B, RHS: Fn(&B, &mut Formatter) -> Result
+pub fn combine<F, A, C>() -> Option<FormatFn<A>>
+    where F: FormatTrait + ?Sized, C: Child<A>
 {
-    // check that both function
-    assert!(size_of::<LHS>() == 0,
-        "Mapper from parent to child must be zero-sized, instead size was {}",
-        size_of::<LHS>());
-    assert!(size_of::<RHS>() == 0,
-        "Formatting function must be zero-sized, instead size was {}",
-        size_of::<RHS>());
-    combined::<A, B, LHS, RHS>
-}
-
-/// Combine a function from `&A` to `&B` and a formatting trait applicable to
-/// `B` and return a function pointer which will convert a `&A` to `&B` and
-/// then format it with the given trait.
+    if F::allowed::<C::Target>() {
+        Some(combined::<A, C, F>)
+    } else {
+        None
+    }
+}
+
+/// As `combine`, but the child is found by `func`, which may capture state
+/// such as an index.
+///
+/// The mapper is moved into the returned box, which costs an allocation up
+/// front and an indirect call whenever the result is used. The result cannot
+/// be used where a function pointer is required, as in
+/// `FormatArgs::get_child`; prefer `combine` where possible.
 ///
 /// Returns `None` if the formatting trait is not applicable to `B`.
-/// Panics if `func` is not zero-sized.
 #[inline]
-pub fn combine<F, A, B, Func>(func: Func)
-    -> Option<FormatFn<A>>
-    where F: FormatTrait + ?Sized, Func: Fn(&A) -> &B
+pub fn combine_boxed<'a, F, A, B, Func>(func: Func)
+    -> Option<Box<Fn(&A, &mut Formatter) -> Result + 'a>>
+    where F: FormatTrait + ?Sized, Func: Fn(&A) -> &B + 'a
 {
-    // Combines `get_formatter` and `make_combined` in one.
-    get_formatter::<B, F>().map(|r| make_combined(func, r))
+    if F::allowed::<B>() {
+        Some(Box::new(move |a: &A, f: &mut Formatter| F::perform::<B>(func(a), f)))
+    } else {
+        None
+    }
 }
 
-// Specialization abuse to select only functions which return `&usize`.
+// Specialization abuse to select only functions which return `&usize`, and
+// only values which are `usize`.
 trait SpecUsize {
     #[inline]
     fn convert<T>(f: fn(&T) -> &Self) -> Option<fn(&T) -> &usize>;
+    #[inline]
+    fn is_usize() -> bool;
+    #[inline]
+    fn usize_of(&self) -> Option<&usize>;
 }
 
 impl<U> SpecUsize for U {
     #[inline]
     default fn convert<T>(_: fn(&T) -> &Self) -> Option<fn(&T) -> &usize> { None }
+    #[inline]
+    default fn is_usize() -> bool { false }
+    #[inline]
+    default fn usize_of(&self) -> Option<&usize> { None }
 }
 
 impl SpecUsize for usize {
     #[inline]
     fn convert<T>(f: fn(&T) -> &usize) -> Option<fn(&T) -> &usize> { Some(f) }
+    #[inline]
+    fn is_usize() -> bool { true }
+    #[inline]
+    fn usize_of(&self) -> Option<&usize> { Some(self) }
 }
 
 /// Attempt to convert a function from `&A` to `&B` to a function from `&A`
@@ -140,10 +398,238 @@ pub fn as_usize<A, B>(f: fn(&A) -> &B) -> Option<fn(&A) -> &usize> {
     <B as SpecUsize>::convert::<A>(f)
 }
 
+/// Return whether `T` is `usize`, and so may be viewed with `usize_of`.
+#[inline]
+pub fn is_usize<T>() -> bool {
+    <T as SpecUsize>::is_usize()
+}
+
+/// View a value as a `&usize`. Returns `Some` only when `is_usize::<T>()`.
+///
+/// Unlike `as_usize`, this applies to a value rather than a function, and so
+/// suits a value which may be absent, such as a field of an enum variant.
+#[inline]
+pub fn usize_of<T>(t: &T) -> Option<&usize> {
+    <T as SpecUsize>::usize_of(t)
+}
+
+// Specialization abuse to select only functions which return `&char`, and
+// only values which are `char`.
+trait SpecChar {
+    #[inline]
+    fn convert<T>(f: fn(&T) -> &Self) -> Option<fn(&T) -> &char>;
+    #[inline]
+    fn is_char() -> bool;
+    #[inline]
+    fn char_of(&self) -> Option<&char>;
+}
+
+impl<U> SpecChar for U {
+    #[inline]
+    default fn convert<T>(_: fn(&T) -> &Self) -> Option<fn(&T) -> &char> { None }
+    #[inline]
+    default fn is_char() -> bool { false }
+    #[inline]
+    default fn char_of(&self) -> Option<&char> { None }
+}
+
+impl SpecChar for char {
+    #[inline]
+    fn convert<T>(f: fn(&T) -> &char) -> Option<fn(&T) -> &char> { Some(f) }
+    #[inline]
+    fn is_char() -> bool { true }
+    #[inline]
+    fn char_of(&self) -> Option<&char> { Some(self) }
+}
+
+/// Attempt to convert a function from `&A` to `&B` to a function from `&A`
+/// to `&char`. Returns `Some` only when `B` is `char`.
+#[inline]
+pub fn as_char<A, B>(f: fn(&A) -> &B) -> Option<fn(&A) -> &char> {
+    <B as SpecChar>::convert::<A>(f)
+}
+
+/// Return whether `T` is `char`, and so may be viewed with `char_of`.
+#[inline]
+pub fn is_char<T>() -> bool {
+    <T as SpecChar>::is_char()
+}
+
+/// View a value as a `&char`. Returns `Some` only when `is_char::<T>()`.
+#[inline]
+pub fn char_of<T>(t: &T) -> Option<&char> {
+    <T as SpecChar>::char_of(t)
+}
+
+// Specialization abuse to select only strings and references to them.
+trait SpecStr {
+    #[inline]
+    fn is_str() -> bool;
+    #[inline]
+    fn as_str(&self) -> Option<&str>;
+}
+
+impl<T: ?Sized> SpecStr for T {
+    #[inline]
+    default fn is_str() -> bool { false }
+    #[inline]
+    default fn as_str(&self) -> Option<&str> { None }
+}
+
+impl SpecStr for str {
+    #[inline]
+    fn is_str() -> bool { true }
+    #[inline]
+    fn as_str(&self) -> Option<&str> { Some(self) }
+}
+
+impl SpecStr for String {
+    #[inline]
+    fn is_str() -> bool { true }
+    #[inline]
+    fn as_str(&self) -> Option<&str> { Some(self) }
+}
+
+impl<'a, T: ?Sized> SpecStr for &'a T {
+    #[inline]
+    fn is_str() -> bool { <T as SpecStr>::is_str() }
+    #[inline]
+    fn as_str(&self) -> Option<&str> { <T as SpecStr>::as_str(*self) }
+}
+
+/// Return whether `T` is `str`, `String`, or a reference to one of them, and
+/// so may be viewed with `as_str`.
+#[inline]
+pub fn is_str<T: ?Sized>() -> bool {
+    <T as SpecStr>::is_str()
+}
+
+/// View a value as a `&str`. Returns `Some` only when `is_str::<T>()`.
+#[inline]
+pub fn as_str<T: ?Sized>(t: &T) -> Option<&str> {
+    <T as SpecStr>::as_str(t)
+}
+
+// Specialization abuse to select only types which implement `DynFormatArgs`.
+trait SpecDyn {
+    #[inline]
+    fn as_dyn(&self) -> Option<&DynFormatArgs>;
+}
+
+impl<T> SpecDyn for T {
+    #[inline]
+    default fn as_dyn(&self) -> Option<&DynFormatArgs> { None }
+}
+
+impl<T: DynFormatArgs> SpecDyn for T {
+    #[inline]
+    fn as_dyn(&self) -> Option<&DynFormatArgs> { Some(self) }
+}
+
+/// View a value as `DynFormatArgs`. Returns `Some` only when its type
+/// implements that trait.
+#[inline]
+pub fn as_dyn<T>(t: &T) -> Option<&DynFormatArgs> {
+    <T as SpecDyn>::as_dyn(t)
+}
+
+// Specialization abuse to select only types which implement `Join`.
+trait SpecJoin {
+    #[inline]
+    fn is_join() -> bool;
+    #[inline]
+    fn as_join(&self) -> Option<&Join>;
+}
+
+impl<T> SpecJoin for T {
+    #[inline]
+    default fn is_join() -> bool { false }
+    #[inline]
+    default fn as_join(&self) -> Option<&Join> { None }
+}
+
+impl<T: Join> SpecJoin for T {
+    #[inline]
+    fn is_join() -> bool { true }
+    #[inline]
+    fn as_join(&self) -> Option<&Join> { Some(self) }
+}
+
+/// Return whether `T` implements `Join`, and so may be viewed with `as_join`.
+#[inline]
+pub fn is_join<T>() -> bool {
+    <T as SpecJoin>::is_join()
+}
+
+/// View a value as `Join`. Returns `Some` only when `is_join::<T>()`.
+#[inline]
+pub fn as_join<T>(t: &T) -> Option<&Join> {
+    <T as SpecJoin>::as_join(t)
+}
+
+// Specialization abuse to select only floating-point types.
+trait SpecFloat {
+    #[inline]
+    fn is_float() -> bool;
+}
+
+impl<T> SpecFloat for T {
+    #[inline]
+    default fn is_float() -> bool { false }
+}
+
+impl SpecFloat for f32 {
+    #[inline]
+    fn is_float() -> bool { true }
+}
+
+impl SpecFloat for f64 {
+    #[inline]
+    fn is_float() -> bool { true }
+}
+
+impl<'a, T> SpecFloat for &'a T {
+    #[inline]
+    fn is_float() -> bool { <T as SpecFloat>::is_float() }
+}
+
+/// Return whether `T` is a floating-point type, or a reference to one.
+#[inline]
+pub fn is_float<T>() -> bool {
+    <T as SpecFloat>::is_float()
+}
+
+/// An out-of-range index was passed to `FormatArgs::try_get_child`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct IndexError(pub usize);
+
+impl Display for IndexError {
+    fn fmt(&self, fmt: &mut Formatter) -> Result {
+        write!(fmt, "index {} out of range", self.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for IndexError {
+    fn description(&self) -> &str {
+        "out-of-range index"
+    }
+}
+
 /// A trait for types against which formatting specifiers may be pre-checked.
 ///
 /// Implementations may be generated automatically using `runtime-fmt-derive`
-/// and `#[derive(FormatArgs)]`.
+/// and `#[derive(FormatArgs)]`. Implementations are also provided for tuples
+/// of up to twelve elements and arrays of up to sixteen, the elements of which
+/// are referred to by position. `Option<T>` and `Result<T, E>` expose their
+/// contents at index 0, and a `Result`'s error at index 1; an absent value
+/// formats as the empty string. This applies only where the `Option` or
+/// `Result` is itself the argument set, as for a `PreparedFormat`; passed as
+/// a single argument to `rt_format!`, it is formatted with its own traits.
+///
+/// `&T` forwards to `T`, so that references may be formatted as they are,
+/// as with a `PreparedFormat<&T>`. Only the first 32 indices of `T` may be
+/// formatted through a reference.
 pub trait FormatArgs {
     /// Find the index within this type corresponding to the provided name.
     ///
@@ -154,9 +640,34 @@ pub trait FormatArgs {
     /// Validate that a given index is within range for this type.
     ///
     /// If this function returns `true`, `get_child` with the given index must
-    /// not panic.
+    /// not panic. The indices accepted must be exactly those below
+    /// `arg_count`, so that every index from some point on is rejected.
     fn validate_index(index: usize) -> bool;
 
+    /// The number of indices which are in range for this type, counting up
+    /// from zero. Indices counted from the end, such as `{-1}`, are resolved
+    /// against it.
+    fn arg_count() -> usize;
+
+    /// Whether the value at the given index is a floating-point number, the
+    /// decimal point of which may be replaced by a decimal separator.
+    ///
+    /// The default returns `false`. Panics if the index is invalid.
+    #[inline]
+    fn is_float(index: usize) -> bool {
+        let _ = index;
+        false
+    }
+
+    /// The primary name of the value at each index, or `""` for a value which
+    /// may only be referred to by position.
+    ///
+    /// The default returns an empty slice, meaning no names are known.
+    #[inline]
+    fn arg_names() -> &'static [&'static str] {
+        &[]
+    }
+
     /// Return the formatter function for the given format trait, accepting
     /// `&Self` and using the given format trait on the value at that index.
     ///
@@ -164,9 +675,240 @@ pub trait FormatArgs {
     /// that index. Panics if the index is invalid.
     fn get_child<F: FormatTrait + ?Sized>(index: usize) -> Option<FormatFn<Self>>;
 
+    /// As `get_child`, but fails with `IndexError` rather than panicking if
+    /// the index is invalid.
+    ///
+    /// `Ok(None)` means that the index is valid but the given format trait
+    /// cannot format the child there.
+    #[inline]
+    fn try_get_child<F: FormatTrait + ?Sized>(index: usize)
+        -> ::std::result::Result<Option<FormatFn<Self>>, IndexError>
+    {
+        if <Self as FormatArgs>::validate_index(index) {
+            Ok(Self::get_child::<F>(index))
+        } else {
+            Err(IndexError(index))
+        }
+    }
+
     /// Return the value at the given index interpreted as a `usize`.
     ///
     /// Returns `None` if the child at the given index cannot be interpreted
     /// as a `usize`. Panics if the index is invalid.
     fn as_usize(index: usize) -> Option<fn(&Self) -> &usize>;
+
+    /// Return the value at the given index interpreted as a `char`, for use
+    /// as a fill character such as `{:fill$>5}`.
+    ///
+    /// The default returns `None`. Panics if the index is invalid.
+    #[inline]
+    fn as_char(index: usize) -> Option<fn(&Self) -> &char> {
+        let _ = index;
+        None
+    }
+
+    /// Return the value at the given index viewed as a `&str`.
+    ///
+    /// Returns `None` unless the child at the given index is a `str`,
+    /// `String`, or a reference to one. The default returns `None`. Panics
+    /// if the index is invalid.
+    #[inline]
+    fn as_str(index: usize) -> Option<fn(&Self) -> &str> {
+        let _ = index;
+        None
+    }
+
+    /// Return the value at the given index converted to an `f64`.
+    ///
+    /// Returns `None` unless the child at the given index is a numeric
+    /// primitive or a reference to one. The default returns `None`. Panics
+    /// if the index is invalid.
+    #[inline]
+    fn as_f64(index: usize) -> Option<fn(&Self) -> f64> {
+        let _ = index;
+        None
+    }
+
+    /// Return a function viewing the value at the given index as
+    /// `DynFormatArgs`, so that dotted names such as `{user.name}` may
+    /// descend into it.
+    ///
+    /// Either this function or the one it returns may return `None` if the
+    /// child cannot be descended into. The default permits no descent.
+    #[inline]
+    fn get_nested(index: usize) -> Option<fn(&Self) -> Option<&DynFormatArgs>> {
+        let _ = index;
+        None
+    }
+
+    /// Return a function viewing the value at the given index as `Join`, so
+    /// that `join(...)` may format its elements.
+    ///
+    /// Returns `None` if the child at the given index does not implement
+    /// `Join`; the function returned may itself return `None` if the child is
+    /// absent. The default permits no joining. Panics if the index is
+    /// invalid.
+    #[inline]
+    fn get_join(index: usize) -> Option<fn(&Self) -> Option<&Join>> {
+        let _ = index;
+        None
+    }
+}
+
+/// An object-safe counterpart to `FormatArgs`, for values whose type is not
+/// known statically.
+///
+/// This trait is implemented for every type implementing `FormatArgs`, and so
+/// allows differently-typed values to be formatted against the same format
+/// string through `FormatBuf::new_dyn`.
+pub trait DynFormatArgs {
+    /// Find the index within this value corresponding to the provided name.
+    fn validate_name(&self, name: &str) -> Option<usize>;
+
+    /// Validate that a given index is within range for this value.
+    ///
+    /// The indices accepted must be exactly those below `arg_count`, so that
+    /// every index from some point on is rejected.
+    fn validate_index(&self, index: usize) -> bool;
+
+    /// Whether `name`, rejected by `validate_name`, names a member which this
+    /// value knows of but has no value for. The default returns `false`.
+    #[inline]
+    fn is_missing(&self, name: &str) -> bool {
+        let _ = name;
+        false
+    }
+
+    /// The number of indices which are in range for this value, counting up
+    /// from zero. Indices counted from the end, such as `{-1}`, are resolved
+    /// against it.
+    fn arg_count(&self) -> usize;
+
+    /// Produce an argument formatting the child at the given index using the
+    /// formatting trait named by `spec`, such as `"?"`.
+    ///
+    /// Panics if the index is invalid.
+    fn format_child<'a, 's>(&'a self, spec: &'s str, index: usize)
+        -> ::std::result::Result<ArgumentV1<'a>, ::Error<'s>>;
+
+    /// Whether the child at the given index is a floating-point number.
+    ///
+    /// The default returns `false`. Panics if the index is invalid.
+    #[inline]
+    fn is_float(&self, index: usize) -> bool {
+        let _ = index;
+        false
+    }
+
+    /// Return the child at the given index, if it is a `usize`.
+    ///
+    /// Panics if the index is invalid.
+    fn as_usize(&self, index: usize) -> Option<&usize>;
+
+    /// Return the child at the given index, if it is a `char`.
+    ///
+    /// The default returns `None`. Panics if the index is invalid.
+    #[inline]
+    fn as_char(&self, index: usize) -> Option<&char> {
+        let _ = index;
+        None
+    }
+
+    /// Return the child at the given index, if it is a string.
+    ///
+    /// The default returns `None`. Panics if the index is invalid.
+    #[inline]
+    fn as_str(&self, index: usize) -> Option<&str> {
+        let _ = index;
+        None
+    }
+
+    /// Return the child at the given index converted to an `f64`, if it is a
+    /// number.
+    ///
+    /// The default returns `None`. Panics if the index is invalid.
+    #[inline]
+    fn as_f64(&self, index: usize) -> Option<f64> {
+        let _ = index;
+        None
+    }
+
+    /// Return the child at the given index, if dotted names may descend
+    /// into it. The default permits no descent.
+    ///
+    /// Panics if the index is invalid.
+    #[inline]
+    fn nested(&self, index: usize) -> Option<&DynFormatArgs> {
+        let _ = index;
+        None
+    }
+
+    /// Return the child at the given index, if `join(...)` may format its
+    /// elements. The default permits no joining.
+    ///
+    /// Panics if the index is invalid.
+    #[inline]
+    fn joinable(&self, index: usize) -> Option<&Join> {
+        let _ = index;
+        None
+    }
+}
+
+impl<T: FormatArgs> DynFormatArgs for T {
+    #[inline]
+    fn validate_name(&self, name: &str) -> Option<usize> {
+        <T as FormatArgs>::validate_name(name)
+    }
+
+    #[inline]
+    fn validate_index(&self, index: usize) -> bool {
+        <T as FormatArgs>::validate_index(index)
+    }
+
+    #[inline]
+    fn arg_count(&self) -> usize {
+        <T as FormatArgs>::arg_count()
+    }
+
+    #[inline]
+    fn is_float(&self, index: usize) -> bool {
+        <T as FormatArgs>::is_float(index)
+    }
+
+    fn format_child<'a, 's>(&'a self, spec: &'s str, index: usize)
+        -> ::std::result::Result<ArgumentV1<'a>, ::Error<'s>>
+    {
+        ::erase::codegen_get_child::<T>(::erase::trait_kind(spec), spec, index)
+            .map(|(f, _)| ArgumentV1::new(self, f))
+    }
+
+    #[inline]
+    fn as_usize(&self, index: usize) -> Option<&usize> {
+        <T as FormatArgs>::as_usize(index).map(|f| f(self))
+    }
+
+    #[inline]
+    fn as_char(&self, index: usize) -> Option<&char> {
+        <T as FormatArgs>::as_char(index).map(|f| f(self))
+    }
+
+    #[inline]
+    fn as_str(&self, index: usize) -> Option<&str> {
+        <T as FormatArgs>::as_str(index).map(|f| f(self))
+    }
+
+    #[inline]
+    fn as_f64(&self, index: usize) -> Option<f64> {
+        <T as FormatArgs>::as_f64(index).map(|f| f(self))
+    }
+
+    #[inline]
+    fn nested(&self, index: usize) -> Option<&DynFormatArgs> {
+        <T as FormatArgs>::get_nested(index).and_then(|f| f(self))
+    }
+
+    #[inline]
+    fn joinable(&self, index: usize) -> Option<&Join> {
+        <T as FormatArgs>::get_join(index).and_then(|f| f(self))
+    }
 }
@@ -3,8 +3,20 @@ use std::fmt;
 use std::convert::TryFrom;
 use Error;
 
+// The traits corresponding to each format type.
+mod bounds {
+    pub use std::fmt::{Display, Debug, LowerExp, UpperExp, Octal, Pointer, Binary,
+                       LowerHex, UpperHex};
+    pub use codegen::{Percent, Permille, HexDump, Raw};
+}
+
 type Func<T> = fn(&T, &mut fmt::Formatter) -> fmt::Result;
 
+// The format type which selects `Display` if it is implemented and `Debug`
+// otherwise, and the traits reported when neither is.
+const DISP_OR_DEBUG: &'static str = "disp_or_debug";
+const DISP_OR_DEBUG_TRAITS: &'static str = "Display or Debug";
+
 trait AsUsize {
     fn as_usize(&self) -> Option<usize>;
 }
@@ -19,27 +31,54 @@ impl<T> AsUsize for T where usize: TryFrom<T>, T: Copy {
     }
 }
 
-macro_rules! traits {
-    ($($string:pat, $upper:ident, $lower:ident;)*) => {
-        $(
-            trait $upper {
-                fn $lower() -> Option<Func<Self>>;
-            }
-            impl<T> $upper for T {
-                #[inline]
-                default fn $lower() -> Option<Func<Self>> { None }
-            }
-            impl<T: fmt::$upper> $upper for T {
-                #[inline]
-                fn $lower() -> Option<Func<Self>> {
-                    Some(<Self as fmt::$upper>::fmt)
-                }
+trait AsChar {
+    fn as_char(&self) -> Option<char>;
+}
+impl<T> AsChar for T {
+    #[inline]
+    default fn as_char(&self) -> Option<char> { None }
+}
+impl AsChar for char {
+    #[inline]
+    fn as_char(&self) -> Option<char> { Some(*self) }
+}
+
+// A trait with a formatter for every type implementing `bounds::$upper`.
+macro_rules! specialized {
+    ($upper:ident, $lower:ident) => {
+        trait $upper {
+            fn $lower() -> Option<Func<Self>>;
+        }
+        impl<T> $upper for T {
+            #[inline]
+            default fn $lower() -> Option<Func<Self>> { None }
+        }
+        impl<T: bounds::$upper> $upper for T {
+            #[inline]
+            fn $lower() -> Option<Func<Self>> {
+                Some(<Self as bounds::$upper>::fmt)
             }
-        )*
+        }
+    }
+}
+
+macro_rules! traits {
+    (
+        standard { $($sstring:tt, $supper:ident, $slower:ident;)* }
+        extension { $($estring:tt, $eupper:ident, $elower:ident;)* }
+    ) => {
+        $(specialized!($supper, $slower);)*
+        $(specialized!($eupper, $elower);)*
 
         pub trait Format {
             fn as_usize(&self) -> Option<usize>;
+            fn as_char(&self) -> Option<char>;
+            fn as_str(&self) -> Option<&str>;
+            fn as_f64(&self) -> Option<f64>;
             fn by_name<'n>(&self, name: &'n str, idx: usize) -> Result<fmt::ArgumentV1, Error<'n>>;
+            fn as_dyn_args(&self) -> Option<&::DynFormatArgs>;
+            fn as_join(&self) -> Option<&::codegen::Join>;
+            fn is_float(&self) -> bool;
         }
 
         impl<T> Format for T {
@@ -47,49 +86,137 @@ macro_rules! traits {
             fn as_usize(&self) -> Option<usize> {
                 AsUsize::as_usize(self)
             }
+            #[inline]
+            fn as_char(&self) -> Option<char> {
+                AsChar::as_char(self)
+            }
+            #[inline]
+            fn as_str(&self) -> Option<&str> {
+                ::codegen::as_str(self)
+            }
+            #[inline]
+            fn as_f64(&self) -> Option<f64> {
+                ::codegen::as_f64(self)
+            }
             fn by_name<'n>(&self, name: &'n str, idx: usize) -> Result<fmt::ArgumentV1, Error<'n>> {
-                match name {
-                    $(
-                        $string => match <Self as $upper>::$lower() {
-                            Some(f) => Ok(fmt::ArgumentV1::new(self, f)),
-                            None => Err(Error::UnsatisfiedFormat {
-                                idx: idx,
-                                must_implement: stringify!($upper),
-                            }),
-                        },
-                    )*
-                    _ => Err(Error::NoSuchFormat(name)),
+                match erased::<Self>(trait_kind(name), name)? {
+                    (Some(f), _) => Ok(fmt::ArgumentV1::new(self, f)),
+                    (None, must_implement) => Err(Error::UnsatisfiedFormat {
+                        idx: idx,
+                        must_implement: must_implement,
+                    }),
                 }
             }
+            #[inline]
+            fn as_dyn_args(&self) -> Option<&::DynFormatArgs> {
+                ::codegen::as_dyn(self)
+            }
+            #[inline]
+            fn as_join(&self) -> Option<&::codegen::Join> {
+                ::codegen::as_join(self)
+            }
+            #[inline]
+            fn is_float(&self) -> bool {
+                ::codegen::is_float::<T>()
+            }
         }
 
-        pub fn codegen_get_child<'n, T: ::FormatArgs>(name: &'n str, idx: usize)
-            -> Result<fn(&T, &mut fmt::Formatter) -> fmt::Result, Error>
+        // The formatter of `T` for the standard trait `kind`, or failing
+        // that, for the extension type `name`, and the name of its trait.
+        fn erased<'n, T>(kind: Option<FormatTraitKind>, name: &'n str)
+            -> Result<(Option<Func<T>>, &'static str), Error<'n>>
         {
+            Ok(match kind {
+                $(Some(FormatTraitKind::$supper) => {
+                    (<T as $supper>::$slower(), stringify!($supper))
+                })*
+                None => match name {
+                    $($estring => (<T as $eupper>::$elower(), stringify!($eupper)),)*
+                    DISP_OR_DEBUG => {
+                        (<T as Display>::display().or_else(<T as Debug>::debug),
+                         DISP_OR_DEBUG_TRAITS)
+                    }
+                    _ => return Err(Error::NoSuchFormat(name)),
+                },
+            })
+        }
+
+        /// The standard trait requested by the format type `ty`, if any.
+        pub fn trait_kind(ty: &str) -> Option<FormatTraitKind> {
+            match ty {
+                $($sstring => Some(FormatTraitKind::$supper),)*
+                _ => None,
+            }
+        }
+
+        /// The format type requesting a standard trait, and the trait's name.
+        pub fn kind_spec(kind: FormatTraitKind) -> (&'static str, &'static str) {
+            match kind {
+                $(FormatTraitKind::$supper => ($sstring, stringify!($supper)),)*
+            }
+        }
+
+        pub fn trait_name(name: &str) -> Option<&'static str> {
+            if let Some(kind) = trait_kind(name) {
+                return Some(kind_spec(kind).1);
+            }
             match name {
-                $(
-                    $string => match T::get_child::<fmt::$upper>(idx) {
-                        Some(f) => Ok(f),
-                        None => Err(Error::UnsatisfiedFormat {
-                            idx: idx,
-                            must_implement: stringify!($upper),
-                        })
-                    },
-                )*
-                _ => Err(Error::NoSuchFormat(name)),
+                $($estring => Some(stringify!($eupper)),)*
+                DISP_OR_DEBUG => Some(DISP_OR_DEBUG_TRAITS),
+                _ => None,
+            }
+        }
+
+        // The formatter of argument `idx` of `T` for the standard trait
+        // `kind`, or failing that, for the extension type `name`, and the
+        // name of its trait.
+        pub fn codegen_get_child<'n, T: ::FormatArgs>(kind: Option<FormatTraitKind>,
+                                                      name: &'n str, idx: usize)
+            -> Result<(fn(&T, &mut fmt::Formatter) -> fmt::Result, &'static str), Error<'n>>
+        {
+            let (found, must_implement) = match kind {
+                $(Some(FormatTraitKind::$supper) => {
+                    (T::get_child::<bounds::$supper>(idx), stringify!($supper))
+                })*
+                None => match name {
+                    $($estring => {
+                        (T::get_child::<bounds::$eupper>(idx), stringify!($eupper))
+                    })*
+                    DISP_OR_DEBUG => {
+                        let display = T::get_child::<bounds::Display>(idx);
+                        (display.or_else(|| T::get_child::<bounds::Debug>(idx)),
+                         DISP_OR_DEBUG_TRAITS)
+                    }
+                    _ => return Err(Error::NoSuchFormat(name)),
+                },
+            };
+            match found {
+                Some(f) => Ok((f, must_implement)),
+                None => Err(Error::UnsatisfiedFormat {
+                    idx: idx,
+                    must_implement: must_implement,
+                }),
             }
         }
     }
 }
 
 traits! {
-    "", Display, display;
-    "?", Debug, debug;
-    "e", LowerExp, lower_exp;
-    "E", UpperExp, upper_exp;
-    "o", Octal, octal;
-    "p", Pointer, pointer;
-    "b", Binary, binary;
-    "x", LowerHex, lower_hex;
-    "X", UpperHex, upper_hex;
+    standard {
+        "", Display, display;
+        "?", Debug, debug;
+        "e", LowerExp, lower_exp;
+        "E", UpperExp, upper_exp;
+        "o", Octal, octal;
+        "p", Pointer, pointer;
+        "b", Binary, binary;
+        "x", LowerHex, lower_hex;
+        "X", UpperHex, upper_hex;
+    }
+    extension {
+        "%", Percent, percent;
+        "permille", Permille, permille;
+        "hexdump", HexDump, hex_dump;
+        "raw", Raw, raw;
+    }
 }
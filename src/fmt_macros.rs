@@ -33,6 +33,11 @@ pub use self::Count::*;
 use std::str;
 use std::string;
 use std::iter;
+use std::ops;
+use std::borrow::ToOwned;
+use std::vec::Vec;
+use ast::FormatTraitKind;
+use erase;
 
 /// A piece is a portion of the format string which represents the next part
 /// to emit. These are emitted as a stream by the `Parser` class.
@@ -59,6 +64,9 @@ pub struct Argument<'a> {
 pub struct FormatSpec<'a> {
     /// Optionally specified character to fill alignment with
     pub fill: Option<char>,
+    /// The name of an argument from which to take the fill character, as
+    /// with `fill$>`. Not part of the standard syntax.
+    pub fill_name: Option<&'a str>,
     /// Optionally specified alignment
     pub align: Alignment,
     /// Packed version of various flags provided
@@ -67,19 +75,38 @@ pub struct FormatSpec<'a> {
     pub precision: Count<'a>,
     /// The string width requested for the resulting format
     pub width: Count<'a>,
+    /// Whether digits should be grouped, as requested by a `_` following the
+    /// width. Not part of the standard syntax.
+    pub grouping: bool,
+    /// An extension directive and its unparsed arguments, such as
+    /// `("style", "red,bold")` for `style(red,bold)`. It takes the place of
+    /// the type or follows `?`. Not part of the standard syntax.
+    pub directive: Option<(&'a str, &'a str)>,
     /// The descriptor string representing the name of the format desired for
     /// this argument, this can be empty or any number of characters, although
     /// it is required to be one word.
     pub ty: &'a str,
+    /// The standard trait `ty` requests, if any, and if no directive
+    /// overrides it. Not part of the standard syntax.
+    pub trait_: Option<FormatTraitKind>,
 }
 
 /// Enum describing where an argument for a format can be located.
 #[derive(Copy, Clone, PartialEq)]
 pub enum Position<'a> {
+    /// The argument is implied to be located at an index
+    ArgumentImplicitlyIs(usize),
     /// The argument is located at a specific index.
     ArgumentIs(usize),
+    /// The argument is located at a specific index counted back from the end,
+    /// as `{-1}` for the last. Not part of the standard syntax.
+    ArgumentFromEnd(usize),
     /// The argument has a name.
     ArgumentNamed(&'a str),
+    /// The argument has a name written in double quotes, as `{"a:b"}`. This
+    /// is the text between the quotes, in which `\"` and `\\` have not been
+    /// resolved. Not part of the standard syntax.
+    ArgumentQuoted(&'a str),
 }
 
 /// Enum of alignments which are supported.
@@ -109,6 +136,10 @@ pub enum Flag {
     /// For numbers, this means that the number will be padded with zeroes,
     /// and the sign (`+` or `-`) will precede them.
     FlagSignAwareZeroPad,
+    /// For Debug / `?`, format integers in lower-case hexadecimal.
+    FlagDebugLowerHex,
+    /// For Debug / `?`, format integers in upper-case hexadecimal.
+    FlagDebugUpperHex,
 }
 
 /// A count is used for the precision and width parameters of an integer, and
@@ -125,6 +156,18 @@ pub enum Count<'a> {
     CountImplied,
 }
 
+/// An error encountered while parsing, along with the byte range of the input
+/// it applies to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// A description of the problem.
+    pub description: string::String,
+    /// An optional note with further detail.
+    pub note: Option<string::String>,
+    /// The byte range of the input string at which the problem was found.
+    pub span: ops::Range<usize>,
+}
+
 /// The parser structure for interpreting the input format string. This is
 /// modeled as an iterator over `Piece` structures to form a stream of tokens
 /// being output.
@@ -135,7 +178,7 @@ pub struct Parser<'a> {
     input: &'a str,
     cur: iter::Peekable<str::CharIndices<'a>>,
     /// Error messages accumulated during parsing
-    pub errors: Vec<(string::String, Option<string::String>)>,
+    pub errors: Vec<ParseError>,
     /// Current position of implicit positional argument pointer
     curarg: usize,
 }
@@ -152,7 +195,9 @@ impl<'a> Iterator for Parser<'a> {
                         Some(String(self.string(pos + 1)))
                     } else {
                         let ret = Some(NextArgument(self.argument()));
-                        self.must_consume('}');
+                        if self.comment() {
+                            self.must_consume('}');
+                        }
                         ret
                     }
                 }
@@ -163,7 +208,8 @@ impl<'a> Iterator for Parser<'a> {
                     } else {
                         self.err_with_note("unmatched `}` found",
                                            "if you intended to print `}`, \
-                                           you can escape it using `}}`");
+                                           you can escape it using `}}`",
+                                           pos..pos + 1);
                         None
                     }
                 }
@@ -189,15 +235,23 @@ impl<'a> Parser<'a> {
     /// Notifies of an error. The message doesn't actually need to be of type
     /// String, but I think it does when this eventually uses conditions so it
     /// might as well start using it now.
-    fn err(&mut self, msg: &str) {
-        self.errors.push((msg.to_owned(), None));
+    fn err(&mut self, msg: &str, span: ops::Range<usize>) {
+        self.errors.push(ParseError {
+            description: msg.to_owned(),
+            note: None,
+            span: span,
+        });
     }
 
     /// Notifies of an error. The message doesn't actually need to be of type
     /// String, but I think it does when this eventually uses conditions so it
     /// might as well start using it now.
-    fn err_with_note(&mut self, msg: &str, note: &str) {
-        self.errors.push((msg.to_owned(), Some(note.to_owned())));
+    fn err_with_note(&mut self, msg: &str, note: &str, span: ops::Range<usize>) {
+        self.errors.push(ParseError {
+            description: msg.to_owned(),
+            note: Some(note.to_owned()),
+            span: span,
+        });
     }
 
     /// Optionally consumes the specified character. If the character is not at
@@ -220,19 +274,22 @@ impl<'a> Parser<'a> {
     /// found, an error is emitted.
     fn must_consume(&mut self, c: char) {
         self.ws();
-        if let Some(&(_, maybe)) = self.cur.peek() {
+        if let Some(&(pos, maybe)) = self.cur.peek() {
             if c == maybe {
                 self.cur.next();
             } else {
-                self.err(&format!("expected `{:?}`, found `{:?}`", c, maybe));
+                self.err(&format!("expected `{:?}`, found `{:?}`", c, maybe),
+                         pos..pos + maybe.len_utf8());
             }
         } else {
             let msg = &format!("expected `{:?}` but string was terminated", c);
+            let end = self.input.len();
             if c == '}' {
                 self.err_with_note(msg,
-                                   "if you intended to print `{`, you can escape it using `{{`");
+                                   "if you intended to print `{`, you can escape it using `{{`",
+                                   end..end);
             } else {
-                self.err(msg);
+                self.err(msg, end..end);
             }
         }
     }
@@ -278,7 +335,7 @@ impl<'a> Parser<'a> {
             None => {
                 let i = self.curarg;
                 self.curarg += 1;
-                ArgumentIs(i)
+                ArgumentImplicitlyIs(i)
             }
         };
 
@@ -288,6 +345,28 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Skips a comment, introduced by `;` and running to the closing brace,
+    /// at the current position. Returns false if the comment is unterminated,
+    /// in which case an error has already been emitted.
+    fn comment(&mut self) -> bool {
+        self.ws();
+        let start = match self.cur.peek() {
+            Some(&(pos, ';')) => pos,
+            _ => return true,
+        };
+        while let Some(&(_, c)) = self.cur.peek() {
+            if c == '}' {
+                return true;
+            }
+            self.cur.next();
+        }
+        let end = self.input.len();
+        self.err_with_note("unterminated comment",
+                           "a comment runs until the closing `}`",
+                           start..end);
+        false
+    }
+
     /// Parses a positional argument for a format. This could either be an
     /// integer index of an argument, a named argument, or a blank string.
     /// Returns `Some(parsed_position)` if the position is not implicitly
@@ -295,9 +374,12 @@ impl<'a> Parser<'a> {
     fn position(&mut self) -> Option<Position<'a>> {
         if let Some(i) = self.integer() {
             Some(ArgumentIs(i))
+        } else if let Some(i) = self.negative_integer() {
+            Some(ArgumentFromEnd(i))
         } else {
             match self.cur.peek() {
-                Some(&(_, c)) if c.is_alphabetic() => Some(ArgumentNamed(self.word())),
+                Some(&(_, c)) if c.is_alphabetic() => Some(ArgumentNamed(self.path())),
+                Some(&(start, '"')) => Some(ArgumentQuoted(self.quoted(start))),
 
                 // This is an `ArgumentNext`.
                 // Record the fact and do the resolution after parsing the
@@ -312,11 +394,15 @@ impl<'a> Parser<'a> {
     fn format(&mut self) -> FormatSpec<'a> {
         let mut spec = FormatSpec {
             fill: None,
+            fill_name: None,
             align: AlignUnknown,
             flags: 0,
             precision: CountImplied,
             width: CountImplied,
+            grouping: false,
+            directive: None,
             ty: &self.input[..0],
+            trait_: Some(FormatTraitKind::Display),
         };
         if !self.consume(':') {
             return spec;
@@ -332,6 +418,20 @@ impl<'a> Parser<'a> {
                 _ => {}
             }
         }
+        // Named fill character, which must be followed by an alignment
+        if spec.fill.is_none() {
+            let tmp = self.cur.clone();
+            let word = self.word();
+            let aligned = match self.cur.clone().skip(1).next() {
+                Some((_, '>')) | Some((_, '<')) | Some((_, '^')) => true,
+                _ => false,
+            };
+            if !word.is_empty() && aligned && self.consume('$') {
+                spec.fill_name = Some(word);
+            } else {
+                self.cur = tmp;
+            }
+        }
         // Alignment
         if self.consume('<') {
             spec.align = AlignLeft;
@@ -367,6 +467,10 @@ impl<'a> Parser<'a> {
         if !havewidth {
             spec.width = self.count();
         }
+        // Digit grouping
+        if self.consume('_') {
+            spec.grouping = true;
+        }
         if self.consume('.') {
             if self.consume('*') {
                 // Resolve `CountIsNextParam`.
@@ -378,12 +482,68 @@ impl<'a> Parser<'a> {
                 spec.precision = self.count();
             }
         }
-        // Finally the actual format specifier
-        if self.consume('?') {
+        // Finally the actual format specifier, where `x?` and `X?` select
+        // `?` with the respective hexadecimal flag.
+        let mut lookahead = self.cur.clone();
+        let debug_hex = match (lookahead.next(), lookahead.next()) {
+            (Some((_, 'x')), Some((_, '?'))) => Some(FlagDebugLowerHex),
+            (Some((_, 'X')), Some((_, '?'))) => Some(FlagDebugUpperHex),
+            _ => None,
+        };
+        if let Some(flag) = debug_hex {
+            self.cur = lookahead;
+            spec.flags |= 1 << (flag as u32);
             spec.ty = "?";
+        } else if self.consume('?') {
+            spec.ty = "?";
+        } else if self.consume('%') {
+            spec.ty = "%";
         } else {
             spec.ty = self.word();
         }
+        // Extension directive
+        let directive = if spec.ty == "?" {
+            let tmp = self.cur.clone();
+            let word = self.word();
+            if !word.is_empty() && self.consume('(') {
+                Some(word)
+            } else {
+                self.cur = tmp;
+                None
+            }
+        } else if !spec.ty.is_empty() && self.consume('(') {
+            let word = spec.ty;
+            spec.ty = &self.input[..0];
+            Some(word)
+        } else {
+            None
+        };
+        if let Some(name) = directive {
+            let start = self.cur.peek().map_or(self.input.len(), |&(pos, _)| pos);
+            let mut end = start;
+            let mut quoted = false;
+            while let Some(&(pos, c)) = self.cur.peek() {
+                if !quoted && (c == ')' || c == '}') {
+                    break;
+                }
+                end = pos + c.len_utf8();
+                self.cur.next();
+                match c {
+                    '"' => quoted = !quoted,
+                    '\\' if quoted => if let Some(&(pos, c)) = self.cur.peek() {
+                        end = pos + c.len_utf8();
+                        self.cur.next();
+                    },
+                    _ => {}
+                }
+            }
+            self.must_consume(')');
+            spec.directive = Some((name, &self.input[start..end]));
+        }
+        spec.trait_ = match spec.directive {
+            Some(("join", _)) => None,
+            _ => erase::trait_kind(spec.ty),
+        };
         spec
     }
 
@@ -437,20 +597,92 @@ impl<'a> Parser<'a> {
         &self.input[start..self.input.len()]
     }
 
-    /// Optionally parses an integer at the current position. This doesn't deal
-    /// with overflow at all, it's just accumulating digits.
+    /// Optionally parses a `-` followed by an integer at the current position,
+    /// returning the integer.
+    fn negative_integer(&mut self) -> Option<usize> {
+        let mut lookahead = self.cur.clone();
+        match (lookahead.next(), lookahead.peek()) {
+            (Some((_, '-')), Some(&(_, c))) if c.is_digit(10) => {}
+            _ => return None,
+        }
+        self.cur = lookahead;
+        self.integer()
+    }
+
+    /// Parses a name in double quotes, starting at the opening quote at
+    /// `start`. Returns the text between the quotes, leaving escapes as they
+    /// are, or up to the end of the string if the closing quote is missing.
+    fn quoted(&mut self, start: usize) -> &'a str {
+        self.cur.next();
+        let mut escaped = false;
+        while let Some((pos, c)) = self.cur.next() {
+            match c {
+                '\\' if !escaped => escaped = true,
+                '"' if !escaped => return &self.input[start + 1..pos],
+                _ => escaped = false,
+            }
+        }
+        let end = self.input.len();
+        self.err("unterminated quoted name", start..end);
+        &self.input[start + 1..end]
+    }
+
+    /// Parses a word followed by any number of `.`-separated words or
+    /// integers, such as `items.0.id`. Not part of the standard syntax.
+    fn path(&mut self) -> &'a str {
+        let start = match self.cur.peek() {
+            Some(&(pos, _)) => pos,
+            None => return &self.input[..0],
+        };
+        let mut end = start + self.word().len();
+        loop {
+            let mut lookahead = self.cur.clone();
+            match (lookahead.next(), lookahead.peek()) {
+                (Some((_, '.')), Some(&(_, c))) if c.is_xid_continue() => {}
+                _ => break,
+            }
+            self.cur = lookahead;
+            while let Some(&(pos, c)) = self.cur.peek() {
+                if !c.is_xid_continue() {
+                    break;
+                }
+                end = pos + c.len_utf8();
+                self.cur.next();
+            }
+        }
+        &self.input[start..end]
+    }
+
+    /// Optionally parses an integer at the current position. An integer too
+    /// large for a `usize` is reported as an error.
     fn integer(&mut self) -> Option<usize> {
-        let mut cur = 0;
+        let mut cur: Option<usize> = Some(0);
         let mut found = false;
-        while let Some(&(_, c)) = self.cur.peek() {
+        let mut start = 0;
+        let mut end = 0;
+        while let Some(&(pos, c)) = self.cur.peek() {
             if let Some(i) = c.to_digit(10) {
-                cur = cur * 10 + i as usize;
+                if !found {
+                    start = pos;
+                }
+                end = pos + 1;
+                cur = cur.and_then(|cur| cur.checked_mul(10))
+                    .and_then(|cur| cur.checked_add(i as usize));
                 found = true;
                 self.cur.next();
             } else {
                 break;
             }
         }
+        let cur = match cur {
+            Some(cur) => cur,
+            None => {
+                let msg = format!("integer `{}` does not fit into the type `usize` whose range \
+                                   is `0..={}`", &self.input[start..end], ::std::usize::MAX);
+                self.err(&msg, start..end);
+                ::std::usize::MAX
+            }
+        };
         if found {
             Some(cur)
         } else {
@@ -471,11 +703,15 @@ mod tests {
     fn fmtdflt() -> FormatSpec<'static> {
         return FormatSpec {
             fill: None,
+            fill_name: None,
             align: AlignUnknown,
             flags: 0,
             precision: CountImplied,
             width: CountImplied,
+            grouping: false,
+            directive: None,
             ty: "",
+            trait_: Some(FormatTraitKind::Display),
         };
     }
 
@@ -520,7 +756,7 @@ mod tests {
     fn format_nothing() {
         same("{}",
              &[NextArgument(Argument {
-                   position: ArgumentIs(0),
+                   position: ArgumentImplicitlyIs(0),
                    format: fmtdflt(),
                })]);
     }
@@ -533,6 +769,26 @@ mod tests {
                })]);
     }
     #[test]
+    fn format_from_end() {
+        same("{-2:}",
+             &[NextArgument(Argument {
+                   position: ArgumentFromEnd(2),
+                   format: fmtdflt(),
+               })]);
+        musterr("{-}");
+        musterr("{-a}");
+    }
+    #[test]
+    fn format_path() {
+        same("{items.0.id:}",
+             &[NextArgument(Argument {
+                   position: ArgumentNamed("items.0.id"),
+                   format: fmtdflt(),
+               })]);
+        musterr("{a.}");
+        musterr("{a..b}");
+    }
+    #[test]
     fn format_position_nothing_else() {
         same("{3:}",
              &[NextArgument(Argument {
@@ -547,11 +803,31 @@ mod tests {
                    position: ArgumentIs(3),
                    format: FormatSpec {
                        fill: None,
+                       fill_name: None,
                        align: AlignUnknown,
                        flags: 0,
                        precision: CountImplied,
                        width: CountImplied,
+                       grouping: false,
+                       directive: None,
                        ty: "a",
+                       trait_: None,
+                   },
+               })]);
+        same("{:.1%}",
+             &[NextArgument(Argument {
+                   position: ArgumentImplicitlyIs(0),
+                   format: FormatSpec {
+                       fill: None,
+                       fill_name: None,
+                       align: AlignUnknown,
+                       flags: 0,
+                       precision: CountIs(1),
+                       width: CountImplied,
+                       grouping: false,
+                       directive: None,
+                       ty: "%",
+                       trait_: None,
                    },
                })]);
     }
@@ -562,11 +838,15 @@ mod tests {
                    position: ArgumentIs(3),
                    format: FormatSpec {
                        fill: None,
+                       fill_name: None,
                        align: AlignRight,
                        flags: 0,
                        precision: CountImplied,
                        width: CountImplied,
+                       grouping: false,
+                       directive: None,
                        ty: "",
+                       trait_: Some(FormatTraitKind::Display),
                    },
                })]);
         same("{3:0<}",
@@ -574,11 +854,15 @@ mod tests {
                    position: ArgumentIs(3),
                    format: FormatSpec {
                        fill: Some('0'),
+                       fill_name: None,
                        align: AlignLeft,
                        flags: 0,
                        precision: CountImplied,
                        width: CountImplied,
+                       grouping: false,
+                       directive: None,
                        ty: "",
+                       trait_: Some(FormatTraitKind::Display),
                    },
                })]);
         same("{3:*<abcd}",
@@ -586,101 +870,316 @@ mod tests {
                    position: ArgumentIs(3),
                    format: FormatSpec {
                        fill: Some('*'),
+                       fill_name: None,
                        align: AlignLeft,
                        flags: 0,
                        precision: CountImplied,
                        width: CountImplied,
+                       grouping: false,
+                       directive: None,
                        ty: "abcd",
+                       trait_: None,
+                   },
+               })]);
+    }
+    #[test]
+    fn format_fill_name() {
+        same("{:pad$^5}",
+             &[NextArgument(Argument {
+                   position: ArgumentImplicitlyIs(0),
+                   format: FormatSpec {
+                       fill: None,
+                       fill_name: Some("pad"),
+                       align: AlignCenter,
+                       flags: 0,
+                       precision: CountImplied,
+                       width: CountIs(5),
+                       grouping: false,
+                       directive: None,
+                       ty: "",
+                       trait_: Some(FormatTraitKind::Display),
+                   },
+               })]);
+        same("{:pad$}",
+             &[NextArgument(Argument {
+                   position: ArgumentImplicitlyIs(0),
+                   format: FormatSpec {
+                       fill: None,
+                       fill_name: None,
+                       align: AlignUnknown,
+                       flags: 0,
+                       precision: CountImplied,
+                       width: CountIsName("pad"),
+                       grouping: false,
+                       directive: None,
+                       ty: "",
+                       trait_: Some(FormatTraitKind::Display),
+                   },
+               })]);
+    }
+    #[test]
+    fn format_quoted() {
+        same("{\"a:b\\\"}\":?}",
+             &[NextArgument(Argument {
+                   position: ArgumentQuoted("a:b\\\"}"),
+                   format: FormatSpec {
+                       ty: "?",
+                       trait_: Some(FormatTraitKind::Debug),
+                       ..fmtdflt()
                    },
                })]);
+        musterr("{\"open}");
     }
     #[test]
     fn format_counts() {
         same("{:10s}",
              &[NextArgument(Argument {
-                   position: ArgumentIs(0),
+                   position: ArgumentImplicitlyIs(0),
                    format: FormatSpec {
                        fill: None,
+                       fill_name: None,
                        align: AlignUnknown,
                        flags: 0,
                        precision: CountImplied,
                        width: CountIs(10),
+                       grouping: false,
+                       directive: None,
                        ty: "s",
+                       trait_: None,
                    },
                })]);
         same("{:10$.10s}",
              &[NextArgument(Argument {
-                   position: ArgumentIs(0),
+                   position: ArgumentImplicitlyIs(0),
                    format: FormatSpec {
                        fill: None,
+                       fill_name: None,
                        align: AlignUnknown,
                        flags: 0,
                        precision: CountIs(10),
                        width: CountIsParam(10),
+                       grouping: false,
+                       directive: None,
                        ty: "s",
+                       trait_: None,
                    },
                })]);
         same("{:.*s}",
              &[NextArgument(Argument {
-                   position: ArgumentIs(1),
+                   position: ArgumentImplicitlyIs(1),
                    format: FormatSpec {
                        fill: None,
+                       fill_name: None,
                        align: AlignUnknown,
                        flags: 0,
                        precision: CountIsParam(0),
                        width: CountImplied,
+                       grouping: false,
+                       directive: None,
                        ty: "s",
+                       trait_: None,
                    },
                })]);
         same("{:.10$s}",
              &[NextArgument(Argument {
-                   position: ArgumentIs(0),
+                   position: ArgumentImplicitlyIs(0),
                    format: FormatSpec {
                        fill: None,
+                       fill_name: None,
                        align: AlignUnknown,
                        flags: 0,
                        precision: CountIsParam(10),
                        width: CountImplied,
+                       grouping: false,
+                       directive: None,
                        ty: "s",
+                       trait_: None,
                    },
                })]);
         same("{:a$.b$s}",
              &[NextArgument(Argument {
-                   position: ArgumentIs(0),
+                   position: ArgumentImplicitlyIs(0),
                    format: FormatSpec {
                        fill: None,
+                       fill_name: None,
                        align: AlignUnknown,
                        flags: 0,
                        precision: CountIsName("b"),
                        width: CountIsName("a"),
+                       grouping: false,
+                       directive: None,
                        ty: "s",
+                       trait_: None,
+                   },
+               })]);
+    }
+    #[test]
+    fn format_grouping() {
+        same("{:>12_.2x}",
+             &[NextArgument(Argument {
+                   position: ArgumentImplicitlyIs(0),
+                   format: FormatSpec {
+                       fill: None,
+                       fill_name: None,
+                       align: AlignRight,
+                       flags: 0,
+                       precision: CountIs(2),
+                       width: CountIs(12),
+                       grouping: true,
+                       directive: None,
+                       ty: "x",
+                       trait_: Some(FormatTraitKind::LowerHex),
+                   },
+               })]);
+    }
+    #[test]
+    fn format_directive() {
+        same("{:>8style(red, bold)}",
+             &[NextArgument(Argument {
+                   position: ArgumentImplicitlyIs(0),
+                   format: FormatSpec {
+                       fill: None,
+                       fill_name: None,
+                       align: AlignRight,
+                       flags: 0,
+                       precision: CountImplied,
+                       width: CountIs(8),
+                       grouping: false,
+                       directive: Some(("style", "red, bold")),
+                       ty: "",
+                       trait_: Some(FormatTraitKind::Display),
+                   },
+               })]);
+        same("{:?style()}",
+             &[NextArgument(Argument {
+                   position: ArgumentImplicitlyIs(0),
+                   format: FormatSpec {
+                       fill: None,
+                       fill_name: None,
+                       align: AlignUnknown,
+                       flags: 0,
+                       precision: CountImplied,
+                       width: CountImplied,
+                       grouping: false,
+                       directive: Some(("style", "")),
+                       ty: "?",
+                       trait_: Some(FormatTraitKind::Debug),
                    },
                })]);
     }
     #[test]
+    fn format_comment() {
+        same("{; a comment}",
+             &[NextArgument(Argument {
+                   position: ArgumentImplicitlyIs(0),
+                   format: fmtdflt(),
+               })]);
+        same("{total:>10 ; right aligned}",
+             &[NextArgument(Argument {
+                   position: ArgumentNamed("total"),
+                   format: FormatSpec {
+                       fill: None,
+                       fill_name: None,
+                       align: AlignRight,
+                       flags: 0,
+                       precision: CountImplied,
+                       width: CountIs(10),
+                       grouping: false,
+                       directive: None,
+                       ty: "",
+                       trait_: Some(FormatTraitKind::Display),
+                   },
+               })]);
+        musterr("{0; unterminated");
+        let mut p = Parser::new("ab{:x;");
+        p.next();
+        p.next();
+        assert_eq!(p.errors.len(), 1);
+        assert_eq!(p.errors[0].span, 5..6);
+    }
+    #[test]
     fn format_flags() {
         same("{:-}",
              &[NextArgument(Argument {
-                   position: ArgumentIs(0),
+                   position: ArgumentImplicitlyIs(0),
                    format: FormatSpec {
                        fill: None,
+                       fill_name: None,
                        align: AlignUnknown,
                        flags: (1 << FlagSignMinus as u32),
                        precision: CountImplied,
                        width: CountImplied,
+                       grouping: false,
+                       directive: None,
                        ty: "",
+                       trait_: Some(FormatTraitKind::Display),
                    },
                })]);
         same("{:+#}",
              &[NextArgument(Argument {
-                   position: ArgumentIs(0),
+                   position: ArgumentImplicitlyIs(0),
                    format: FormatSpec {
                        fill: None,
+                       fill_name: None,
                        align: AlignUnknown,
                        flags: (1 << FlagSignPlus as u32) | (1 << FlagAlternate as u32),
                        precision: CountImplied,
                        width: CountImplied,
+                       grouping: false,
+                       directive: None,
                        ty: "",
+                       trait_: Some(FormatTraitKind::Display),
+                   },
+               })]);
+    }
+    #[test]
+    fn format_debug_hex() {
+        same("{:#x?}",
+             &[NextArgument(Argument {
+                   position: ArgumentImplicitlyIs(0),
+                   format: FormatSpec {
+                       fill: None,
+                       fill_name: None,
+                       align: AlignUnknown,
+                       flags: (1 << FlagAlternate as u32) | (1 << FlagDebugLowerHex as u32),
+                       precision: CountImplied,
+                       width: CountImplied,
+                       grouping: false,
+                       directive: None,
+                       ty: "?",
+                       trait_: Some(FormatTraitKind::Debug),
+                   },
+               })]);
+        same("{:X?}",
+             &[NextArgument(Argument {
+                   position: ArgumentImplicitlyIs(0),
+                   format: FormatSpec {
+                       fill: None,
+                       fill_name: None,
+                       align: AlignUnknown,
+                       flags: 1 << FlagDebugUpperHex as u32,
+                       precision: CountImplied,
+                       width: CountImplied,
+                       grouping: false,
+                       directive: None,
+                       ty: "?",
+                       trait_: Some(FormatTraitKind::Debug),
+                   },
+               })]);
+        same("{:xy}",
+             &[NextArgument(Argument {
+                   position: ArgumentImplicitlyIs(0),
+                   format: FormatSpec {
+                       fill: None,
+                       fill_name: None,
+                       align: AlignUnknown,
+                       flags: 0,
+                       precision: CountImplied,
+                       width: CountImplied,
+                       grouping: false,
+                       directive: None,
+                       ty: "xy",
+                       trait_: None,
                    },
                })]);
     }
@@ -692,11 +1191,15 @@ mod tests {
                    position: ArgumentIs(3),
                    format: FormatSpec {
                        fill: None,
+                       fill_name: None,
                        align: AlignUnknown,
                        flags: 0,
                        precision: CountImplied,
                        width: CountImplied,
+                       grouping: false,
+                       directive: None,
                        ty: "a",
+                       trait_: None,
                    },
                }),
                String(" efg")]);
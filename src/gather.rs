@@ -0,0 +1,46 @@
+//! Short slices gathered on the stack while formatting.
+//!
+//! Each render hands `Arguments` slices of the pieces, arguments, and specs
+//! of a format. Those of up to `STACK_LEN` elements are built in place rather
+//! than in a `Vec`, so that rendering most formats allocates nothing.
+use std::ops::Deref;
+use std::vec::Vec;
+
+/// The most elements gathered on the stack.
+pub const STACK_LEN: usize = 16;
+
+/// A slice which is borrowed, gathered on the stack, or failing that, on the
+/// heap.
+pub enum Gathered<'a, T: 'a> {
+    Borrowed(&'a [T]),
+    Stack([T; STACK_LEN], usize),
+    Heap(Vec<T>),
+}
+
+impl<'a, T: Copy> Gathered<'a, T> {
+    /// Gather the elements of `iter`, using `filler` for the unused slots.
+    pub fn new<I: ExactSizeIterator<Item = T>>(filler: T, iter: I) -> Self {
+        let len = iter.len();
+        if len > STACK_LEN {
+            return Gathered::Heap(iter.collect());
+        }
+        let mut array = [filler; STACK_LEN];
+        for (slot, item) in array.iter_mut().zip(iter) {
+            *slot = item;
+        }
+        Gathered::Stack(array, len)
+    }
+}
+
+impl<'a, T> Deref for Gathered<'a, T> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &[T] {
+        match *self {
+            Gathered::Borrowed(slice) => slice,
+            Gathered::Stack(ref array, len) => &array[..len],
+            Gathered::Heap(ref vec) => vec,
+        }
+    }
+}
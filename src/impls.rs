@@ -0,0 +1,570 @@
+//! `FormatArgs` implementations for tuples, arrays, `Option` and `Result`,
+//! and for references to any other implementation.
+//!
+//! The elements of tuples, arrays, `Option` and `Result` may be referred to
+//! by position only, as with `{0}`.
+use std::fmt::{Formatter, Result};
+use codegen::{combine, as_dyn, as_join, as_usize, as_char, as_str, as_f64, is_float, is_join,
+              is_str, is_number, Child, DynFormatArgs, FormatArgs, FormatTrait, Join};
+
+// The name of an element which may only be referred to by position.
+macro_rules! blank {
+    ($idx:tt) => { "" }
+}
+
+// Format the element at `$idx`, of type `$name`, of a tuple of the types in
+// `$all`. The tuple's type parameters are passed whole so that they may be
+// repeated for each of its elements.
+macro_rules! tuple_child {
+    ($f:ident; [$($all:ident),*]; $idx:tt $name:ident) => {{
+        struct Element;
+        impl<$($all),*> Child<($($all,)*)> for Element {
+            type Target = $name;
+            #[inline]
+            fn get(this: &($($all,)*)) -> Option<&$name> {
+                Some(&this.$idx)
+            }
+        }
+        combine::<$f, Self, Element>()
+    }}
+}
+
+// The body of `get_child` for the tuple of the types in `$all`.
+macro_rules! tuple_get_child {
+    ($index:ident; $f:ident; $all:tt; $($idx:tt $name:ident),*) => {
+        match $index {
+            $($idx => tuple_child!($f; $all; $idx $name),)*
+            _ => panic!("bad index {}", $index),
+        }
+    }
+}
+
+macro_rules! tuples {
+    ($($len:expr => ($($idx:tt $name:ident),*);)*) => {
+        $(
+            impl<$($name),*> FormatArgs for ($($name,)*) {
+                #[inline]
+                fn validate_name(_: &str) -> Option<usize> {
+                    None
+                }
+
+                #[inline]
+                fn validate_index(index: usize) -> bool {
+                    index < $len
+                }
+
+                #[inline]
+                fn arg_count() -> usize {
+                    $len
+                }
+
+                fn arg_names() -> &'static [&'static str] {
+                    const NAMES: &'static [&'static str] = &[$(blank!($idx)),*];
+                    NAMES
+                }
+
+                fn is_float(index: usize) -> bool {
+                    match index {
+                        $($idx => is_float::<$name>(),)*
+                        _ => panic!("bad index {}", index),
+                    }
+                }
+
+                fn get_child<__F: FormatTrait + ?Sized>(index: usize)
+                    -> Option<fn(&Self, &mut Formatter) -> Result>
+                {
+                    tuple_get_child!(index; __F; [$($name),*]; $($idx $name),*)
+                }
+
+                fn as_usize(index: usize) -> Option<fn(&Self) -> &usize> {
+                    match index {
+                        $($idx => as_usize::<Self, $name>(|this| &this.$idx),)*
+                        _ => panic!("bad index {}", index),
+                    }
+                }
+
+                fn as_char(index: usize) -> Option<fn(&Self) -> &char> {
+                    match index {
+                        $($idx => as_char::<Self, $name>(|this| &this.$idx),)*
+                        _ => panic!("bad index {}", index),
+                    }
+                }
+
+                fn as_str(index: usize) -> Option<fn(&Self) -> &str> {
+                    match index {
+                        $($idx => {
+                            let f: fn(&Self) -> &str = |this| as_str(&this.$idx).unwrap_or("");
+                            if is_str::<$name>() { Some(f) } else { None }
+                        })*
+                        _ => panic!("bad index {}", index),
+                    }
+                }
+
+                fn as_f64(index: usize) -> Option<fn(&Self) -> f64> {
+                    match index {
+                        $($idx => {
+                            let f: fn(&Self) -> f64 = |this| as_f64(&this.$idx).unwrap_or(0.0);
+                            if is_number::<$name>() { Some(f) } else { None }
+                        })*
+                        _ => panic!("bad index {}", index),
+                    }
+                }
+
+                fn get_nested(index: usize) -> Option<fn(&Self) -> Option<&DynFormatArgs>> {
+                    match index {
+                        $($idx => Some(|this: &Self| as_dyn(&this.$idx)),)*
+                        _ => panic!("bad index {}", index),
+                    }
+                }
+
+                fn get_join(index: usize) -> Option<fn(&Self) -> Option<&Join>> {
+                    match index {
+                        $($idx => {
+                            let f: fn(&Self) -> Option<&Join> = |this| as_join(&this.$idx);
+                            if is_join::<$name>() { Some(f) } else { None }
+                        })*
+                        _ => panic!("bad index {}", index),
+                    }
+                }
+            }
+        )*
+    }
+}
+
+tuples! {
+    1 => (0 A);
+    2 => (0 A, 1 B);
+    3 => (0 A, 1 B, 2 C);
+    4 => (0 A, 1 B, 2 C, 3 D);
+    5 => (0 A, 1 B, 2 C, 3 D, 4 E);
+    6 => (0 A, 1 B, 2 C, 3 D, 4 E, 5 F);
+    7 => (0 A, 1 B, 2 C, 3 D, 4 E, 5 F, 6 G);
+    8 => (0 A, 1 B, 2 C, 3 D, 4 E, 5 F, 6 G, 7 H);
+    9 => (0 A, 1 B, 2 C, 3 D, 4 E, 5 F, 6 G, 7 H, 8 I);
+    10 => (0 A, 1 B, 2 C, 3 D, 4 E, 5 F, 6 G, 7 H, 8 I, 9 J);
+    11 => (0 A, 1 B, 2 C, 3 D, 4 E, 5 F, 6 G, 7 H, 8 I, 9 J, 10 K);
+    12 => (0 A, 1 B, 2 C, 3 D, 4 E, 5 F, 6 G, 7 H, 8 I, 9 J, 10 K, 11 L);
+}
+
+macro_rules! arrays {
+    ($($len:expr => [$($idx:tt)*];)*) => {
+        $(
+            impl<T> FormatArgs for [T; $len] {
+                #[inline]
+                fn validate_name(_: &str) -> Option<usize> {
+                    None
+                }
+
+                #[inline]
+                fn validate_index(index: usize) -> bool {
+                    index < $len
+                }
+
+                #[inline]
+                fn arg_count() -> usize {
+                    $len
+                }
+
+                fn arg_names() -> &'static [&'static str] {
+                    const NAMES: &'static [&'static str] = &[$(blank!($idx)),*];
+                    NAMES
+                }
+
+                #[inline]
+                fn is_float(index: usize) -> bool {
+                    assert!(index < $len, "bad index {}", index);
+                    is_float::<T>()
+                }
+
+                fn get_child<F: FormatTrait + ?Sized>(index: usize)
+                    -> Option<fn(&Self, &mut Formatter) -> Result>
+                {
+                    // Each index needs its own `Child`.
+                    match index {
+                        $($idx => {
+                            struct Element;
+                            impl<T> Child<[T; $len]> for Element {
+                                type Target = T;
+                                #[inline]
+                                fn get(this: &[T; $len]) -> Option<&T> {
+                                    Some(&this[$idx])
+                                }
+                            }
+                            combine::<F, Self, Element>()
+                        })*
+                        _ => panic!("bad index {}", index),
+                    }
+                }
+
+                fn as_usize(index: usize) -> Option<fn(&Self) -> &usize> {
+                    match index {
+                        $($idx => as_usize::<Self, T>(|this| &this[$idx]),)*
+                        _ => panic!("bad index {}", index),
+                    }
+                }
+
+                fn as_char(index: usize) -> Option<fn(&Self) -> &char> {
+                    match index {
+                        $($idx => as_char::<Self, T>(|this| &this[$idx]),)*
+                        _ => panic!("bad index {}", index),
+                    }
+                }
+
+                fn as_str(index: usize) -> Option<fn(&Self) -> &str> {
+                    match index {
+                        $($idx => {
+                            let f: fn(&Self) -> &str = |this| as_str(&this[$idx]).unwrap_or("");
+                            if is_str::<T>() { Some(f) } else { None }
+                        })*
+                        _ => panic!("bad index {}", index),
+                    }
+                }
+
+                fn as_f64(index: usize) -> Option<fn(&Self) -> f64> {
+                    match index {
+                        $($idx => {
+                            let f: fn(&Self) -> f64 = |this| as_f64(&this[$idx]).unwrap_or(0.0);
+                            if is_number::<T>() { Some(f) } else { None }
+                        })*
+                        _ => panic!("bad index {}", index),
+                    }
+                }
+
+                fn get_nested(index: usize) -> Option<fn(&Self) -> Option<&DynFormatArgs>> {
+                    match index {
+                        $($idx => Some(|this: &Self| as_dyn(&this[$idx])),)*
+                        _ => panic!("bad index {}", index),
+                    }
+                }
+
+                fn get_join(index: usize) -> Option<fn(&Self) -> Option<&Join>> {
+                    match index {
+                        $($idx => {
+                            let f: fn(&Self) -> Option<&Join> = |this| as_join(&this[$idx]);
+                            if is_join::<T>() { Some(f) } else { None }
+                        })*
+                        _ => panic!("bad index {}", index),
+                    }
+                }
+            }
+        )*
+    }
+}
+
+arrays! {
+    1 => [0];
+    2 => [0 1];
+    3 => [0 1 2];
+    4 => [0 1 2 3];
+    5 => [0 1 2 3 4];
+    6 => [0 1 2 3 4 5];
+    7 => [0 1 2 3 4 5 6];
+    8 => [0 1 2 3 4 5 6 7];
+    9 => [0 1 2 3 4 5 6 7 8];
+    10 => [0 1 2 3 4 5 6 7 8 9];
+    11 => [0 1 2 3 4 5 6 7 8 9 10];
+    12 => [0 1 2 3 4 5 6 7 8 9 10 11];
+    13 => [0 1 2 3 4 5 6 7 8 9 10 11 12];
+    14 => [0 1 2 3 4 5 6 7 8 9 10 11 12 13];
+    15 => [0 1 2 3 4 5 6 7 8 9 10 11 12 13 14];
+    16 => [0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15];
+}
+
+// A `Some` is at index 0; a `None` formats as the empty string, without any
+// padding. Accessors needing a value to be present are unavailable.
+impl<T> FormatArgs for Option<T> {
+    #[inline]
+    fn validate_name(_: &str) -> Option<usize> {
+        None
+    }
+
+    #[inline]
+    fn validate_index(index: usize) -> bool {
+        index < 1
+    }
+
+    #[inline]
+    fn arg_count() -> usize {
+        1
+    }
+
+    fn arg_names() -> &'static [&'static str] {
+        const NAMES: &'static [&'static str] = &[""];
+        NAMES
+    }
+
+    #[inline]
+    fn is_float(index: usize) -> bool {
+        assert!(index < 1, "bad index {}", index);
+        is_float::<T>()
+    }
+
+    fn as_usize(index: usize) -> Option<fn(&Self) -> &usize> {
+        match index {
+            0 => None,
+            _ => panic!("bad index {}", index),
+        }
+    }
+
+    fn get_child<F: FormatTrait + ?Sized>(index: usize)
+        -> Option<fn(&Self, &mut Formatter) -> Result>
+    {
+        match index {
+            0 => {
+                struct Contents;
+                impl<T> Child<Option<T>> for Contents {
+                    type Target = T;
+                    #[inline]
+                    fn get(this: &Option<T>) -> Option<&T> {
+                        this.as_ref()
+                    }
+                }
+                combine::<F, Self, Contents>()
+            }
+            _ => panic!("bad index {}", index),
+        }
+    }
+
+    fn get_nested(index: usize) -> Option<fn(&Self) -> Option<&DynFormatArgs>> {
+        match index {
+            0 => Some(|this: &Self| this.as_ref().and_then(as_dyn)),
+            _ => panic!("bad index {}", index),
+        }
+    }
+
+    fn get_join(index: usize) -> Option<fn(&Self) -> Option<&Join>> {
+        match index {
+            0 if is_join::<T>() => Some(|this: &Self| this.as_ref().and_then(as_join)),
+            0 => None,
+            _ => panic!("bad index {}", index),
+        }
+    }
+}
+
+// An `Ok` is at index 0 and an `Err` at index 1; whichever is absent formats
+// as the empty string, as for `Option`.
+impl<T, E> FormatArgs for ::std::result::Result<T, E> {
+    #[inline]
+    fn validate_name(_: &str) -> Option<usize> {
+        None
+    }
+
+    #[inline]
+    fn validate_index(index: usize) -> bool {
+        index < 2
+    }
+
+    #[inline]
+    fn arg_count() -> usize {
+        2
+    }
+
+    fn arg_names() -> &'static [&'static str] {
+        const NAMES: &'static [&'static str] = &["", ""];
+        NAMES
+    }
+
+    #[inline]
+    fn is_float(index: usize) -> bool {
+        match index {
+            0 => is_float::<T>(),
+            1 => is_float::<E>(),
+            _ => panic!("bad index {}", index),
+        }
+    }
+
+    fn as_usize(index: usize) -> Option<fn(&Self) -> &usize> {
+        match index {
+            0 | 1 => None,
+            _ => panic!("bad index {}", index),
+        }
+    }
+
+    fn get_child<F: FormatTrait + ?Sized>(index: usize)
+        -> Option<fn(&Self, &mut Formatter) -> Result>
+    {
+        match index {
+            0 => {
+                struct OkValue;
+                impl<T, E> Child<::std::result::Result<T, E>> for OkValue {
+                    type Target = T;
+                    #[inline]
+                    fn get(this: &::std::result::Result<T, E>) -> Option<&T> {
+                        this.as_ref().ok()
+                    }
+                }
+                combine::<F, Self, OkValue>()
+            }
+            1 => {
+                struct ErrValue;
+                impl<T, E> Child<::std::result::Result<T, E>> for ErrValue {
+                    type Target = E;
+                    #[inline]
+                    fn get(this: &::std::result::Result<T, E>) -> Option<&E> {
+                        this.as_ref().err()
+                    }
+                }
+                combine::<F, Self, ErrValue>()
+            }
+            _ => panic!("bad index {}", index),
+        }
+    }
+
+    fn get_nested(index: usize) -> Option<fn(&Self) -> Option<&DynFormatArgs>> {
+        match index {
+            0 => Some(|this: &Self| this.as_ref().ok().and_then(as_dyn)),
+            1 => Some(|this: &Self| this.as_ref().err().and_then(as_dyn)),
+            _ => panic!("bad index {}", index),
+        }
+    }
+
+    fn get_join(index: usize) -> Option<fn(&Self) -> Option<&Join>> {
+        match index {
+            0 if is_join::<T>() => Some(|this: &Self| this.as_ref().ok().and_then(as_join)),
+            1 if is_join::<E>() => Some(|this: &Self| this.as_ref().err().and_then(as_join)),
+            0 | 1 => None,
+            _ => panic!("bad index {}", index),
+        }
+    }
+}
+
+// A type standing for an index, so that a function pointer adapted to a
+// reference may know which index it formats without capturing it.
+trait At {
+    fn index() -> usize;
+}
+
+// Format the child of `*this` at `I`, by the function `T::get_child` returned
+// when this one was handed out.
+fn child_at<T, F, I>(this: &&T, f: &mut Formatter) -> Result
+    where T: FormatArgs, F: FormatTrait + ?Sized, I: At
+{
+    T::get_child::<F>(I::index()).map_or(Ok(()), |func| func(*this, f))
+}
+
+fn usize_at<T: FormatArgs, I: At>(this: &&T) -> &usize {
+    T::as_usize(I::index()).unwrap()(*this)
+}
+
+fn char_at<T: FormatArgs, I: At>(this: &&T) -> &char {
+    T::as_char(I::index()).unwrap()(*this)
+}
+
+fn str_at<T: FormatArgs, I: At>(this: &&T) -> &str {
+    T::as_str(I::index()).unwrap()(*this)
+}
+
+fn f64_at<T: FormatArgs, I: At>(this: &&T) -> f64 {
+    T::as_f64(I::index()).unwrap()(*this)
+}
+
+fn nested_at<'a, T: FormatArgs, I: At>(this: &'a &T) -> Option<&'a DynFormatArgs> {
+    T::get_nested(I::index()).and_then(|func| func(*this))
+}
+
+fn join_at<'a, T: FormatArgs, I: At>(this: &'a &T) -> Option<&'a Join> {
+    T::get_join(I::index()).and_then(|func| func(*this))
+}
+
+// Forward to `T`. Each accessor is adapted to `&T` by a function which finds
+// `T`'s own accessor again from the index its `At` stands for, and so only
+// the indices listed here may be formatted through a reference; the rest
+// have no accessors at all.
+macro_rules! references {
+    ($($at:ident $idx:tt)*) => {
+        $(
+            struct $at;
+            impl At for $at {
+                #[inline]
+                fn index() -> usize {
+                    $idx
+                }
+            }
+        )*
+
+        impl<'a, T: FormatArgs> FormatArgs for &'a T {
+            #[inline]
+            fn validate_name(name: &str) -> Option<usize> {
+                T::validate_name(name)
+            }
+
+            #[inline]
+            fn validate_index(index: usize) -> bool {
+                T::validate_index(index)
+            }
+
+            #[inline]
+            fn arg_count() -> usize {
+                T::arg_count()
+            }
+
+            #[inline]
+            fn arg_names() -> &'static [&'static str] {
+                T::arg_names()
+            }
+
+            #[inline]
+            fn is_float(index: usize) -> bool {
+                T::is_float(index)
+            }
+
+            fn get_child<F: FormatTrait + ?Sized>(index: usize)
+                -> Option<fn(&Self, &mut Formatter) -> Result>
+            {
+                match index {
+                    $($idx => T::get_child::<F>(index).map(|_| child_at::<T, F, $at> as _),)*
+                    _ => None,
+                }
+            }
+
+            fn as_usize(index: usize) -> Option<fn(&Self) -> &usize> {
+                match index {
+                    $($idx => T::as_usize(index).map(|_| usize_at::<T, $at> as _),)*
+                    _ => None,
+                }
+            }
+
+            fn as_char(index: usize) -> Option<fn(&Self) -> &char> {
+                match index {
+                    $($idx => T::as_char(index).map(|_| char_at::<T, $at> as _),)*
+                    _ => None,
+                }
+            }
+
+            fn as_str(index: usize) -> Option<fn(&Self) -> &str> {
+                match index {
+                    $($idx => T::as_str(index).map(|_| str_at::<T, $at> as _),)*
+                    _ => None,
+                }
+            }
+
+            fn as_f64(index: usize) -> Option<fn(&Self) -> f64> {
+                match index {
+                    $($idx => T::as_f64(index).map(|_| f64_at::<T, $at> as _),)*
+                    _ => None,
+                }
+            }
+
+            fn get_nested(index: usize) -> Option<fn(&Self) -> Option<&DynFormatArgs>> {
+                match index {
+                    $($idx => T::get_nested(index).map(|_| nested_at::<T, $at> as _),)*
+                    _ => None,
+                }
+            }
+
+            fn get_join(index: usize) -> Option<fn(&Self) -> Option<&Join>> {
+                match index {
+                    $($idx => T::get_join(index).map(|_| join_at::<T, $at> as _),)*
+                    _ => None,
+                }
+            }
+        }
+    }
+}
+
+references! {
+    At0 0 At1 1 At2 2 At3 3 At4 4 At5 5 At6 6 At7 7 At8 8 At9 9 At10 10 At11 11
+    At12 12 At13 13 At14 14 At15 15 At16 16 At17 17 At18 18 At19 19 At20 20
+    At21 21 At22 22 At23 23 At24 24 At25 25 At26 26 At27 27 At28 28 At29 29
+    At30 30 At31 31
+}
@@ -0,0 +1,100 @@
+//! Short vectors kept inline until they outgrow a fixed capacity.
+//!
+//! The arguments, specs, and indices of a format are stored this way, so
+//! that preparing a format with few arguments allocates nothing for them.
+use std::iter::FromIterator;
+use std::ops::Deref;
+use std::vec::Vec;
+
+/// The most elements kept inline.
+pub const INLINE_LEN: usize = 4;
+
+/// A vector of `Copy` elements, kept inline while there are at most
+/// `INLINE_LEN` of them.
+pub struct InlineVec<T>(Repr<T>);
+
+enum Repr<T> {
+    Empty,
+    Inline([T; INLINE_LEN], usize),
+    Heap(Vec<T>),
+}
+
+impl<T> InlineVec<T> {
+    #[inline]
+    pub fn new() -> Self {
+        InlineVec(Repr::Empty)
+    }
+
+    /// Shorten the vector to `len` elements, keeping its storage.
+    pub fn truncate(&mut self, len: usize) {
+        match self.0 {
+            Repr::Empty => {}
+            Repr::Inline(_, ref mut current) => if len < *current {
+                *current = len;
+            },
+            Repr::Heap(ref mut vec) => vec.truncate(len),
+        }
+    }
+}
+
+impl<T: Copy> InlineVec<T> {
+    pub fn push(&mut self, item: T) {
+        let len = self.len();
+        if let Repr::Heap(ref mut vec) = self.0 {
+            vec.push(item);
+            return;
+        }
+        if len == 0 {
+            self.0 = Repr::Inline([item; INLINE_LEN], 1);
+        } else if len < INLINE_LEN {
+            if let Repr::Inline(ref mut array, ref mut current) = self.0 {
+                array[len] = item;
+                *current += 1;
+            }
+        } else {
+            let mut vec = Vec::with_capacity(INLINE_LEN * 2);
+            vec.extend_from_slice(&self[..]);
+            vec.push(item);
+            self.0 = Repr::Heap(vec);
+        }
+    }
+}
+
+impl<T: Copy> Clone for InlineVec<T> {
+    fn clone(&self) -> Self {
+        InlineVec(match self.0 {
+            Repr::Empty => Repr::Empty,
+            Repr::Inline(array, len) => Repr::Inline(array, len),
+            Repr::Heap(ref vec) => Repr::Heap(vec.clone()),
+        })
+    }
+}
+
+impl<T> Deref for InlineVec<T> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &[T] {
+        match self.0 {
+            Repr::Empty => &[],
+            Repr::Inline(ref array, len) => &array[..len],
+            Repr::Heap(ref vec) => vec,
+        }
+    }
+}
+
+impl<T: Copy> Extend<T> for InlineVec<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.push(item);
+        }
+    }
+}
+
+impl<T: Copy> FromIterator<T> for InlineVec<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut vec = InlineVec::new();
+        vec.extend(iter);
+        vec
+    }
+}
@@ -0,0 +1,88 @@
+//! Formatting of `serde_json::Value` through `DynFormatArgs`.
+use std::fmt::ArgumentV1;
+use serde_json::Value;
+use codegen::DynFormatArgs;
+use erase::Format;
+use Error;
+
+// The member of an object or element of an array at `index`.
+fn child(value: &Value, index: usize) -> &Value {
+    let child = match *value {
+        Value::Object(ref map) => map.values().nth(index),
+        Value::Array(ref vec) => vec.get(index),
+        _ => None,
+    };
+    child.expect("invalid index")
+}
+
+/// Objects are indexed by key and arrays by position. Object keys whose value
+/// is `null` are reported as `MissingValue`. Dotted names may descend into
+/// nested objects and arrays.
+///
+/// Strings, booleans, and numbers format as themselves; other values format
+/// as JSON through `Display` and as a tree through `Debug`. No child may be
+/// used as a width or precision.
+impl DynFormatArgs for Value {
+    fn validate_name(&self, name: &str) -> Option<usize> {
+        match *self {
+            Value::Object(ref map) => map.iter().position(|(k, v)| k == name && !v.is_null()),
+            _ => None,
+        }
+    }
+
+    fn validate_index(&self, index: usize) -> bool {
+        index < self.arg_count()
+    }
+
+    fn is_missing(&self, name: &str) -> bool {
+        match *self {
+            Value::Object(ref map) => map.get(name).map_or(false, Value::is_null),
+            _ => false,
+        }
+    }
+
+    fn arg_count(&self) -> usize {
+        match *self {
+            Value::Array(ref vec) => vec.len(),
+            _ => 0,
+        }
+    }
+
+    fn format_child<'a, 's>(&'a self, spec: &'s str, index: usize)
+        -> Result<ArgumentV1<'a>, Error<'s>>
+    {
+        match *child(self, index) {
+            Value::Bool(ref b) => b.by_name(spec, index),
+            Value::Number(ref n) => n.by_name(spec, index),
+            Value::String(ref s) => s.by_name(spec, index),
+            ref other => other.by_name(spec, index),
+        }
+    }
+
+    #[inline]
+    fn as_usize(&self, _: usize) -> Option<&usize> {
+        None
+    }
+
+    fn as_str(&self, index: usize) -> Option<&str> {
+        child(self, index).as_str()
+    }
+
+    fn as_f64(&self, index: usize) -> Option<f64> {
+        child(self, index).as_f64()
+    }
+
+    fn is_float(&self, index: usize) -> bool {
+        match *child(self, index) {
+            Value::Number(ref n) => n.is_f64(),
+            _ => false,
+        }
+    }
+
+    fn nested(&self, index: usize) -> Option<&DynFormatArgs> {
+        match *child(self, index) {
+            ref value @ Value::Object(_) | ref value @ Value::Array(_) => Some(value),
+            _ => None,
+        }
+    }
+}
@@ -6,52 +6,302 @@
 //! customization.
 //!
 //! Each of the standard formatting macros `format_args!`, `format!`,
-//! `print!`, `println!`, `write!`, and `writeln!` have corresponding `rt_`
-//! variants. Calls which previously succeeded unconditionally now return
-//! `Result`, which may indicate a bad format string or arguments.
+//! `print!`, `println!`, `eprint!`, `eprintln!`, `write!`, and `writeln!` have
+//! corresponding `rt_` variants. Calls which previously succeeded
+//! unconditionally now return `Result`, which may indicate a bad format string
+//! or arguments.
 //!
 //! The syntax for format strings and for macro invocations is equivalent to
 //! that used by `std::fmt`, including support for positional and named
 //! arguments. This crate shells out to the standard library implementations
 //! for as much as possible to ensure feature parity.
+//!
+//! A width or precision named or indexed with `$`, as in `{:w$}`, must refer
+//! to an argument which converts to a `usize`. Otherwise `BadCount` is
+//! reported, or for a name `BadNamedCount`, which also says whether it was
+//! given as a width or a precision. An argument may serve as its own width,
+//! so `{w:w$}` with `w = 4` formats as `   4`. Counts are read from the
+//! arguments themselves rather than from other counts, so no chain of
+//! references can form a cycle.
+//!
+//! As an extension, a `_` following the width requests that the integer part
+//! of a number be separated into groups of thousands, as in `{:>12_}`. The
+//! separator defaults to `,` and may be changed with `group_separator`.
+//! Likewise, the decimal point of floating-point numbers may be changed with
+//! `decimal_separator`.
+//!
+//! A `style(...)` directive in place of the trait name, or following `?`,
+//! surrounds the padded output with ANSI escape sequences, as in
+//! `{name:>8style(red,bold)}`. The attributes are `bold`, `dim`, `italic`,
+//! `underline`, `blink`, `reverse`, `hidden`, `strikethrough`, the eight
+//! colors `black` through `white`, and those colors prefixed with `on_` for
+//! the background. Styling may be disabled with `styling(false)`.
+//!
+//! A `join(sep)` directive formats each element of a slice, `Vec`, or array
+//! with `Display`, separated by `sep`, as in `{items:join(", ")}`. The
+//! separator may be quoted so that it can begin or end with spaces or contain
+//! `)` or `}`, with `\"` and `\\` escaping a quote and a backslash. The
+//! precision applies to each element and the width to the joined output.
+//!
+//! An `or(text)` directive substitutes `text` for an argument whose output
+//! is empty, such as an empty string or a `None`, as in `{nick:or(none)}`.
+//! The text may be quoted as for `join(sep)`, and the width applies to
+//! whichever output is chosen.
+//!
+//! A `maxlen(n)` directive truncates an argument's output to its first `n`
+//! characters followed by `…`, as in `{items:?maxlen(50)}`, which bounds the
+//! length of `Debug` output for large collections. Truncation never splits a
+//! `char`, and the width applies to the truncated output.
+//!
+//! The type `%` multiplies a number by 100 and appends `%`, and `permille`
+//! multiplies it by 1000 and appends `‰`, as in `{ratio:.1%}`. The precision
+//! applies to the scaled value, so 0.1234 becomes `12.3%`.
+//!
+//! The type `hexdump` formats a byte slice, `Vec<u8>`, byte array, or string
+//! as lines of an offset, hexadecimal bytes, and their ASCII rendering, as in
+//! `{bytes:hexdump}`. The precision sets the number of bytes per line, which
+//! is 16 by default. Other types are reported as `UnsatisfiedFormat`.
+//!
+//! The type `disp_or_debug` formats an argument with `Display` if its type
+//! implements it and with `Debug` otherwise, as in `{value:disp_or_debug}`,
+//! which suits generic logging helpers. A type implementing neither is
+//! reported as `UnsatisfiedFormat`.
+//!
+//! The type `raw` writes a `str`, `String`, or other `AsRef<str>` verbatim
+//! rather than through `Display`, as in `{fragment:raw}`, for splicing in
+//! text which has already been rendered. The width and precision apply as
+//! they do for `str`. Other types are reported as `UnsatisfiedFormat`.
+//!
+//! The types `html`, `shell`, and `json` format an argument with `Display`
+//! and then escape the result, as in `<b>{user:html}</b>`. `html` replaces
+//! `&`, `<`, `>`, `"`, and `'` with character references, `shell` quotes the
+//! output as a single word for POSIX shells, and `json` quotes it as a JSON
+//! string. The precision applies to the value before it is escaped, and the
+//! width to the escaped output. Literal text is never escaped.
+//!
+//! Values which have already been erased to trait objects, such as
+//! `Box<Display>`, `Rc<Debug>`, or `&Display`, may be formatted with the
+//! trait they were erased to, since the standard library implements each of
+//! its formatting traits for `Box`, `Rc`, `Arc`, and references to types
+//! implementing them. The other traits are unavailable through such a value,
+//! even if the original type implemented them, and are reported as
+//! `UnsatisfiedFormat`.
+//!
+//! An index may be counted back from the end with a `-`, as in `{-1}` for the
+//! last argument. One out of range is reported as `BadIndexFromEnd`.
+//!
+//! The fill character may be taken from a named `char` argument by writing
+//! its name and a `$` before the alignment, as in `{val:fill$>w$.p$}`, much
+//! as widths and precisions may be. An argument which is not a `char` is
+//! reported as `BadFill`.
+//!
+//! Names may also be dotted paths, such as `{user.address.city}` or
+//! `{items.0.id}`, which descend into the values of `FormatArgs` types and
+//! `DynFormatArgs` values. Numeric segments are indices and all others are
+//! names; a dotted path is always interpreted this way, even if a name
+//! containing a `.` exists. A `PreparedFormat` checks only the first segment
+//! of a path when prepared, since the rest descend into values not yet known,
+//! and formats a path which then fails to resolve as the empty string.
+//!
+//! A name may be written in double quotes, as in `{"weird:name":?}`, to use
+//! it literally: a `:` or `.` inside the quotes is part of the name, and `\"`
+//! and `\\` stand for `"` and `\`.
+//!
+//! A `;` ends a format specifier and begins a comment which runs to the
+//! closing brace and is ignored, as in `{total:>10 ; right aligned}`.
+//!
+//! Indices, widths, and precisions written in a format string may be at most
+//! `usize::MAX`; larger values are reported as `BadSyntax` rather than
+//! overflowing.
+//!
+//! The `std` feature, enabled by default, may be disabled to use this crate in
+//! `no_std` environments which provide `alloc`. The `rt_print!` family of
+//! macros and the `io::Write` support are then unavailable.
+//!
+//! The `unicode-width` feature adds `display_width`, which measures widths in
+//! terminal columns rather than in `char`s, so that wide and combining
+//! characters are padded correctly.
+//!
+//! The `log` feature adds `rt_log!` and the `rt_error!` through `rt_trace!`
+//! macros, which format a message only if its level is enabled and pass it to
+//! the `log` crate.
+//!
+//! `DynFormatArgs` is implemented for `BTreeMap` and `HashMap` with string
+//! keys, so that their values may be formatted by key through
+//! `FormatBuf::new_dyn`. Their values may not be referred to by position.
+//! Values of differing types may be formatted together by boxing them as
+//! trait objects, as with `HashMap<String, Box<Display>>`. This takes the
+//! place of a `FormatArgs` adapter over a map, which cannot be written, since
+//! `FormatArgs` resolves names before any value is at hand.
+//!
+//! Likewise, `DynFormatArgs` is implemented for `&[T]` and `Vec<T>`, so that
+//! their elements may be formatted by position. Since their lengths are known
+//! only at runtime, they do not implement `FormatArgs`, and an index past the
+//! end is reported as `BadIndex` when the format is built against the value.
+//!
+//! A `FormatBuilder` collects arguments whose number and names are known only
+//! at runtime, taking ownership of each, and formats them as `rt_format!`
+//! would format the same arguments.
+//!
+//! The `serde_json` feature implements `DynFormatArgs` for
+//! `serde_json::Value`, so that object keys and array elements may be
+//! formatted by name and index through `FormatBuf::new_dyn`.
+//!
+//! The `serialize` feature implements serde's `Serialize` and `Deserialize`
+//! for the pieces of `ast::parse`, so that format strings may be parsed ahead
+//! of time and prepared from the loaded pieces with
+//! `PreparedFormat::from_pieces`. Text containing escapes, such as a literal
+//! newline, must be loaded through `ast::OwnedPiece`.
+#![cfg_attr(not(feature = "std"), no_std)]
+#![cfg_attr(not(feature = "std"), feature(alloc))]
 #![feature(fmt_internals)]
-#![feature(conservative_impl_trait)]
 #![feature(specialization)]
 #![feature(unicode)]
 #![feature(print_internals)]
 #![feature(try_from)]
 
+#[cfg(not(feature = "std"))]
+#[macro_use]
+extern crate alloc;
+#[cfg(not(feature = "std"))]
+extern crate std_unicode;
+#[cfg(feature = "log")]
+extern crate log;
+#[cfg(feature = "serialize")]
+extern crate serde;
+#[cfg(feature = "serialize")]
+#[macro_use]
+extern crate serde_derive;
+#[cfg(feature = "serde_json")]
+extern crate serde_json;
+#[cfg(feature = "unicode-width")]
+extern crate unicode_width;
+
+// Without libstd, gather up the parts of it this crate uses from libcore and
+// liballoc, so that the rest of the crate can refer to them as usual.
+#[cfg(not(feature = "std"))]
+mod std {
+    pub use core::*;
+    pub use alloc::{borrow, boxed, fmt, string, vec};
+    pub mod collections {
+        pub use alloc::btree_map::BTreeMap;
+        pub use alloc::btree_set::BTreeSet;
+    }
+}
+
+#[cfg(feature = "std")]
 #[doc(hidden)]
 #[inline]
 pub fn _print(args: Arguments) {
     std::io::_print(args)
 }
 
+#[cfg(feature = "std")]
+#[doc(hidden)]
+#[inline]
+pub fn _eprint(args: Arguments) {
+    std::io::_eprint(args)
+}
+
+#[doc(hidden)]
+#[cold]
+pub fn _panic(buf: Result<FormatBuf, Error>) -> ! {
+    match buf {
+        Ok(buf) => panic!("{}", buf),
+        Err(error) => panic!("{}", error),
+    }
+}
+
+#[doc(hidden)]
+pub fn _write_counted<'s, W>(dest: &mut W, buf: Result<FormatBuf<'s>, Error<'s>>)
+    -> Result<usize, Error<'s>>
+    where W: fmt::Write + ?Sized
+{
+    // Tally the length of everything written through to `dest`.
+    struct Counted<'a, W: fmt::Write + ?Sized + 'a> {
+        dest: &'a mut W,
+        count: usize,
+    }
+
+    impl<'a, W: fmt::Write + ?Sized> fmt::Write for Counted<'a, W> {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            self.dest.write_str(s)?;
+            self.count += s.len();
+            Ok(())
+        }
+    }
+
+    let mut counted = Counted { dest: dest, count: 0 };
+    buf?.with(|args| fmt::write(&mut counted, args))?;
+    Ok(counted.count)
+}
+
+#[cfg(feature = "log")]
+#[doc(hidden)]
+pub use logging::{enabled as _log_enabled, log as _log};
+#[cfg(feature = "log")]
+#[doc(hidden)]
+pub use log::Level as _log_level;
+
+pub mod ast;
+#[cfg(feature = "std")]
+mod cache;
 pub mod codegen;
 mod erase;
+mod gather;
+mod impls;
+mod inline;
+#[cfg(feature = "serde_json")]
+mod json;
+mod literal;
+#[cfg(feature = "log")]
+mod logging;
 mod macros;
+mod maps;
+mod seqs;
+mod transform;
 
 // fmt_macros.rs is from rust/src/libfmt_macros/lib.rs
 // copy-pasted rather than externed to avoid dynamically linking libstd
 mod fmt_macros;
 
+#[cfg(feature = "std")]
 use std::io;
 use std::fmt::{self, Arguments, ArgumentV1};
 use std::fmt::rt::v1;
 use std::borrow::Cow;
-use std::marker::PhantomData;
-
-pub use codegen::FormatArgs;
+use std::boxed::Box;
+use std::collections::BTreeSet;
+use std::ops::Range;
+use std::string::String;
+use std::vec::Vec;
+use ast::FormatTraitKind;
+use codegen::Join;
+use gather::Gathered;
+use inline::InlineVec;
+use literal::{Literal, Pieces};
+use transform::{Options, Transform, Transformed, Value};
+
+pub use codegen::{DynFormatArgs, FormatArgs, FormatTrait, IndexError};
+pub use fmt_macros::ParseError;
+#[cfg(feature = "std")]
+pub use cache::{CacheStats, FormatCache};
 
 /// An error during parsing or formatting.
 #[derive(Debug)]
 pub enum Error<'a> {
     /// Invalid format string syntax.
-    BadSyntax(Vec<(String, Option<String>)>),
+    BadSyntax(Vec<ParseError>),
     /// A format specifier referred to an out-of-range index.
     BadIndex(usize),
+    /// A format specifier referred to an out-of-range index counted from the
+    /// end, such as `{-3}`. This holds its distance from the end, such as 3.
+    BadIndexFromEnd(usize),
     /// A format specifier referred to a non-existent name.
     BadName(&'a str),
+    /// A format specifier referred to a name which a dynamic value knows of
+    /// but has no value for, such as a `null` member of a JSON object.
+    MissingValue(&'a str),
     /// A format specifier referred to a non-existent type.
     NoSuchFormat(&'a str),
     /// A format specifier's type was not satisfied by its argument.
@@ -61,12 +311,24 @@ pub enum Error<'a> {
     },
     /// A parameter was of a type not suitable for use as a count.
     BadCount(usize),
+    /// A parameter named as a width or precision, as with `{:w$}`, was of a
+    /// type not suitable for use as a count.
+    BadNamedCount {
+        /// Which count the name was given as: `"width"` or `"precision"`.
+        count: &'static str,
+        /// The name given for the count.
+        name: &'a str,
+    },
+    /// A parameter named as a fill, as with `{:pad$>5}`, was not a `char`.
+    BadFill(usize),
     /// An I/O error from an `rt_write!` or `rt_writeln!` call.
+    #[cfg(feature = "std")]
     Io(std::io::Error),
     /// A formatting error from an `rt_write!` or `rt_writeln!` call.
     Fmt(std::fmt::Error),
 }
 
+#[cfg(feature = "std")]
 impl<'a> From<std::io::Error> for Error<'a> {
     fn from(e: std::io::Error) -> Self {
         Error::Io(e)
@@ -79,15 +341,18 @@ impl<'a> From<std::fmt::Error> for Error<'a> {
     }
 }
 
+#[cfg(feature = "std")]
 impl<'a> std::error::Error for Error<'a> {
     fn description(&self) -> &str {
         match *self {
             Error::BadSyntax(_) => "bad syntax",
-            Error::BadIndex(_) => "out-of-range index",
+            Error::BadIndex(_) | Error::BadIndexFromEnd(_) => "out-of-range index",
             Error::BadName(_) => "unknown name",
+            Error::MissingValue(_) => "missing value",
             Error::NoSuchFormat(_) => "bad formatting specifier",
             Error::UnsatisfiedFormat{..} => "formatting trait not satisfied",
-            Error::BadCount(_) => "non-integer used as count",
+            Error::BadCount(_) | Error::BadNamedCount{..} => "non-integer used as count",
+            Error::BadFill(_) => "non-char used as fill",
             Error::Io(ref e) => e.description(),
             Error::Fmt(ref f) => f.description(),
         }
@@ -105,11 +370,17 @@ impl<'a> fmt::Display for Error<'a> {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             Error::BadIndex(i) => write!(fmt, "index {} out of range", i),
-            Error::BadName(n) => write!(fmt, "unknown name {:?}", n),
+            Error::BadIndexFromEnd(i) => write!(fmt, "index -{} out of range", i),
+            Error::BadName(n) => write!(fmt, "no argument named '{}'", n),
+            Error::MissingValue(n) => write!(fmt, "no value for '{}'", n),
             Error::NoSuchFormat(c) => write!(fmt, "bad formatting specifier {:?}", c),
             Error::UnsatisfiedFormat { idx, must_implement } =>
                 write!(fmt, "argument {} does not implement {}", idx, must_implement),
             Error::BadCount(i) => write!(fmt, "argument {} cannot be used as a count", i),
+            Error::BadNamedCount { count, name } =>
+                write!(fmt, "{} argument '{}' is not a usize", count, name),
+            Error::BadFill(i) => write!(fmt, "argument {} cannot be used as a fill", i),
+            #[cfg(feature = "std")]
             Error::Io(ref e) => e.fmt(fmt),
             Error::Fmt(ref e) => e.fmt(fmt),
             Error::BadSyntax(ref errors) => {
@@ -117,8 +388,8 @@ impl<'a> fmt::Display for Error<'a> {
                     if i > 0 {
                         fmt.write_str("; ")?;
                     }
-                    fmt.write_str(&err.0)?;
-                    if let Some(ref more) = err.1 {
+                    write!(fmt, "{} at offset {}", err.description, err.span.start)?;
+                    if let Some(ref more) = err.note {
                         write!(fmt, " ({})", more)?;
                     }
                 }
@@ -128,11 +399,64 @@ impl<'a> fmt::Display for Error<'a> {
     }
 }
 
+impl<'a> Error<'a> {
+    /// Display this error along with the format string which caused it.
+    ///
+    /// Beneath the message, each syntax error's line of `spec` is shown with
+    /// carets marking where parsing failed. Other errors are displayed as
+    /// usual.
+    #[inline]
+    pub fn with_source<'e>(&'e self, spec: &'e str) -> WithSource<'e, 'a> {
+        WithSource { error: self, spec: spec }
+    }
+}
+
+/// An error displayed along with its format string, from `Error::with_source`.
+pub struct WithSource<'e, 'a: 'e> {
+    error: &'e Error<'a>,
+    spec: &'e str,
+}
+
+impl<'e, 'a> fmt::Display for WithSource<'e, 'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        self.error.fmt(fmt)?;
+        let errors = match *self.error {
+            Error::BadSyntax(ref errors) => errors,
+            _ => return Ok(()),
+        };
+        for err in errors {
+            let start = err.span.start;
+            let (before, after) = match (self.spec.get(..start), self.spec.get(start..)) {
+                (Some(before), Some(after)) => (before, after),
+                _ => continue,
+            };
+            let before = &before[before.rfind('\n').map_or(0, |i| i + 1)..];
+            let after = &after[..after.find('\n').unwrap_or(after.len())];
+            let marked = after.char_indices()
+                .take_while(|&(i, _)| i < err.span.end.saturating_sub(start))
+                .count();
+            write!(fmt, "\n{}{}\n", before, after)?;
+            for _ in before.chars() {
+                fmt.write_str(" ")?;
+            }
+            for _ in 0..std::cmp::max(marked, 1) {
+                fmt.write_str("^")?;
+            }
+        }
+        Ok(())
+    }
+}
+
 /// A type-erased parameter, with an optional name.
+///
+/// Names need not be known at compile time, so a set of parameters may be
+/// collected from a runtime source such as a map and passed to
+/// `FormatBuf::new` directly.
 pub struct Param<'a> {
-    name: Option<&'static str>,
+    name: Option<&'a str>,
     value: &'a erase::Format,
     as_usize: Option<usize>,
+    as_char: Option<char>,
 }
 
 impl<'a> Param<'a> {
@@ -142,298 +466,1692 @@ impl<'a> Param<'a> {
         Param {
             name: None,
             as_usize: t.as_usize(),
+            as_char: t.as_char(),
             value: t,
         }
     }
 
     /// Create a named parameter from the given value.
-    pub fn named<T>(name: &'static str, t: &'a T) -> Param<'a> {
+    pub fn named<T>(name: &'a str, t: &'a T) -> Param<'a> {
         use erase::Format;
         Param {
             name: Some(name),
             as_usize: t.as_usize(),
+            as_char: t.as_char(),
             value: t,
         }
     }
 }
 
-enum PreparedArgument<T> {
-    Normal(fn(&T, &mut fmt::Formatter) -> fmt::Result),
-    Usize(fn(&T) -> &usize),
-}
-impl<T> Copy for PreparedArgument<T> {}
-impl<T> Clone for PreparedArgument<T> {
-    fn clone(&self) -> Self { *self }
+/// A list of arguments assembled at runtime, for formatting without a macro.
+///
+/// Arguments are added in turn by `arg` and `named`, which take ownership of
+/// their values. As with the arguments to `rt_format!`, every argument has a
+/// position, whether or not it also has a name, so positional and named
+/// arguments may be mixed freely.
+#[derive(Default)]
+pub struct FormatBuilder<'a> {
+    args: Vec<BuiltArg<'a>>,
 }
 
-/// A pre-checked format string, ready for values of a specific type to be
-/// formatted against it.
-pub struct PreparedFormat<'s, T: FormatArgs> {
-    inner: Parsed<'s, DelayedParse<T>>,
+struct BuiltArg<'a> {
+    name: Option<&'a str>,
+    value: Box<erase::Format + 'a>,
+    as_usize: Option<usize>,
+    as_char: Option<char>,
 }
 
-impl<'s, T: FormatArgs> PreparedFormat<'s, T> {
-    /// Prepare a format string against a formattable type.
-    ///
-    /// Once the format string has been prepared, formatting individual values
-    /// will not require checking the validity of the format string over again.
+impl<'a> FormatBuilder<'a> {
+    /// Create a builder with no arguments.
     #[inline]
-    pub fn prepare(spec: &'s str) -> Result<Self, Error> {
-        parse(spec, &mut DelayedParse::<T>(PhantomData))
-            .map(|result| PreparedFormat { inner: result })
+    pub fn new() -> Self {
+        FormatBuilder { args: Vec::new() }
     }
 
-    /// Append a linefeed (`\n`) to the end of this buffer.
+    /// Add a nameless argument.
     #[inline]
-    pub fn newln(&mut self) -> &mut Self {
-        self.inner.newln();
-        self
+    pub fn arg<T: 'a>(&mut self, t: T) -> &mut Self {
+        self.push(None, t)
     }
 
-    /// Call a function accepting `Arguments` with the contents of this buffer.
-    pub fn with<F: FnOnce(Arguments) -> R, R>(&self, t: &T, f: F) -> R {
-        let pieces = self.inner.pieces();
-        let args: Vec<ArgumentV1> = self.inner.args.iter().map(|f| match *f {
-            PreparedArgument::Normal(func) => ArgumentV1::new(t, func),
-            PreparedArgument::Usize(func) => ArgumentV1::from_usize(func(t)),
-        }).collect();
-        f(match self.inner.fmt {
-            Some(ref fmt) => Arguments::new_v1_formatted(&pieces, &args, fmt),
-            None => Arguments::new_v1(&pieces, &args),
-        })
+    /// Add a named argument.
+    #[inline]
+    pub fn named<T: 'a>(&mut self, name: &'a str, t: T) -> &mut Self {
+        self.push(Some(name), t)
     }
 
-    /// Format the given value to a `String`.
-    #[inline]
-    pub fn format(&self, t: &T) -> String {
-        self.with(t, ::std::fmt::format)
+    fn push<T: 'a>(&mut self, name: Option<&'a str>, t: T) -> &mut Self {
+        use erase::Format;
+        self.args.push(BuiltArg {
+            name: name,
+            as_usize: t.as_usize(),
+            as_char: t.as_char(),
+            value: Box::new(t),
+        });
+        self
     }
 
-    /// Print the given value to standard output.
-    #[inline]
-    pub fn print(&self, t: &T) {
-        self.with(t, _print)
+    /// Format the arguments added so far with the given format string.
+    pub fn format<'s, S>(&'s self, spec: &'s S) -> Result<String, Error<'s>>
+        where S: AsRef<str> + ?Sized
+    {
+        FormatBuf::new_dyn(spec, self).map(|buf| buf.format())
     }
+}
 
-    /// Write the given value to an `io::Write`.
-    #[inline]
-    pub fn write_io<W: io::Write + ?Sized>(&self, t: &T, dest: &mut W) -> io::Result<()> {
-        self.with(t, |args| dest.write_fmt(args))
+impl<'a> DynFormatArgs for FormatBuilder<'a> {
+    fn validate_name(&self, name: &str) -> Option<usize> {
+        self.args.iter().position(|arg| arg.name.map_or(false, |n| n == name))
     }
 
-    /// Write the given value to a `fmt::Write`.
     #[inline]
-    pub fn write_fmt<W: fmt::Write + ?Sized>(&self, t: &T, dest: &mut W) -> fmt::Result {
-        self.with(t, |args| dest.write_fmt(args))
+    fn validate_index(&self, index: usize) -> bool {
+        index < self.args.len()
     }
-}
 
-impl<'s, T: FormatArgs> Clone for PreparedFormat<'s, T> {
-    fn clone(&self) -> Self {
-        PreparedFormat { inner: self.inner.clone() }
-    }
-    fn clone_from(&mut self, source: &Self) {
-        self.inner.clone_from(&source.inner)
+    #[inline]
+    fn arg_count(&self) -> usize {
+        self.args.len()
     }
-}
-
-/// A buffer representing a parsed format string and arguments.
-#[derive(Clone)]
-pub struct FormatBuf<'s> {
-    inner: Parsed<'s, ImmediateParse<'s>>,
-}
 
-impl<'s> FormatBuf<'s> {
-    /// Construct a new buffer from the given format string and arguments.
-    ///
-    /// This method should usually not be called directly. Instead use the
-    /// `rt_format_args!` macro.
-    #[inline]
-    pub fn new(spec: &'s str, params: &'s [Param<'s>]) -> Result<Self, Error<'s>> {
-        parse(spec, &mut ImmediateParse(params))
-            .map(|result| FormatBuf { inner: result })
+    fn format_child<'b, 's>(&'b self, spec: &'s str, index: usize)
+        -> Result<ArgumentV1<'b>, Error<'s>>
+    {
+        self.args[index].value.by_name(spec, index)
     }
 
-    /// Append a linefeed (`\n`) to the end of this buffer.
-    #[inline]
-    pub fn newln(&mut self) -> &mut Self {
-        self.inner.newln();
-        self
+    fn is_float(&self, index: usize) -> bool {
+        self.args[index].value.is_float()
     }
 
-    /// Call a function accepting `Arguments` with the contents of this buffer.
-    pub fn with<F: FnOnce(Arguments) -> R, R>(&self, f: F) -> R {
-        let pieces = self.inner.pieces();
-        f(match self.inner.fmt {
-            Some(ref fmt) => Arguments::new_v1_formatted(&pieces, &self.inner.args, fmt),
-            None => Arguments::new_v1(&pieces, &self.inner.args),
-        })
+    fn as_usize(&self, index: usize) -> Option<&usize> {
+        self.args[index].as_usize.as_ref()
     }
 
-    /// Format this buffer to a `String`.
-    #[inline]
-    pub fn format(&self) -> String {
-        self.with(::std::fmt::format)
+    fn as_char(&self, index: usize) -> Option<&char> {
+        self.args[index].as_char.as_ref()
     }
 
-    /// Print this buffer to standard output.
-    #[inline]
-    pub fn print(&self) {
-        self.with(_print)
+    fn as_str(&self, index: usize) -> Option<&str> {
+        self.args[index].value.as_str()
     }
 
-    /// Write this buffer to an `io::Write`.
-    #[inline]
-    pub fn write_io<W: io::Write + ?Sized>(&self, dest: &mut W) -> io::Result<()> {
-        self.with(|args| dest.write_fmt(args))
+    fn as_f64(&self, index: usize) -> Option<f64> {
+        self.args[index].value.as_f64()
     }
 
-    /// Write this buffer to a `fmt::Write`.
-    #[inline]
-    pub fn write_fmt<W: fmt::Write + ?Sized>(&self, dest: &mut W) -> fmt::Result {
-        self.with(|args| dest.write_fmt(args))
+    fn nested(&self, index: usize) -> Option<&DynFormatArgs> {
+        self.args[index].value.as_dyn_args()
     }
-}
 
-impl<'a> fmt::Display for FormatBuf<'a> {
-    #[inline]
-    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        self.with(|args| fmt.write_fmt(args))
+    fn joinable(&self, index: usize) -> Option<&Join> {
+        self.args[index].value.as_join()
     }
 }
 
-impl<'a> fmt::Debug for FormatBuf<'a> {
-    #[inline]
-    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        fmt::Display::fmt(self, fmt)
-    }
+enum PreparedArgument<T> {
+    // The index of the value formatted and the name of its trait are kept
+    // for `required_traits`.
+    Normal(fn(&T, &mut fmt::Formatter) -> fmt::Result, usize, &'static str),
+    Usize(fn(&T) -> &usize),
+    Char(fn(&T) -> &char),
+    // A sequence for `join(...)`, and the index of the value it views.
+    Join(fn(&T) -> Option<&Join>, usize),
+    // A dotted path, resolved against the value its first segment names as
+    // each value is formatted. The rest of the path and the trait to format
+    // with are held among the texts, at the given index and the one after.
+    Path(fn(&T) -> Option<&DynFormatArgs>, usize),
 }
 
-trait ParseTarget<'p> {
-    type Argument;
-    fn validate_name(&mut self, name: &str) -> Option<usize>;
-    fn validate_index(&mut self, index: usize) -> bool;
-    fn format<'s>(&mut self, spec: &'s str, idx: usize) -> Result<Self::Argument, Error<'s>>;
-    fn format_usize(&mut self, idx: usize) -> Option<Self::Argument>;
+// What a prepared path which fails to resolve formats as.
+static UNRESOLVED: &'static str = "";
+impl<T> Copy for PreparedArgument<T> {}
+impl<T> Clone for PreparedArgument<T> {
+    fn clone(&self) -> Self { *self }
 }
 
-struct ImmediateParse<'p>(&'p [Param<'p>]);
+type CustomTrait<T> = fn(usize) -> Option<fn(&T, &mut fmt::Formatter) -> fmt::Result>;
 
-impl<'p> ParseTarget<'p> for ImmediateParse<'p> {
-    type Argument = ArgumentV1<'p>;
+/// Options which control how a format string is prepared.
+///
+/// This builder is used to prepare format strings which make use of custom
+/// formatting traits. The simple case is covered by `PreparedFormat::prepare`.
+pub struct PrepareOptions<T: FormatArgs> {
+    custom: Vec<(&'static str, CustomTrait<T>)>,
+    missing: Option<Box<Fn(&str) -> String>>,
+    default_trait: DefaultTrait,
+}
 
-    fn validate_name(&mut self, name: &str) -> Option<usize> {
-        self.0.iter().position(|p| p.name.map_or(false, |n| n == name))
-    }
+/// The formatting trait used by references which name none, such as `{}`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum DefaultTrait {
+    /// Format with `Display`, as the standard library does.
+    Display,
+    /// Format with `Debug`, as if `?` had been written.
+    Debug,
+}
 
-    fn validate_index(&mut self, index: usize) -> bool {
-        index < self.0.len()
+impl Default for DefaultTrait {
+    fn default() -> Self {
+        DefaultTrait::Display
     }
+}
 
-    fn format<'s>(&mut self, spec: &'s str, idx: usize) -> Result<Self::Argument, Error<'s>> {
-        self.0[idx].value.by_name(spec, idx)
+impl<T: FormatArgs> PrepareOptions<T> {
+    /// Create a blank set of options, which recognize only the standard
+    /// formatting traits.
+    pub fn new() -> Self {
+        PrepareOptions {
+            custom: Vec::new(),
+            missing: None,
+            default_trait: DefaultTrait::Display,
+        }
     }
 
-    fn format_usize(&mut self, idx: usize) -> Option<Self::Argument> {
-        self.0[idx].as_usize.as_ref().map(ArgumentV1::from_usize)
+    /// Replace references to names which `T` does not recognize with text
+    /// produced by `fallback`, rather than failing with `BadName`.
+    ///
+    /// The fallback receives the unknown name and is called while preparing,
+    /// so its text is fixed for every value formatted. A reference whose
+    /// width or precision names an unknown argument is likewise replaced as
+    /// a whole. Bad indices and unsatisfied traits remain errors.
+    pub fn missing<F: Fn(&str) -> String + 'static>(&mut self, fallback: F) -> &mut Self {
+        self.missing = Some(Box::new(fallback));
+        self
     }
-}
 
-struct DelayedParse<T>(PhantomData<fn(&T)>);
-
-impl<'p, T: FormatArgs> ParseTarget<'p> for DelayedParse<T> {
-    type Argument = PreparedArgument<T>;
+    /// Set the formatting trait used by references which name none.
+    ///
+    /// With `DefaultTrait::Debug`, `{x}` behaves like `{x:?}` and `{x:#}`
+    /// like `{x:#?}`, so that a whole format string may be switched to debug
+    /// output. The default is `DefaultTrait::Display`.
+    pub fn default_trait(&mut self, default: DefaultTrait) -> &mut Self {
+        self.default_trait = default;
+        self
+    }
 
-    fn validate_name(&mut self, name: &str) -> Option<usize> {
-        T::validate_name(name)
+    /// Recognize `name` in format specifiers, such as `{:name}`, as referring
+    /// to the formatting trait `F`.
+    ///
+    /// `F` is typically a marker type whose `FormatTrait` implementation
+    /// forwards to a user-defined trait. Because `FormatArgs::get_child` is
+    /// generic over the formatting trait, derived implementations need no
+    /// knowledge of `F`. Custom names are checked before the standard ones,
+    /// and so may replace them.
+    pub fn custom_trait<F: FormatTrait + ?Sized>(&mut self, name: &'static str) -> &mut Self {
+        self.custom.push((name, T::get_child::<F>));
+        self
     }
 
-    fn validate_index(&mut self, index: usize) -> bool {
-        T::validate_index(index)
+    /// Prepare a format string against a formattable type using these options.
+    pub fn prepare<'s, S>(&self, spec: &'s S) -> Result<PreparedFormat<'s, T>, Error<'s>>
+        where S: AsRef<str> + ?Sized
+    {
+        parse(spec.as_ref(), &mut DelayedParse::new(self))
+            .map(|result| PreparedFormat { inner: result })
     }
 
-    fn format<'s>(&mut self, spec: &'s str, idx: usize) -> Result<Self::Argument, Error<'s>> {
-        erase::codegen_get_child::<T>(spec, idx).map(PreparedArgument::Normal)
+    /// Prepare already-parsed pieces, such as those produced by
+    /// `ast::parse`, against a formattable type using these options.
+    pub fn prepare_pieces<'s>(&self, pieces: &[ast::Piece<'s>])
+        -> Result<PreparedFormat<'s, T>, Error<'s>>
+    {
+        let mut pieces = pieces.iter().map(|&piece| piece.into());
+        inner_parse(&mut pieces, &mut DelayedParse::new(self))
+            .map(|result| PreparedFormat { inner: result })
     }
+}
 
-    fn format_usize(&mut self, idx: usize) -> Option<Self::Argument> {
-        T::as_usize(idx).map(PreparedArgument::Usize)
+impl<T: FormatArgs> Default for PrepareOptions<T> {
+    fn default() -> Self {
+        PrepareOptions::new()
     }
 }
 
-struct Parsed<'s, P: ParseTarget<'s>> {
-    pieces: Vec<Cow<'s, str>>,
-    args: Vec<P::Argument>,
-    fmt: Option<Vec<v1::Argument>>,
+/// A pre-checked format string, ready for values of a specific type to be
+/// formatted against it.
+pub struct PreparedFormat<'s, T: FormatArgs> {
+    inner: Parsed<'s, PreparedArgument<T>>,
 }
 
-impl<'s, P: ParseTarget<'s>> Clone for Parsed<'s, P>
-    where P::Argument: Clone
-{
-    fn clone(&self) -> Self {
-        Parsed {
-            pieces: self.pieces.clone(),
-            args: self.args.clone(),
-            fmt: self.fmt.clone(),
-        }
+impl<'s, T: FormatArgs> PreparedFormat<'s, T> {
+    /// Prepare a format string against a formattable type.
+    ///
+    /// Once the format string has been prepared, formatting individual values
+    /// will not require checking the validity of the format string over again.
+    ///
+    /// Literal text is borrowed from `spec` where possible, copying only the
+    /// literals containing escapes such as `{{`. See `prepare_borrowed` for
+    /// static templates.
+    ///
+    /// The format string may be a `str`, `String`, `Cow<str>`, or anything
+    /// else which implements `AsRef<str>`.
+    #[inline]
+    pub fn prepare<S: AsRef<str> + ?Sized>(spec: &'s S) -> Result<Self, Error<'s>> {
+        PrepareOptions::new().prepare(spec)
     }
 
-    fn clone_from(&mut self, source: &Self) {
-        self.pieces.clone_from(&source.pieces);
-        self.args.clone_from(&source.args);
-        self.fmt.clone_from(&source.fmt);
+    /// Prepare a static template, borrowing its pieces for `'static`.
+    ///
+    /// A template of up to four pieces and four arguments is kept inline and
+    /// allocates nothing, provided its literals contain no escapes such as
+    /// `{{` and its arguments need no post-processing such as grouping or a
+    /// directive. Larger templates spill onto the heap as `prepare` would.
+    #[inline]
+    pub fn prepare_borrowed(spec: &'static str)
+        -> Result<PreparedFormat<'static, T>, Error<'static>>
+    {
+        PrepareOptions::new().prepare(spec)
     }
-}
 
-impl<'s, P: ParseTarget<'s>> Parsed<'s, P> {
-    fn newln(&mut self) {
-        // If fmt is None, the number of implicit formatting specifiers
-        // is the same as the number of arguments.
-        let len = self.fmt.as_ref().map_or(self.args.len(), |fmt| fmt.len());
-        if self.pieces.len() > len {
-            // The final piece is after the final formatting specifier, so
-            // it's okay to just add to the end of it.
-            self.pieces.last_mut().unwrap().to_mut().push_str("\n")
-        } else {
-            // The final piece is before the final formatting specifier, so
-            // a new piece needs to be added at the end.
-            self.pieces.push("\n".into())
+    /// Prepare already-parsed pieces against a formattable type.
+    ///
+    /// The pieces are those of `ast::parse`, possibly having been serialized
+    /// and loaded again with the `serialize` feature, so that the format
+    /// string itself need not be parsed at runtime. They are checked against
+    /// `T` just as `prepare` checks a format string.
+    #[inline]
+    pub fn from_pieces(pieces: &[ast::Piece<'s>]) -> Result<Self, Error<'s>> {
+        PrepareOptions::new().prepare_pieces(pieces)
+    }
+
+    /// Convert this format into one which owns its literal pieces.
+    ///
+    /// The result no longer borrows from the original format string, and so
+    /// may outlive it. Each piece is kept as a `Box<str>`, without spare
+    /// capacity.
+    pub fn into_owned(self) -> PreparedFormat<'static, T> {
+        let Parsed {
+            pieces, args, fmt, transforms, indices, positions, texts, fills, options
+        } = self.inner;
+        let mut texts: Vec<Cow<'static, str>> =
+            texts.into_iter().map(|s| Cow::Owned(s.into_owned())).collect();
+        let positions = positions.iter().map(|&(pos, referent)| {
+            (pos, referent.into_owned(&mut texts))
+        }).collect();
+        PreparedFormat {
+            inner: Parsed {
+                pieces: pieces.into_owned(),
+                args: args,
+                fmt: fmt,
+                transforms: transforms,
+                indices: indices,
+                positions: positions,
+                texts: texts,
+                fills: fills,
+                options: options,
+            }
         }
     }
 
+    /// Append a linefeed (`\n`) to the end of this buffer.
     #[inline]
-    fn pieces(&self) -> Vec<&str> {
-        self.pieces.iter().map(|r| &**r).collect()
+    pub fn newln(&mut self) -> &mut Self {
+        self.inner.newln();
+        self
     }
-}
 
-fn parse<'s, P: ParseTarget<'s>>(spec: &'s str, target: &mut P)
-    -> Result<Parsed<'s, P>, Error<'s>>
-{
-    let mut parser = fmt_macros::Parser::new(spec);
-    let result = inner_parse(&mut parser, target);
-    // Perform a separate check so that syntax errors take priority.
-    if parser.errors.is_empty() {
-        result
+    /// Replace each piece of literal text with the result of `f` upon it.
+    ///
+    /// Arguments and their specifiers are unaffected, as are the empty
+    /// literals between adjacent arguments. Escapes such as `{{` have already
+    /// been resolved, so `f` sees `{` rather than `{{`.
+    pub fn map_literals<F: Fn(&str) -> String>(&mut self, f: F) -> &mut Self {
+        self.inner.map_literals(f);
+        self
+    }
+
+    /// Set the separator placed between groups of digits by the `_` flag.
+    ///
+    /// The default separator is `,`. If `separator` is the current decimal
+    /// separator, the two are exchanged.
+    #[inline]
+    pub fn group_separator(&mut self, separator: char) -> &mut Self {
+        self.inner.options.set_group_separator(separator);
+        self
+    }
+
+    /// Set the separator between the integer and fractional parts of
+    /// floating-point numbers formatted with `Display`, `Debug`, `LowerExp`,
+    /// or `UpperExp`.
+    ///
+    /// The default separator is `.`. If `separator` is the current group
+    /// separator, the two are exchanged, so that `decimal_separator(',')`
+    /// alone selects `.` for grouping.
+    #[inline]
+    pub fn decimal_separator(&mut self, separator: char) -> &mut Self {
+        self.inner.options.set_decimal_separator(separator);
+        self
+    }
+
+    /// Enable or disable the escape sequences produced by `style(...)`.
+    ///
+    /// Styling is enabled by default. When disabled, styled arguments are
+    /// formatted as if no style had been requested.
+    #[inline]
+    pub fn styling(&mut self, enabled: bool) -> &mut Self {
+        self.inner.options.styling = enabled;
+        self
+    }
+
+    /// Enable or disable adding a `+` to `Display` output for the `+` flag.
+    ///
+    /// Primitive numbers honor `{:+}` themselves, but other types often
+    /// ignore it. When enabled, a `+` is prepended to the output of `{:+}`
+    /// if it begins with an ASCII digit. Output which begins otherwise, such
+    /// as `inf` or text, is left alone, and negative values must still
+    /// produce their own `-`. Disabled by default.
+    #[inline]
+    pub fn synthesize_sign(&mut self, enabled: bool) -> &mut Self {
+        self.inner.options.synthesize_sign = enabled;
+        self
+    }
+
+    /// Indent each level of pretty `Debug` output, as from `{:#?}`, with
+    /// `width` copies of `fill` rather than four spaces.
+    ///
+    /// The output is re-indented after formatting, so a `Debug`
+    /// implementation which itself writes lines beginning with spaces has
+    /// those spaces converted as well.
+    #[inline]
+    pub fn indent(&mut self, fill: char, width: usize) -> &mut Self {
+        self.inner.options.indent = Some((fill, width));
+        self
+    }
+
+    /// Measure widths in terminal columns, as given by the `unicode-width`
+    /// crate, rather than in `char`s.
+    ///
+    /// This is disabled by default.
+    #[cfg(feature = "unicode-width")]
+    #[inline]
+    pub fn display_width(&mut self, enabled: bool) -> &mut Self {
+        self.inner.options.display_width = enabled;
+        self
+    }
+
+    /// Estimate the length in bytes of this format's output, for reserving
+    /// capacity in a buffer.
+    ///
+    /// This is the length of the literal text plus, for each argument, its
+    /// width if one is given directly and a small guess otherwise. `format`
+    /// reserves this much capacity up front.
+    #[inline]
+    pub fn estimate_len(&self) -> usize {
+        self.inner.estimate_len()
+    }
+
+    /// The indices of every argument this format refers to, whether by index,
+    /// by name, or as a width or precision.
+    ///
+    /// Comparing this against `T::arg_count()` reveals unused arguments.
+    pub fn referenced_indices(&self) -> BTreeSet<usize> {
+        self.inner.indices.iter().cloned().collect()
+    }
+
+    /// The position, index, and formatting trait of each argument this format
+    /// formats, in order of appearance. Positions are as `ast::required_traits`
+    /// reports them, while the index is that which the position resolved to.
+    ///
+    /// Widths, precisions, and fills are not included, since they require a
+    /// `usize` or `char` rather than a trait, and neither are dotted paths,
+    /// which are resolved only as each value is formatted.
+    pub fn required_traits(&self) -> Vec<(ast::Position, usize, &'static str)> {
+        let texts = &self.inner.texts;
+        self.inner.positions.iter().filter_map(|&(pos, referent)| {
+            let (idx, name) = match self.inner.args[pos] {
+                PreparedArgument::Normal(_, idx, name) => (idx, name),
+                PreparedArgument::Join(_, idx) => (idx, transform::JOIN_TRAIT),
+                _ => return None,
+            };
+            Some((referent.position(texts), idx, name))
+        }).collect()
+    }
+
+    // The arguments to format `t` with, in the order of `inner.args`. A path
+    // which does not resolve against `t` formats as `UNRESOLVED`.
+    fn arguments<'a>(&'a self, t: &'a T) -> Gathered<'a, Value<'a>> {
+        let unresolved = Value::Normal(ArgumentV1::new(&UNRESOLVED, fmt::Display::fmt));
+        Gathered::new(unresolved, self.inner.args.iter().map(|f| match *f {
+            PreparedArgument::Normal(func, _, _) => Value::Normal(ArgumentV1::new(t, func)),
+            PreparedArgument::Usize(func) => Value::Normal(ArgumentV1::from_usize(func(t))),
+            PreparedArgument::Char(func) => {
+                Value::Normal(ArgumentV1::new(func(t), fmt::Display::fmt))
+            }
+            PreparedArgument::Join(func, _) => func(t).map_or(unresolved, Value::Join),
+            PreparedArgument::Path(nested, text) => {
+                let (rest, spec) = (&self.inner.texts[text], &self.inner.texts[text + 1]);
+                let resolved = nested(t).and_then(|value| {
+                    resolve_path(value, rest, rest, spec).ok()
+                });
+                match resolved {
+                    Some((arg, _, _)) => arg,
+                    None => unresolved,
+                }
+            }
+        }))
+    }
+
+    /// Call a function accepting `Arguments` with the contents of this buffer.
+    pub fn with<F: FnOnce(Arguments) -> R, R>(&self, t: &T, f: F) -> R {
+        let args = self.arguments(t);
+        self.inner.with(&args, f)
+    }
+
+    /// Format the given value to a `String`.
+    #[inline]
+    pub fn format(&self, t: &T) -> String {
+        match self.inner.literal() {
+            Some(literal) => String::from(literal),
+            None => {
+                let mut buf = String::with_capacity(self.estimate_len());
+                self.write_fmt(t, &mut buf)
+                    .expect("a formatting trait implementation returned an error");
+                buf
+            }
+        }
+    }
+
+    /// Format the given value to a `String`, or fail with `Error::Fmt` if a
+    /// formatting trait implementation returns an error.
+    #[inline]
+    pub fn render(&self, t: &T) -> Result<String, Error<'s>> {
+        let mut buf = String::new();
+        self.render_into(&mut buf, t)?;
+        Ok(buf)
+    }
+
+    /// Format the given value into an existing `String`, reusing its capacity.
+    ///
+    /// `buf` is cleared first, and is reserved `estimate_len()` bytes if it
+    /// has less capacity than that. If a formatting trait implementation
+    /// returns an error, `buf` holds the output up to the failing argument.
+    ///
+    /// Nothing else is allocated for formats of up to 16 pieces and arguments
+    /// which need no post-processing, such as digit grouping or escaping,
+    /// unless the arguments' own formatting traits allocate.
+    pub fn render_into(&self, buf: &mut String, t: &T) -> Result<(), Error<'s>> {
+        buf.clear();
+        match self.inner.literal() {
+            Some(literal) => buf.push_str(literal),
+            None => {
+                buf.reserve(self.estimate_len());
+                self.write_fmt(t, buf)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Format the given value, passing each piece of the output to `sink`.
+    ///
+    /// See `format_with_sink` for more information.
+    pub fn write_chunks<F: FnMut(Chunk)>(&self, t: &T, mut sink: F) -> fmt::Result {
+        let args = self.arguments(t);
+        self.inner.chunks(&args, |chunk, _| sink(chunk))
+    }
+
+    /// Format the given value to a `String`, along with the origin of each
+    /// range of the output.
+    ///
+    /// The ranges are in order and exactly cover the output. Each is the
+    /// output of a single literal piece or argument; those producing nothing
+    /// have no range. If a formatting trait implementation returns an error,
+    /// that error is returned instead.
+    pub fn render_spans(&self, t: &T)
+        -> Result<(String, Vec<(Range<usize>, PieceRef)>), Error<'s>>
+    {
+        let args = self.arguments(t);
+        let mut buf = String::with_capacity(self.estimate_len());
+        let mut spans = Vec::new();
+        self.inner.chunks(&args, |chunk, piece| {
+            let text = match chunk {
+                Chunk::Literal(text) | Chunk::Formatted(text) => text,
+            };
+            if !text.is_empty() {
+                let start = buf.len();
+                buf.push_str(text);
+                spans.push((start..buf.len(), piece));
+            }
+        })?;
+        Ok((buf, spans))
+    }
+
+    /// Print the given value to standard output.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn print(&self, t: &T) {
+        self.with(t, _print)
+    }
+
+    /// Write the given value to an `io::Write`.
+    #[cfg(feature = "std")]
+    #[deprecated(since = "0.2.0", note = "use `write_to`")]
+    #[inline]
+    pub fn write_io<W: io::Write + ?Sized>(&self, t: &T, dest: &mut W) -> io::Result<()> {
+        self.write_to(t, dest)
+    }
+
+    /// Write the given value to an `io::Write`.
+    ///
+    /// Output is streamed into `dest` as it is produced rather than first
+    /// being collected into a `String`. An I/O error from `dest` is returned
+    /// as is, and a formatting error from an argument becomes an error of kind
+    /// `io::ErrorKind::Other`. In either case, any bytes already written
+    /// remain written.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn write_to<W: io::Write + ?Sized>(&self, t: &T, dest: &mut W) -> io::Result<()> {
+        write_through(dest, |shim| self.write_fmt(t, shim))
+    }
+
+    /// Write the given value to a `fmt::Write`.
+    #[inline]
+    pub fn write_fmt<W: fmt::Write + ?Sized>(&self, t: &T, dest: &mut W) -> fmt::Result {
+        self.with(t, |args| dest.write_fmt(args))
+    }
+
+    /// Format the given value directly into a `Formatter`, as from within an
+    /// implementation of `Display`.
+    ///
+    /// No intermediate `String` is built, and as for `render_into`, small
+    /// formats allocate nothing. The width, fill, and flags of `f` itself are
+    /// not applied; each argument is formatted as this format specifies.
+    #[inline]
+    pub fn render_fmt(&self, f: &mut fmt::Formatter, t: &T) -> fmt::Result {
+        self.with(t, |args| f.write_fmt(args))
+    }
+}
+
+// Stream the text written by `write` into `dest`, through a `fmt::Write` shim
+// which keeps the I/O error that stopped it, if any.
+#[cfg(feature = "std")]
+fn write_through<W, F>(dest: &mut W, write: F) -> io::Result<()>
+    where W: io::Write + ?Sized, F: FnOnce(&mut fmt::Write) -> fmt::Result
+{
+    struct Shim<'a, W: io::Write + ?Sized + 'a> {
+        dest: &'a mut W,
+        error: Option<io::Error>,
+    }
+
+    impl<'a, W: io::Write + ?Sized> fmt::Write for Shim<'a, W> {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            match self.dest.write_all(s.as_bytes()) {
+                Ok(()) => Ok(()),
+                Err(e) => {
+                    self.error = Some(e);
+                    Err(fmt::Error)
+                }
+            }
+        }
+    }
+
+    let mut shim = Shim { dest: dest, error: None };
+    match write(&mut shim) {
+        Ok(()) => Ok(()),
+        Err(fmt::Error) => Err(shim.error.take().unwrap_or_else(|| {
+            io::Error::new(io::ErrorKind::Other, "an argument failed to format")
+        })),
+    }
+}
+
+impl<'s, T: FormatArgs> Clone for PreparedFormat<'s, T> {
+    fn clone(&self) -> Self {
+        PreparedFormat { inner: self.inner.clone() }
+    }
+    fn clone_from(&mut self, source: &Self) {
+        self.inner.clone_from(&source.inner)
+    }
+}
+
+/// Check a format string against a formattable type, collecting every error.
+///
+/// Where `PreparedFormat::prepare` stops at the first bad reference, this
+/// reports all of them in order of appearance in the format string. Syntax
+/// errors are reported on their own, since references cannot then be checked.
+pub fn validate_all<T: FormatArgs>(spec: &str) -> Result<(), Vec<Error>> {
+    let pieces = match ast::parse(spec) {
+        Ok(pieces) => pieces,
+        Err(e) => return Err(vec![e]),
+    };
+
+    let mut errors = Vec::new();
+    for piece in pieces {
+        let (position, format) = match piece {
+            ast::Piece::Argument { position, format } => (position, format),
+            ast::Piece::Literal(_) => continue,
+        };
+        let idx = match position {
+            ast::Position::Next(idx) | ast::Position::Index(idx) => {
+                if T::validate_index(idx) {
+                    Some(idx)
+                } else {
+                    errors.push(Error::BadIndex(idx));
+                    None
+                }
+            }
+            ast::Position::FromEnd(back) => {
+                let count = T::arg_count();
+                if back > 0 && back <= count {
+                    Some(count - back)
+                } else {
+                    errors.push(Error::BadIndexFromEnd(back));
+                    None
+                }
+            }
+            // Only the first segment of a path can be checked, and not the
+            // trait it is formatted with.
+            ast::Position::Named(name) if name.contains('.') => {
+                if nested_path::<T>(name).is_none() {
+                    errors.push(Error::BadName(name));
+                }
+                None
+            }
+            ast::Position::Named(name) => {
+                let idx = T::validate_name(name);
+                if idx.is_none() {
+                    errors.push(Error::BadName(name));
+                }
+                idx
+            }
+            ast::Position::Quoted(name) => {
+                let idx = T::validate_name(&unquote(name));
+                if idx.is_none() {
+                    errors.push(Error::BadName(name));
+                }
+                idx
+            }
+        };
+        let requested = match transform::directive(format.directive()) {
+            Ok(directive) => {
+                transform::trait_spec(format.trait_, format.ty, directive.as_ref())
+            }
+            Err(e) => Err(e),
+        };
+        match (idx, requested) {
+            (Some(idx), Ok((_, transform::JOIN))) => if let Err(e) = get_join::<T>(idx) {
+                errors.push(e);
+            },
+            (Some(idx), Ok((kind, ty))) => {
+                if let Err(e) = erase::codegen_get_child::<T>(kind, ty, idx) {
+                    errors.push(e);
+                }
+            }
+            (_, Err(e)) => errors.push(e),
+            (None, Ok(_)) => {}
+        }
+        if let Some(name) = format.fill_name() {
+            match T::validate_name(name) {
+                Some(idx) => if T::as_char(idx).is_none() {
+                    errors.push(Error::BadFill(idx));
+                },
+                None => errors.push(Error::BadName(name)),
+            }
+        }
+        validate_count::<T>(format.width, "width", &mut errors);
+        validate_count::<T>(format.precision, "precision", &mut errors);
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Format a value with a format string, substituting `placeholder` for any
+/// reference which cannot be formatted.
+///
+/// This never fails: bad names, indices, counts, and unsatisfied traits are
+/// each replaced by the placeholder, while the rest of the format string is
+/// formatted as usual. If the format string's syntax is invalid, it is
+/// returned unformatted.
+pub fn format_lossy<T: FormatArgs>(spec: &str, t: &T, placeholder: &str) -> String {
+    let options = PrepareOptions::new();
+    let mut target = LossyParse {
+        inner: DelayedParse::new(&options),
+        placeholder: placeholder,
+    };
+    match parse(spec, &mut target) {
+        Ok(inner) => PreparedFormat { inner: inner }.format(t),
+        Err(_) => spec.into(),
+    }
+}
+
+/// A piece of output delivered by `format_with_sink`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Chunk<'a> {
+    /// Literal text from the format string, with escapes such as `{{`
+    /// resolved.
+    Literal(&'a str),
+    /// The complete output of a single argument, including any padding.
+    Formatted(&'a str),
+}
+
+/// The origin of a range of output produced by `render_spans`.
+///
+/// Literal pieces and arguments are numbered separately, each from zero in
+/// the order they appear in the format string. `Literal(i)` is the text
+/// preceding `Argument(i)`, if any, and the final literal follows the last
+/// argument.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum PieceRef {
+    /// Literal text from the format string.
+    Literal(usize),
+    /// An argument, such as `{0}` or `{name:>8}`.
+    Argument(usize),
+}
+
+/// Format a value with a format string, passing each piece of the output to
+/// `sink` in order.
+///
+/// Literal text and formatted arguments are delivered separately, so that
+/// the sink may treat them differently, for example by escaping only the
+/// arguments. Empty literals are skipped.
+pub fn format_with_sink<'s, T, F>(spec: &'s str, t: &T, sink: F) -> Result<(), Error<'s>>
+    where T: FormatArgs, F: FnMut(Chunk)
+{
+    PreparedFormat::<T>::prepare(spec)?.write_chunks(t, sink).map_err(Error::from)
+}
+
+// `which` is `"width"` or `"precision"`, for reporting a bad named count.
+fn validate_count<'s, T: FormatArgs>(count: ast::Count<'s>, which: &'static str,
+                                     errors: &mut Vec<Error<'s>>) {
+    let (idx, error) = match count {
+        ast::Count::Param(idx) => {
+            if !T::validate_index(idx) {
+                errors.push(Error::BadIndex(idx));
+                return;
+            }
+            (idx, Error::BadCount(idx))
+        }
+        ast::Count::Named(name) => match T::validate_name(name) {
+            Some(idx) => (idx, Error::BadNamedCount { count: which, name: name }),
+            None => {
+                errors.push(Error::BadName(name));
+                return;
+            }
+        },
+        ast::Count::Is(_) | ast::Count::Implied => return,
+    };
+    if T::as_usize(idx).is_none() {
+        errors.push(error);
+    }
+}
+
+/// A buffer representing a parsed format string and arguments.
+#[derive(Clone)]
+pub struct FormatBuf<'s> {
+    inner: Parsed<'s, Value<'s>>,
+}
+
+impl<'s> FormatBuf<'s> {
+    /// Construct a new buffer from the given format string and arguments.
+    ///
+    /// This method should usually not be called directly. Instead use the
+    /// `rt_format_args!` macro.
+    ///
+    /// The format string may be a `str`, `String`, `Cow<str>`, or anything
+    /// else which implements `AsRef<str>`.
+    #[inline]
+    pub fn new<S>(spec: &'s S, params: &'s [Param<'s>]) -> Result<Self, Error<'s>>
+        where S: AsRef<str> + ?Sized
+    {
+        parse(spec.as_ref(), &mut ImmediateParse(params))
+            .map(|result| FormatBuf { inner: result })
+    }
+
+    /// Construct a new buffer from the given format string and a value whose
+    /// type need not be known statically.
+    #[inline]
+    pub fn new_dyn<S>(spec: &'s S, args: &'s DynFormatArgs) -> Result<Self, Error<'s>>
+        where S: AsRef<str> + ?Sized
+    {
+        parse(spec.as_ref(), &mut DynParse(args))
+            .map(|result| FormatBuf { inner: result })
+    }
+
+    /// Append a linefeed (`\n`) to the end of this buffer.
+    #[inline]
+    pub fn newln(&mut self) -> &mut Self {
+        self.inner.newln();
+        self
+    }
+
+    /// Set the separator placed between groups of digits by the `_` flag.
+    ///
+    /// The default separator is `,`. If `separator` is the current decimal
+    /// separator, the two are exchanged.
+    #[inline]
+    pub fn group_separator(&mut self, separator: char) -> &mut Self {
+        self.inner.options.set_group_separator(separator);
+        self
+    }
+
+    /// Set the separator between the integer and fractional parts of
+    /// floating-point numbers formatted with `Display`, `Debug`, `LowerExp`,
+    /// or `UpperExp`.
+    ///
+    /// The default separator is `.`. If `separator` is the current group
+    /// separator, the two are exchanged, so that `decimal_separator(',')`
+    /// alone selects `.` for grouping.
+    #[inline]
+    pub fn decimal_separator(&mut self, separator: char) -> &mut Self {
+        self.inner.options.set_decimal_separator(separator);
+        self
+    }
+
+    /// Enable or disable the escape sequences produced by `style(...)`.
+    ///
+    /// Styling is enabled by default. When disabled, styled arguments are
+    /// formatted as if no style had been requested.
+    #[inline]
+    pub fn styling(&mut self, enabled: bool) -> &mut Self {
+        self.inner.options.styling = enabled;
+        self
+    }
+
+    /// Enable or disable adding a `+` to `Display` output for the `+` flag.
+    ///
+    /// Primitive numbers honor `{:+}` themselves, but other types often
+    /// ignore it. When enabled, a `+` is prepended to the output of `{:+}`
+    /// if it begins with an ASCII digit. Output which begins otherwise, such
+    /// as `inf` or text, is left alone, and negative values must still
+    /// produce their own `-`. Disabled by default.
+    #[inline]
+    pub fn synthesize_sign(&mut self, enabled: bool) -> &mut Self {
+        self.inner.options.synthesize_sign = enabled;
+        self
+    }
+
+    /// Indent each level of pretty `Debug` output, as from `{:#?}`, with
+    /// `width` copies of `fill` rather than four spaces.
+    ///
+    /// The output is re-indented after formatting, so a `Debug`
+    /// implementation which itself writes lines beginning with spaces has
+    /// those spaces converted as well.
+    #[inline]
+    pub fn indent(&mut self, fill: char, width: usize) -> &mut Self {
+        self.inner.options.indent = Some((fill, width));
+        self
+    }
+
+    /// Measure widths in terminal columns, as given by the `unicode-width`
+    /// crate, rather than in `char`s.
+    ///
+    /// This is disabled by default.
+    #[cfg(feature = "unicode-width")]
+    #[inline]
+    pub fn display_width(&mut self, enabled: bool) -> &mut Self {
+        self.inner.options.display_width = enabled;
+        self
+    }
+
+    /// Call a function accepting `Arguments` with the contents of this buffer.
+    pub fn with<F: FnOnce(Arguments) -> R, R>(&self, f: F) -> R {
+        self.inner.with(&self.inner.args, f)
+    }
+
+    /// Format this buffer to a `String`.
+    #[inline]
+    pub fn format(&self) -> String {
+        match self.inner.literal() {
+            Some(literal) => String::from(literal),
+            None => {
+                let mut buf = String::with_capacity(self.inner.estimate_len());
+                self.write_fmt(&mut buf)
+                    .expect("a formatting trait implementation returned an error");
+                buf
+            }
+        }
+    }
+
+    /// Print this buffer to standard output.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn print(&self) {
+        self.with(_print)
+    }
+
+    /// Write this buffer to an `io::Write`.
+    #[cfg(feature = "std")]
+    #[deprecated(since = "0.2.0", note = "use `write_to`")]
+    #[inline]
+    pub fn write_io<W: io::Write + ?Sized>(&self, dest: &mut W) -> io::Result<()> {
+        self.write_to(dest)
+    }
+
+    /// Write this buffer to an `io::Write`, as `PreparedFormat::write_to`
+    /// does.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn write_to<W: io::Write + ?Sized>(&self, dest: &mut W) -> io::Result<()> {
+        write_through(dest, |shim| self.write_fmt(shim))
+    }
+
+    /// Write this buffer to a `fmt::Write`.
+    #[inline]
+    pub fn write_fmt<W: fmt::Write + ?Sized>(&self, dest: &mut W) -> fmt::Result {
+        self.with(|args| dest.write_fmt(args))
+    }
+}
+
+impl<'a> fmt::Display for FormatBuf<'a> {
+    #[inline]
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        self.with(|args| fmt.write_fmt(args))
+    }
+}
+
+impl<'a> fmt::Debug for FormatBuf<'a> {
+    #[inline]
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, fmt)
+    }
+}
+
+trait ParseTarget<'p> {
+    type Argument: Copy;
+    fn validate_name(&mut self, name: &str) -> Option<usize>;
+    fn validate_index(&mut self, index: usize) -> bool;
+    fn arg_count(&mut self) -> usize;
+    fn is_float(&mut self, idx: usize) -> bool;
+    fn is_number(&mut self, idx: usize) -> bool;
+    // Called with the standard trait requested, if any, and otherwise the
+    // name of an extension type in `spec`.
+    fn format<'s>(&mut self, kind: Option<FormatTraitKind>, spec: &'s str, idx: usize)
+        -> Result<Self::Argument, Error<'s>>;
+    // Called for a `join(...)` directive, which views the value as `Join`.
+    fn format_join<'s>(&mut self, idx: usize) -> Result<Self::Argument, Error<'s>>;
+    fn format_usize(&mut self, idx: usize) -> Option<Self::Argument>;
+    fn format_char(&mut self, idx: usize) -> Option<Self::Argument>;
+
+    // The trait used by references which name none.
+    fn default_trait(&mut self) -> FormatTraitKind {
+        FormatTraitKind::Display
+    }
+
+    // The error for a name which `validate_name` rejected.
+    fn unknown_name<'s>(&mut self, name: &'s str) -> Error<'s> {
+        Error::BadName(name)
+    }
+
+    // Called for a dotted name such as `user.name`. Returns the argument and
+    // whether the value it names is a float and whether it is a number. Any
+    // text the argument needs when formatted is pushed to `texts`.
+    fn format_path<'s>(&mut self, spec: &'s str, path: &'s str,
+                       texts: &mut Vec<Cow<'s, str>>)
+        -> Result<(Self::Argument, bool, bool), Error<'s>>
+    {
+        let _ = (spec, texts);
+        Err(Error::BadName(path))
+    }
+
+    // Called when an argument cannot be converted. Returns the text to emit
+    // in its place, or the error to fail with.
+    fn recover<'s>(&mut self, error: Error<'s>) -> Result<&str, Error<'s>> {
+        Err(error)
+    }
+}
+
+struct ImmediateParse<'p>(&'p [Param<'p>]);
+
+impl<'p> ParseTarget<'p> for ImmediateParse<'p> {
+    type Argument = Value<'p>;
+
+    fn validate_name(&mut self, name: &str) -> Option<usize> {
+        self.0.iter().position(|p| p.name.map_or(false, |n| n == name))
+    }
+
+    fn validate_index(&mut self, index: usize) -> bool {
+        index < self.0.len()
+    }
+
+    fn arg_count(&mut self) -> usize {
+        self.0.len()
+    }
+
+    fn is_float(&mut self, idx: usize) -> bool {
+        self.0[idx].value.is_float()
+    }
+
+    fn is_number(&mut self, idx: usize) -> bool {
+        self.0[idx].value.as_f64().is_some()
+    }
+
+    fn format<'s>(&mut self, _: Option<FormatTraitKind>, spec: &'s str, idx: usize)
+        -> Result<Self::Argument, Error<'s>>
+    {
+        self.0[idx].value.by_name(spec, idx).map(Value::Normal)
+    }
+
+    fn format_join<'s>(&mut self, idx: usize) -> Result<Self::Argument, Error<'s>> {
+        joinable(self.0[idx].value.as_join(), idx)
+    }
+
+    fn format_usize(&mut self, idx: usize) -> Option<Self::Argument> {
+        self.0[idx].as_usize.as_ref().map(|n| Value::Normal(ArgumentV1::from_usize(n)))
+    }
+
+    fn format_char(&mut self, idx: usize) -> Option<Self::Argument> {
+        self.0[idx].as_char.as_ref().map(|c| {
+            Value::Normal(ArgumentV1::new(c, fmt::Display::fmt))
+        })
+    }
+
+    fn format_path<'s>(&mut self, spec: &'s str, path: &'s str, _: &mut Vec<Cow<'s, str>>)
+        -> Result<(Self::Argument, bool, bool), Error<'s>>
+    {
+        let (head, rest) = path.split_at(path.find('.').unwrap_or(path.len()));
+        let params = self.0;
+        let value = params.iter()
+            .find(|p| p.name == Some(head))
+            .and_then(|p| p.value.as_dyn_args());
+        match value {
+            Some(value) => resolve_path(value, &rest[1..], path, spec),
+            None => Err(Error::BadName(path)),
+        }
+    }
+}
+
+struct DynParse<'p>(&'p DynFormatArgs);
+
+impl<'p> ParseTarget<'p> for DynParse<'p> {
+    type Argument = Value<'p>;
+
+    fn validate_name(&mut self, name: &str) -> Option<usize> {
+        self.0.validate_name(name)
+    }
+
+    fn validate_index(&mut self, index: usize) -> bool {
+        self.0.validate_index(index)
+    }
+
+    fn arg_count(&mut self) -> usize {
+        self.0.arg_count()
+    }
+
+    fn is_float(&mut self, idx: usize) -> bool {
+        self.0.is_float(idx)
+    }
+
+    fn is_number(&mut self, idx: usize) -> bool {
+        self.0.as_f64(idx).is_some()
+    }
+
+    fn format<'s>(&mut self, _: Option<FormatTraitKind>, spec: &'s str, idx: usize)
+        -> Result<Self::Argument, Error<'s>>
+    {
+        self.0.format_child(spec, idx).map(Value::Normal)
+    }
+
+    fn format_join<'s>(&mut self, idx: usize) -> Result<Self::Argument, Error<'s>> {
+        joinable(self.0.joinable(idx), idx)
+    }
+
+    fn unknown_name<'s>(&mut self, name: &'s str) -> Error<'s> {
+        if self.0.is_missing(name) {
+            Error::MissingValue(name)
+        } else {
+            Error::BadName(name)
+        }
+    }
+
+    fn format_usize(&mut self, idx: usize) -> Option<Self::Argument> {
+        self.0.as_usize(idx).map(|n| Value::Normal(ArgumentV1::from_usize(n)))
+    }
+
+    fn format_char(&mut self, idx: usize) -> Option<Self::Argument> {
+        self.0.as_char(idx).map(|c| Value::Normal(ArgumentV1::new(c, fmt::Display::fmt)))
+    }
+
+    fn format_path<'s>(&mut self, spec: &'s str, path: &'s str, _: &mut Vec<Cow<'s, str>>)
+        -> Result<(Self::Argument, bool, bool), Error<'s>>
+    {
+        resolve_path(self.0, path, path, spec)
+    }
+}
+
+// Resolve the `\"` and `\\` escapes in a quoted name.
+fn unquote(name: &str) -> Cow<str> {
+    if !name.contains('\\') {
+        return Cow::Borrowed(name);
+    }
+    let mut result = String::with_capacity(name.len());
+    let mut chars = name.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => result.extend(chars.next()),
+            c => result.push(c),
+        }
+    }
+    Cow::Owned(result)
+}
+
+// Resolve the `.`-separated segments of `rest`, the tail of `path`, against
+// `value`, and format the final child, which is also reported as a float or
+// a number as for `ParseTarget::format_path`.
+fn resolve_path<'a, 's>(mut value: &'a DynFormatArgs, rest: &str, path: &'s str, spec: &'s str)
+    -> Result<(Value<'a>, bool, bool), Error<'s>>
+{
+    // Numeric segments are indices and all others are names.
+    fn find(value: &DynFormatArgs, segment: &str) -> Option<usize> {
+        match segment.parse() {
+            Ok(idx) => if value.validate_index(idx) { Some(idx) } else { None },
+            Err(_) => value.validate_name(segment),
+        }
+    }
+
+    // A path which ends at a missing value is reported as such.
+    let unknown = |value: &DynFormatArgs, segment| if value.is_missing(segment) {
+        Error::MissingValue(path)
+    } else {
+        Error::BadName(path)
+    };
+
+    let mut segments = rest.split('.');
+    let last = segments.next_back().unwrap_or("");
+    for segment in segments {
+        let nested = match find(value, segment) {
+            Some(idx) => value.nested(idx),
+            None => return Err(unknown(value, segment)),
+        };
+        value = match nested {
+            Some(nested) => nested,
+            None => return Err(Error::BadName(path)),
+        };
+    }
+    match find(value, last) {
+        Some(idx) => {
+            let arg = if spec == transform::JOIN {
+                joinable(value.joinable(idx), idx)?
+            } else {
+                Value::Normal(value.format_child(spec, idx)?)
+            };
+            Ok((arg, value.is_float(idx), value.as_f64(idx).is_some()))
+        }
+        None => Err(unknown(value, last)),
+    }
+}
+
+// The argument for `join(...)` given the value at `idx` viewed as `Join`, or
+// the error for a value which cannot be.
+fn joinable<'a, 's>(value: Option<&'a Join>, idx: usize) -> Result<Value<'a>, Error<'s>> {
+    value.map(Value::Join).ok_or(Error::UnsatisfiedFormat {
+        idx: idx,
+        must_implement: transform::JOIN_TRAIT,
+    })
+}
+
+struct DelayedParse<'o, T: FormatArgs + 'o> {
+    options: &'o PrepareOptions<T>,
+    // Holds the most recent text produced by `options.missing`.
+    replacement: String,
+}
+
+impl<'o, T: FormatArgs> DelayedParse<'o, T> {
+    fn new(options: &'o PrepareOptions<T>) -> Self {
+        DelayedParse {
+            options: options,
+            replacement: String::new(),
+        }
+    }
+}
+
+impl<'o, 'p, T: FormatArgs> ParseTarget<'p> for DelayedParse<'o, T> {
+    type Argument = PreparedArgument<T>;
+
+    fn validate_name(&mut self, name: &str) -> Option<usize> {
+        T::validate_name(name)
+    }
+
+    fn validate_index(&mut self, index: usize) -> bool {
+        T::validate_index(index)
+    }
+
+    fn arg_count(&mut self) -> usize {
+        T::arg_count()
+    }
+
+    fn is_float(&mut self, idx: usize) -> bool {
+        T::is_float(idx)
+    }
+
+    fn is_number(&mut self, idx: usize) -> bool {
+        T::as_f64(idx).is_some()
+    }
+
+    fn format<'s>(&mut self, kind: Option<FormatTraitKind>, spec: &'s str, idx: usize)
+        -> Result<Self::Argument, Error<'s>>
+    {
+        if let Some(&(name, get_child)) = self.options.custom.iter().find(|c| c.0 == spec) {
+            return match get_child(idx) {
+                Some(f) => Ok(PreparedArgument::Normal(f, idx, name)),
+                None => Err(Error::UnsatisfiedFormat {
+                    idx: idx,
+                    must_implement: name,
+                }),
+            };
+        }
+        let (func, name) = erase::codegen_get_child::<T>(kind, spec, idx)?;
+        Ok(PreparedArgument::Normal(func, idx, name))
+    }
+
+    fn format_join<'s>(&mut self, idx: usize) -> Result<Self::Argument, Error<'s>> {
+        get_join::<T>(idx).map(|func| PreparedArgument::Join(func, idx))
+    }
+
+    fn format_usize(&mut self, idx: usize) -> Option<Self::Argument> {
+        T::as_usize(idx).map(PreparedArgument::Usize)
+    }
+
+    fn format_char(&mut self, idx: usize) -> Option<Self::Argument> {
+        T::as_char(idx).map(PreparedArgument::Char)
+    }
+
+    fn format_path<'s>(&mut self, spec: &'s str, path: &'s str,
+                       texts: &mut Vec<Cow<'s, str>>)
+        -> Result<(Self::Argument, bool, bool), Error<'s>>
+    {
+        match nested_path::<T>(path) {
+            Some((nested, rest)) => {
+                texts.push(Cow::Borrowed(rest));
+                texts.push(Cow::Borrowed(spec));
+                Ok((PreparedArgument::Path(nested, texts.len() - 2), false, false))
+            }
+            None => Err(Error::BadName(path)),
+        }
+    }
+
+    fn default_trait(&mut self) -> FormatTraitKind {
+        match self.options.default_trait {
+            DefaultTrait::Display => FormatTraitKind::Display,
+            DefaultTrait::Debug => FormatTraitKind::Debug,
+        }
+    }
+
+    fn recover<'s>(&mut self, error: Error<'s>) -> Result<&str, Error<'s>> {
+        let options = self.options;
+        match (error, options.missing.as_ref()) {
+            (Error::BadName(name), Some(missing)) => {
+                self.replacement = missing(name);
+                Ok(&self.replacement)
+            }
+            (error, _) => Err(error),
+        }
+    }
+}
+
+// The function viewing the value named by the first segment of the dotted
+// `path` as `DynFormatArgs`, and the rest of the path. Only the first segment
+// can be checked against `T`, since the rest descend into values which are
+// not yet known.
+fn nested_path<T: FormatArgs>(path: &str) -> Option<(fn(&T) -> Option<&DynFormatArgs>, &str)> {
+    let (head, rest) = path.split_at(path.find('.').unwrap_or(path.len()));
+    let idx = match head.parse() {
+        Ok(idx) => if T::validate_index(idx) { Some(idx) } else { None },
+        Err(_) => T::validate_name(head),
+    };
+    idx.and_then(T::get_nested).map(|nested| (nested, &rest[1..]))
+}
+
+// The function viewing argument `idx` of `T` as `Join`, for `join(...)`.
+fn get_join<'s, T: FormatArgs>(idx: usize) -> Result<fn(&T) -> Option<&Join>, Error<'s>> {
+    T::get_join(idx).ok_or(Error::UnsatisfiedFormat {
+        idx: idx,
+        must_implement: transform::JOIN_TRAIT,
+    })
+}
+
+struct LossyParse<'o, T: FormatArgs + 'o> {
+    inner: DelayedParse<'o, T>,
+    placeholder: &'o str,
+}
+
+impl<'o, 'p, T: FormatArgs> ParseTarget<'p> for LossyParse<'o, T> {
+    type Argument = PreparedArgument<T>;
+
+    fn validate_name(&mut self, name: &str) -> Option<usize> {
+        self.inner.validate_name(name)
+    }
+
+    fn validate_index(&mut self, index: usize) -> bool {
+        self.inner.validate_index(index)
+    }
+
+    fn arg_count(&mut self) -> usize {
+        self.inner.arg_count()
+    }
+
+    fn is_float(&mut self, idx: usize) -> bool {
+        self.inner.is_float(idx)
+    }
+
+    fn is_number(&mut self, idx: usize) -> bool {
+        self.inner.is_number(idx)
+    }
+
+    fn format<'s>(&mut self, kind: Option<FormatTraitKind>, spec: &'s str, idx: usize)
+        -> Result<Self::Argument, Error<'s>>
+    {
+        self.inner.format(kind, spec, idx)
+    }
+
+    fn format_join<'s>(&mut self, idx: usize) -> Result<Self::Argument, Error<'s>> {
+        self.inner.format_join(idx)
+    }
+
+    fn format_usize(&mut self, idx: usize) -> Option<Self::Argument> {
+        self.inner.format_usize(idx)
+    }
+
+    fn format_char(&mut self, idx: usize) -> Option<Self::Argument> {
+        self.inner.format_char(idx)
+    }
+
+    fn format_path<'s>(&mut self, spec: &'s str, path: &'s str,
+                       texts: &mut Vec<Cow<'s, str>>)
+        -> Result<(Self::Argument, bool, bool), Error<'s>>
+    {
+        self.inner.format_path(spec, path, texts)
+    }
+
+    fn default_trait(&mut self) -> FormatTraitKind {
+        self.inner.default_trait()
+    }
+
+    fn recover<'s>(&mut self, _: Error<'s>) -> Result<&str, Error<'s>> {
+        Ok(self.placeholder)
+    }
+}
+
+struct Parsed<'s, A> {
+    pieces: Pieces<'s>,
+    args: InlineVec<A>,
+    fmt: Option<InlineVec<v1::Argument>>,
+    // Arguments, by index into `args`, which require post-processing.
+    transforms: Vec<(usize, Transform)>,
+    // The index of every value referred to, in order and with repeats.
+    indices: InlineVec<usize>,
+    // Each formatted value, by index into `args`, and how it was referred to.
+    positions: InlineVec<(usize, Referent<'s>)>,
+    // The separators of `join(...)` and defaults of `or(...)` directives, by
+    // `Transform::join` and `Transform::or`, and the texts of prepared paths.
+    texts: Vec<Cow<'s, str>>,
+    // Specs, by index into `fmt`, whose fill is the `char` formatted by an
+    // argument, by index into `args`.
+    fills: Vec<(usize, usize)>,
+    options: Options,
+}
+
+// How a value was referred to. Once a format owns its text, names are kept in
+// its `texts` rather than borrowed from the format string.
+#[derive(Copy, Clone)]
+enum Referent<'s> {
+    Position(ast::Position<'s>),
+    Named(usize),
+    Quoted(usize),
+}
+
+impl<'s> Referent<'s> {
+    fn into_owned(self, texts: &mut Vec<Cow<'static, str>>) -> Referent<'static> {
+        let (name, quoted) = match self {
+            Referent::Position(ast::Position::Next(idx)) => {
+                return Referent::Position(ast::Position::Next(idx))
+            }
+            Referent::Position(ast::Position::Index(idx)) => {
+                return Referent::Position(ast::Position::Index(idx))
+            }
+            Referent::Position(ast::Position::FromEnd(back)) => {
+                return Referent::Position(ast::Position::FromEnd(back))
+            }
+            Referent::Position(ast::Position::Named(name)) => (name, false),
+            Referent::Position(ast::Position::Quoted(name)) => (name, true),
+            Referent::Named(text) => return Referent::Named(text),
+            Referent::Quoted(text) => return Referent::Quoted(text),
+        };
+        texts.push(Cow::Owned(name.into()));
+        if quoted {
+            Referent::Quoted(texts.len() - 1)
+        } else {
+            Referent::Named(texts.len() - 1)
+        }
+    }
+
+    fn position<'a>(&self, texts: &'a [Cow<'s, str>]) -> ast::Position<'a> where 's: 'a {
+        match *self {
+            Referent::Position(position) => position,
+            Referent::Named(text) => ast::Position::Named(&texts[text]),
+            Referent::Quoted(text) => ast::Position::Quoted(&texts[text]),
+        }
+    }
+}
+
+impl<'s, A: Copy> Clone for Parsed<'s, A> {
+    fn clone(&self) -> Self {
+        Parsed {
+            pieces: self.pieces.clone(),
+            args: self.args.clone(),
+            fmt: self.fmt.clone(),
+            transforms: self.transforms.clone(),
+            indices: self.indices.clone(),
+            positions: self.positions.clone(),
+            texts: self.texts.clone(),
+            fills: self.fills.clone(),
+            options: self.options,
+        }
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.pieces.clone_from(&source.pieces);
+        self.args.clone_from(&source.args);
+        self.fmt.clone_from(&source.fmt);
+        self.transforms.clone_from(&source.transforms);
+        self.indices.clone_from(&source.indices);
+        self.positions.clone_from(&source.positions);
+        self.texts.clone_from(&source.texts);
+        self.fills.clone_from(&source.fills);
+        self.options = source.options;
+    }
+}
+
+impl<'s, A> Parsed<'s, A> {
+    fn newln(&mut self) {
+        // If fmt is None, the number of implicit formatting specifiers
+        // is the same as the number of arguments.
+        let len = self.fmt.as_ref().map_or(self.args.len(), |fmt| fmt.len());
+        if self.pieces.len() > len {
+            // The final piece is after the final formatting specifier, so
+            // it's okay to just add to the end of it.
+            self.pieces.to_mut().last_mut().unwrap().push_str("\n")
+        } else {
+            // The final piece is before the final formatting specifier, so
+            // a new piece needs to be added at the end.
+            self.pieces.push(Literal::Borrowed("\n"))
+        }
+    }
+
+    fn map_literals<F: Fn(&str) -> String>(&mut self, f: F) {
+        for piece in self.pieces.to_mut() {
+            if !piece.is_empty() {
+                *piece = Literal::Owned(f(piece).into_boxed_str());
+            }
+        }
+    }
+
+    // The output, if it does not depend on any arguments.
+    #[inline]
+    fn literal(&self) -> Option<&str> {
+        match (self.args.is_empty(), self.pieces.len()) {
+            (true, 0) => Some(""),
+            (true, 1) => Some(&self.pieces[0]),
+            _ => None,
+        }
+    }
+
+    fn estimate_len(&self) -> usize {
+        const ARGUMENT_LEN: usize = 8;
+        let arguments = match self.fmt {
+            Some(ref fmt) => fmt.iter().fold(0usize, |sum, arg| sum.saturating_add(
+                match arg.format.width {
+                    v1::Count::Is(width) => width,
+                    _ => ARGUMENT_LEN,
+                }
+            )),
+            None => self.args.len().saturating_mul(ARGUMENT_LEN),
+        };
+        self.pieces.iter().fold(arguments, |sum, piece| sum.saturating_add(piece.len()))
+    }
+
+    #[inline]
+    fn pieces(&self) -> Gathered<&str> {
+        match self.pieces {
+            Pieces::Borrowed(ref pieces) => Gathered::Borrowed(pieces),
+            Pieces::Owned(_) => Gathered::new("", self.pieces.iter()),
+        }
+    }
+
+    // Call `f` with the given arguments, which correspond to `self.args`,
+    // after wrapping those which require post-processing.
+    fn with<'a, F, R>(&'a self, args: &[Value<'a>], f: F) -> R
+        where F: FnOnce(Arguments) -> R
+    {
+        let pieces = self.pieces();
+        let specs = self.specs(args);
+        self.wrap(args, |args| f(match specs {
+            Some(ref fmt) => Arguments::new_v1_formatted(&pieces, args, fmt),
+            None => Arguments::new_v1(&pieces, args),
+        }))
+    }
+
+    // The format specs, if explicit, with any fills taken from `args`.
+    fn specs(&self, args: &[Value]) -> Option<Gathered<v1::Argument>> {
+        let fmt = match self.fmt {
+            Some(ref fmt) => fmt,
+            None => return None,
+        };
+        if self.fills.is_empty() {
+            return Some(Gathered::Borrowed(&fmt[..]));
+        }
+        Some(Gathered::new(fmt[0], fmt.iter().enumerate().map(|(i, spec)| {
+            let mut spec = *spec;
+            if let Some(&(_, pos)) = self.fills.iter().find(|&&(j, _)| j == i) {
+                if let Value::Normal(arg) = args[pos] {
+                    let mut first = FirstChar(None);
+                    let arg = Arguments::new_v1(&[""], &[arg]);
+                    if let (Ok(()), Some(fill)) = (fmt::write(&mut first, arg), first.0) {
+                        spec.format.fill = fill;
+                    }
+                }
+            }
+            spec
+        })))
+    }
+
+    // Format each argument on its own, passing it and the literal pieces
+    // between arguments to `sink` in order.
+    fn chunks<'a, F>(&'a self, args: &[Value<'a>], mut sink: F) -> fmt::Result
+        where F: FnMut(Chunk, PieceRef)
+    {
+        let len = self.fmt.as_ref().map_or(self.args.len(), |fmt| fmt.len());
+        let specs = self.specs(args);
+        self.wrap(args, |args| {
+            let mut buf = String::new();
+            for i in 0..len {
+                match self.pieces.get(i) {
+                    Some(piece) if !piece.is_empty() => {
+                        sink(Chunk::Literal(piece), PieceRef::Literal(i))
+                    }
+                    _ => {}
+                }
+                buf.clear();
+                fmt::write(&mut buf, match specs {
+                    Some(ref fmt) => Arguments::new_v1_formatted(&[""], args, &fmt[i..i + 1]),
+                    None => Arguments::new_v1(&[""], &args[i..i + 1]),
+                })?;
+                sink(Chunk::Formatted(&buf), PieceRef::Argument(i));
+            }
+            for (i, piece) in self.pieces.iter().enumerate().skip(len) {
+                if !piece.is_empty() {
+                    sink(Chunk::Literal(piece), PieceRef::Literal(i));
+                }
+            }
+            Ok(())
+        })
+    }
+
+    // Call `f` with the given arguments after wrapping those which require
+    // post-processing, as every argument to `join(...)` does.
+    fn wrap<'a, F, R>(&'a self, args: &[Value<'a>], f: F) -> R
+        where F: for<'b> FnOnce(&'b [ArgumentV1<'b>]) -> R
+    {
+        let unresolved = ArgumentV1::new(&UNRESOLVED, fmt::Display::fmt);
+        let plain = Gathered::new(unresolved, args.iter().map(|value| match *value {
+            Value::Normal(arg) => arg,
+            Value::Join(_) => unresolved,
+        }));
+        if !self.transforms.iter().any(|&(_, transform)| transform.is_active(&self.options)) {
+            return f(&plain);
+        }
+        let active: Vec<(usize, Transform)> = self.transforms.iter()
+            .cloned()
+            .filter(|&(_, transform)| transform.is_active(&self.options))
+            .collect();
+        let wrapped: Vec<Transformed> = active.iter()
+            .map(|&(i, transform)| {
+                Transformed::new(args[i], transform, &self.options, &self.texts)
+            })
+            .collect();
+        let mut args: Vec<ArgumentV1> = plain.to_vec();
+        for (&(i, _), wrapper) in active.iter().zip(&wrapped) {
+            args[i] = ArgumentV1::new(wrapper, Transformed::fmt);
+        }
+        f(&args)
+    }
+}
+
+// Keeps the first `char` written to it, as the fill formatted by an argument.
+struct FirstChar(Option<char>);
+
+impl fmt::Write for FirstChar {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.0.is_none() {
+            self.0 = s.chars().next();
+        }
+        Ok(())
+    }
+}
+
+fn parse<'s, P: ParseTarget<'s>>(spec: &'s str, target: &mut P)
+    -> Result<Parsed<'s, P::Argument>, Error<'s>>
+{
+    let mut parser = fmt_macros::Parser::new(spec);
+    let result = inner_parse(&mut parser, target);
+    // Perform a separate check so that syntax errors take priority.
+    if parser.errors.is_empty() {
+        result
     } else {
         Err(Error::BadSyntax(parser.errors))
     }
 }
 
-fn inner_parse<'s, P>(parser: &mut fmt_macros::Parser<'s>, target: &mut P)
-    -> Result<Parsed<'s, P>, Error<'s>>
-    where P: ParseTarget<'s>
+fn inner_parse<'s, I, P>(parser: &mut I, target: &mut P)
+    -> Result<Parsed<'s, P::Argument>, Error<'s>>
+    where I: Iterator<Item = fmt_macros::Piece<'s>>, P: ParseTarget<'s>
 {
     use fmt_macros as p;
 
     const DEFAULT_FILL: char = ' ';
     const DEFAULT_KEY: p::FormatSpec = p::FormatSpec {
         fill: None,
+        fill_name: None,
         align: p::AlignUnknown,
         flags: 0,
         precision: p::CountImplied,
         width: p::CountImplied,
+        grouping: false,
+        directive: None,
         ty: "",
+        trait_: Some(FormatTraitKind::Display),
     };
     const DEFAULT_VALUE: v1::FormatSpec = v1::FormatSpec {
         fill: DEFAULT_FILL,
@@ -443,10 +2161,188 @@ fn inner_parse<'s, P>(parser: &mut fmt_macros::Parser<'s>, target: &mut P)
         width: v1::Count::Implied,
     };
 
-    let mut pieces = Vec::new();
-    let mut args = Vec::new();
+    // Convert a single argument, pushing its value and any counts or fill it
+    // uses to `args`, the indices they were taken from to `indices`, and the
+    // text of its directive or path, if any, to `texts`. Returns the value's position
+    // within `args`, its format spec, the post-processing it requests, and the
+    // position of its fill within `args`.
+    fn convert_argument<'s, P>(arg: &p::Argument<'s>, target: &mut P,
+                               args: &mut InlineVec<P::Argument>,
+                               indices: &mut InlineVec<usize>,
+                               texts: &mut Vec<Cow<'s, str>>)
+        -> Result<(usize, v1::FormatSpec, Transform, Option<usize>), Error<'s>>
+        where P: ParseTarget<'s>
+    {
+        let mut push_arg = |arg, idx: Option<usize>| {
+            // TODO: if this arg already appears in `args`, don't push
+            // it another time, reuse the previous index.
+            let len = args.len();
+            args.push(arg);
+            indices.extend(idx);
+            len
+        };
+
+        // convert the argument
+        let idx = match arg.position {
+            p::Position::ArgumentIs(idx) |
+            p::Position::ArgumentImplicitlyIs(idx) => {
+                if !target.validate_index(idx) {
+                    return Err(Error::BadIndex(idx))
+                }
+                Some(idx)
+            }
+            p::Position::ArgumentFromEnd(back) => {
+                let count = target.arg_count();
+                if back == 0 || back > count {
+                    return Err(Error::BadIndexFromEnd(back))
+                }
+                Some(count - back)
+            }
+            p::Position::ArgumentNamed(name) if name.contains('.') => None,
+            p::Position::ArgumentNamed(name) => {
+                match target.validate_name(name) {
+                    Some(idx) => Some(idx),
+                    None => return Err(target.unknown_name(name))
+                }
+            }
+            p::Position::ArgumentQuoted(name) => {
+                match target.validate_name(&unquote(name)) {
+                    Some(idx) => Some(idx),
+                    None => return Err(target.unknown_name(name))
+                }
+            }
+        };
+        let directive = transform::directive(arg.format.directive)?;
+        let requested =
+            transform::trait_spec(arg.format.trait_, arg.format.ty, directive.as_ref())?;
+        let (kind, ty) = match requested {
+            (_, "") if arg.format.ty.is_empty() => {
+                let kind = target.default_trait();
+                (Some(kind), erase::kind_spec(kind).0)
+            }
+            requested => requested,
+        };
+        let escape = transform::escape(arg.format.ty);
+        let (value, float, number) = match (idx, arg.position) {
+            (Some(idx), _) => {
+                let value = if ty == transform::JOIN {
+                    target.format_join(idx)?
+                } else {
+                    target.format(kind, ty, idx)?
+                };
+                (value, target.is_float(idx), target.is_number(idx))
+            }
+            (None, p::Position::ArgumentNamed(path)) => target.format_path(ty, path, texts)?,
+            (None, _) => unreachable!(),
+        };
+        let argument_pos = push_arg(value, idx);
+
+        // convert the fill, if it is named
+        let fill_pos = match arg.format.fill_name {
+            Some(name) => {
+                let idx = match target.validate_name(name) {
+                    Some(idx) => idx,
+                    None => return Err(target.unknown_name(name))
+                };
+                Some(push_arg(match target.format_char(idx) {
+                    Some(arg) => arg,
+                    None => return Err(Error::BadFill(idx))
+                }, Some(idx)))
+            }
+            None => None,
+        };
+
+        // convert the format spec
+        // `which` is `"width"` or `"precision"`, for reporting a bad name.
+        let mut convert_count = |c, which| -> Result<v1::Count, Error<'s>> {
+            Ok(match c {
+                p::CountIs(val) => v1::Count::Is(val),
+                p::CountIsName(name) => {
+                    let idx = match target.validate_name(name) {
+                        Some(idx) => idx,
+                        None => return Err(target.unknown_name(name))
+                    };
+                    v1::Count::Param(push_arg(match target.format_usize(idx) {
+                        Some(arg) => arg,
+                        None => return Err(Error::BadNamedCount { count: which, name: name })
+                    }, Some(idx)))
+                }
+                p::CountIsParam(idx) => {
+                    if !target.validate_index(idx) {
+                        return Err(Error::BadIndex(idx))
+                    }
+                    v1::Count::Param(push_arg(match target.format_usize(idx) {
+                        Some(arg) => arg,
+                        None => return Err(Error::BadCount(idx))
+                    }, Some(idx)))
+                },
+                p::CountImplied => v1::Count::Implied,
+            })
+        };
+
+        let spec = v1::FormatSpec {
+            fill: arg.format.fill.unwrap_or(DEFAULT_FILL),
+            flags: arg.format.flags,
+            align: match arg.format.align {
+                p::AlignLeft => v1::Alignment::Left,
+                p::AlignRight => v1::Alignment::Right,
+                p::AlignCenter => v1::Alignment::Center,
+                p::AlignUnknown => v1::Alignment::Unknown,
+            },
+            precision: convert_count(arg.format.precision, "precision")?,
+            width: convert_count(arg.format.width, "width")?,
+        };
+        let mut post = Transform {
+            align: spec.align,
+            grouping: arg.format.grouping,
+            style: match directive {
+                Some(transform::Directive::Style(style)) => Some(style),
+                _ => None,
+            },
+            width: match spec.width {
+                v1::Count::Implied => false,
+                _ => true,
+            },
+            pretty: ty == "?" &&
+                arg.format.flags & (1 << p::FlagAlternate as u32) != 0,
+            decimal: float && match ty {
+                "" | "?" | "e" | "E" | "%" | "permille" => true,
+                _ => false,
+            },
+            plus: ty == "" &&
+                arg.format.flags & (1 << p::FlagSignPlus as u32) != 0,
+            escape: escape,
+            maxlen: match directive {
+                Some(transform::Directive::MaxLen(len)) => Some(len),
+                _ => None,
+            },
+            numeric: number,
+            join: None,
+            or: None,
+        };
+        match directive {
+            Some(transform::Directive::Join(separator)) => {
+                post.join = Some(texts.len());
+                texts.push(separator);
+            }
+            Some(transform::Directive::Or(default)) => {
+                post.or = Some(texts.len());
+                texts.push(default);
+            }
+            _ => {}
+        }
+        Ok((argument_pos, spec, post, fill_pos))
+    }
+
+    let mut pieces = Pieces::new();
+    let mut args = InlineVec::new();
     let mut fmt = None;
     let mut fmt_len = 0;
+    let mut transforms = Vec::new();
+    let mut indices = InlineVec::new();
+    let mut positions = InlineVec::new();
+    let mut texts = Vec::new();
+    let mut fills = Vec::new();
 
     let mut str_accum: Cow<str> = "".into();
     while let Some(piece) = parser.next() {
@@ -460,60 +2356,32 @@ fn inner_parse<'s, P>(parser: &mut fmt_macros::Parser<'s>, target: &mut P)
                 }
             }
             p::Piece::NextArgument(arg) => {
-                let mut push_arg = |arg| {
-                    // TODO: if this arg already appears in `args`, don't push
-                    // it another time, reuse the previous index.
-                    let len = args.len();
-                    args.push(arg);
-                    len
+                let (args_len, indices_len, texts_len) = (args.len(), indices.len(), texts.len());
+                let converted = convert_argument(&arg, target, &mut args, &mut indices,
+                                                 &mut texts);
+                let (argument_pos, spec, post, fill_pos) = match converted {
+                    Ok(converted) => converted,
+                    Err(e) => {
+                        // Discard anything pushed for this argument, and
+                        // emit the target's replacement text if it has any.
+                        args.truncate(args_len);
+                        indices.truncate(indices_len);
+                        texts.truncate(texts_len);
+                        str_accum.to_mut().push_str(target.recover(e)?);
+                        continue;
+                    }
                 };
 
                 // flush accumulator always
-                pieces.push(std::mem::replace(&mut str_accum, "".into()));
-
-                // convert the argument
-                let idx = match arg.position {
-                    p::Position::ArgumentIs(idx) => {
-                        if !target.validate_index(idx) {
-                            return Err(Error::BadIndex(idx))
-                        }
-                        idx
-                    }
-                    p::Position::ArgumentNamed(name) => {
-                        match target.validate_name(name) {
-                            Some(idx) => idx,
-                            None => return Err(Error::BadName(name))
-                        }
-                    }
-                };
-                let argument_pos = push_arg(target.format(arg.format.ty, idx)?);
-
-                // convert the format spec
-                let mut convert_count = |c| -> Result<v1::Count, Error<'s>> {
-                    Ok(match c {
-                        p::CountIs(val) => v1::Count::Is(val),
-                        p::CountIsName(name) => {
-                            let idx = match target.validate_name(name) {
-                                Some(idx) => idx,
-                                None => return Err(Error::BadName(name))
-                            };
-                            v1::Count::Param(push_arg(match target.format_usize(idx) {
-                                Some(arg) => arg,
-                                None => return Err(Error::BadCount(idx))
-                            }))
-                        }
-                        p::CountIsParam(idx) => {
-                            if !target.validate_index(idx) {
-                                return Err(Error::BadIndex(idx))
-                            }
-                            v1::Count::Param(push_arg(match target.format_usize(idx) {
-                                Some(arg) => arg,
-                                None => return Err(Error::BadCount(idx))
-                            }))
-                        },
-                        p::CountImplied => v1::Count::Implied,
-                    })
-                };
+                pieces.push(std::mem::replace(&mut str_accum, "".into()).into());
+                positions.push((argument_pos, Referent::Position(arg.position.into())));
+
+                if post.grouping || post.style.is_some() || post.width || post.pretty ||
+                    post.decimal || post.plus || post.escape.is_some() ||
+                    post.maxlen.is_some() || post.join.is_some() || post.or.is_some()
+                {
+                    transforms.push((argument_pos, post));
+                }
 
                 // If specs were implicit but this is non-default, fill in the
                 // previously-implicit values.
@@ -521,24 +2389,14 @@ fn inner_parse<'s, P>(parser: &mut fmt_macros::Parser<'s>, target: &mut P)
                     fmt = Some((0..fmt_len).map(|i| v1::Argument {
                         position: v1::Position::At(i),
                         format: DEFAULT_VALUE,
-                    }).collect::<Vec<_>>());
+                    }).collect::<InlineVec<_>>());
                 }
 
                 // If specs are currently explicit, push this spec.
                 if let Some(fmt) = fmt.as_mut() {
-                    let spec = v1::FormatSpec {
-                        fill: arg.format.fill.unwrap_or(DEFAULT_FILL),
-                        flags: arg.format.flags,
-                        align: match arg.format.align {
-                            p::AlignLeft => v1::Alignment::Left,
-                            p::AlignRight => v1::Alignment::Right,
-                            p::AlignCenter => v1::Alignment::Center,
-                            p::AlignUnknown => v1::Alignment::Unknown,
-                        },
-                        precision: convert_count(arg.format.precision)?,
-                        width: convert_count(arg.format.width)?,
-                    };
-
+                    if let Some(fill_pos) = fill_pos {
+                        fills.push((fmt.len(), fill_pos));
+                    }
                     // push the format spec and argument value
                     fmt.push(v1::Argument {
                         position: v1::Position::At(argument_pos),
@@ -552,12 +2410,18 @@ fn inner_parse<'s, P>(parser: &mut fmt_macros::Parser<'s>, target: &mut P)
     }
     // flush accumulator if needed
     if !str_accum.is_empty() {
-        pieces.push(str_accum);
+        pieces.push(str_accum.into());
     }
 
     Ok(Parsed {
         pieces: pieces,
         args: args,
         fmt: fmt,
+        transforms: transforms,
+        indices: indices,
+        positions: positions,
+        texts: texts,
+        fills: fills,
+        options: Options::default(),
     })
 }
@@ -0,0 +1,173 @@
+//! Literal text, borrowed from a format string or owned apart from it.
+use std::borrow::Cow;
+use std::boxed::Box;
+use std::ops::{Deref, Index};
+use std::string::String;
+use std::vec::Vec;
+use inline::InlineVec;
+
+/// A piece of literal text. Owned text is kept as a `Box<str>`, which holds
+/// no spare capacity.
+#[derive(Clone)]
+pub enum Literal<'s> {
+    Borrowed(&'s str),
+    Owned(Box<str>),
+}
+
+impl<'s> Literal<'s> {
+    /// Take ownership of the text, so that it no longer borrows.
+    pub fn into_owned(self) -> Literal<'static> {
+        match self {
+            Literal::Borrowed(text) => Literal::Owned(text.into()),
+            Literal::Owned(text) => Literal::Owned(text),
+        }
+    }
+
+    /// Append `text` to the end of this text.
+    pub fn push_str(&mut self, text: &str) {
+        let mut owned = String::from(&**self);
+        owned.push_str(text);
+        *self = Literal::Owned(owned.into_boxed_str());
+    }
+}
+
+impl<'s> From<Cow<'s, str>> for Literal<'s> {
+    #[inline]
+    fn from(text: Cow<'s, str>) -> Self {
+        match text {
+            Cow::Borrowed(text) => Literal::Borrowed(text),
+            Cow::Owned(text) => Literal::Owned(text.into_boxed_str()),
+        }
+    }
+}
+
+impl<'s> From<&'s str> for Literal<'s> {
+    #[inline]
+    fn from(text: &'s str) -> Self {
+        Literal::Borrowed(text)
+    }
+}
+
+impl<'s> Deref for Literal<'s> {
+    type Target = str;
+
+    #[inline]
+    fn deref(&self) -> &str {
+        match *self {
+            Literal::Borrowed(text) => text,
+            Literal::Owned(ref text) => text,
+        }
+    }
+}
+
+/// The literal pieces of a format. While every piece borrows from the format
+/// string, they are kept in an `InlineVec`, and so few of them need no
+/// allocation.
+#[derive(Clone)]
+pub enum Pieces<'s> {
+    Borrowed(InlineVec<&'s str>),
+    Owned(Vec<Literal<'s>>),
+}
+
+impl<'s> Pieces<'s> {
+    #[inline]
+    pub fn new() -> Self {
+        Pieces::Borrowed(InlineVec::new())
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        match *self {
+            Pieces::Borrowed(ref pieces) => pieces.len(),
+            Pieces::Owned(ref pieces) => pieces.len(),
+        }
+    }
+
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<&str> {
+        if index < self.len() {
+            Some(&self[index])
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    pub fn iter<'a>(&'a self) -> Iter<'a, 's> {
+        Iter { pieces: self, index: 0 }
+    }
+
+    pub fn push(&mut self, piece: Literal<'s>) {
+        if let Literal::Borrowed(text) = piece {
+            if let Pieces::Borrowed(ref mut pieces) = *self {
+                pieces.push(text);
+                return;
+            }
+        }
+        self.to_mut().push(piece);
+    }
+
+    /// The pieces as `Literal`s, which may be changed.
+    pub fn to_mut(&mut self) -> &mut Vec<Literal<'s>> {
+        let owned = match *self {
+            Pieces::Borrowed(ref pieces) => {
+                Some(pieces.iter().map(|&text| Literal::Borrowed(text)).collect())
+            }
+            Pieces::Owned(_) => None,
+        };
+        if let Some(owned) = owned {
+            *self = Pieces::Owned(owned);
+        }
+        match *self {
+            Pieces::Owned(ref mut pieces) => pieces,
+            Pieces::Borrowed(_) => unreachable!(),
+        }
+    }
+
+    /// Take ownership of the pieces, so that they no longer borrow.
+    pub fn into_owned(self) -> Pieces<'static> {
+        Pieces::Owned(match self {
+            Pieces::Borrowed(pieces) => {
+                pieces.iter().map(|&text| Literal::Owned(text.into())).collect()
+            }
+            Pieces::Owned(pieces) => pieces.into_iter().map(Literal::into_owned).collect(),
+        })
+    }
+}
+
+impl<'s> Index<usize> for Pieces<'s> {
+    type Output = str;
+
+    #[inline]
+    fn index(&self, index: usize) -> &str {
+        match *self {
+            Pieces::Borrowed(ref pieces) => pieces[index],
+            Pieces::Owned(ref pieces) => &pieces[index],
+        }
+    }
+}
+
+/// An iterator over the text of each piece.
+pub struct Iter<'a, 's: 'a> {
+    pieces: &'a Pieces<'s>,
+    index: usize,
+}
+
+impl<'a, 's> Iterator for Iter<'a, 's> {
+    type Item = &'a str;
+
+    #[inline]
+    fn next(&mut self) -> Option<&'a str> {
+        let piece = self.pieces.get(self.index);
+        self.index += 1;
+        piece
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.pieces.len().saturating_sub(self.index);
+        (len, Some(len))
+    }
+}
+
+impl<'a, 's> ExactSizeIterator for Iter<'a, 's> {}
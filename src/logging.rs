@@ -0,0 +1,34 @@
+//! Support for the `rt_log!` family of macros.
+use std::fmt::Arguments;
+use log::{self, Level, Metadata, Record};
+use {Error, FormatBuf};
+
+/// Whether a record at `level` for `target` would be logged.
+#[inline]
+pub fn enabled(level: Level, target: &str) -> bool {
+    level <= log::STATIC_MAX_LEVEL && level <= log::max_level() &&
+        log::logger().enabled(&Metadata::builder().level(level).target(target).build())
+}
+
+/// Log a formatted message, or the error which prevented formatting it.
+pub fn log(level: Level,
+           target: &str,
+           &(module_path, file, line): &(&'static str, &'static str, u32),
+           buf: Result<FormatBuf, Error>) {
+    let emit = |level: Level, args: Arguments| {
+        log::logger().log(&Record::builder()
+            .args(args)
+            .level(level)
+            .target(target)
+            .module_path(Some(module_path))
+            .file(Some(file))
+            .line(Some(line))
+            .build())
+    };
+    match buf {
+        Ok(buf) => buf.with(|args| emit(level, args)),
+        Err(err) => if enabled(Level::Error, target) {
+            emit(Level::Error, format_args!("invalid format string: {}", err))
+        },
+    }
+}
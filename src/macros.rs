@@ -2,6 +2,12 @@
 //!
 //! The central macro is `rt_format_args!`, analogous to `format_args!`.
 //! The rest of the macros correspond to the other `std` formatting macros.
+//!
+//! Every argument must be passed explicitly, even when the format string is a
+//! literal. A name such as `{x}` is never captured from the surrounding
+//! scope: `macro_rules!` cannot look inside a string literal to discover the
+//! names it uses, and format strings are generally unknown until runtime.
+//! Named arguments may be written `x = x` to the same effect.
 
 /// The core macro for runtime formatting.
 ///
@@ -11,6 +17,13 @@
 ///
 /// The syntax accepted is the same as `format_args!`. See the module-level
 /// docs for more detail.
+///
+/// The `FormatBuf` also implements `Display` and `Debug`, so it may be
+/// formatted into several sinks in turn without being rebuilt. Like
+/// `Arguments`, it borrows the format string and arguments, and the
+/// parameter list it is built from is a temporary of the macro invocation;
+/// it therefore cannot outlive the statement containing it unless bound
+/// in a `match` or passed directly to a function.
 #[macro_export]
 macro_rules! rt_format_args {
     (@[$spec:expr] [$($args:tt)*] $name:tt = $e:expr, $($rest:tt)*) => {
@@ -38,15 +51,27 @@ macro_rules! rt_format_args {
 
 /// Format a value of type `String` with a runtime format string.
 ///
-/// The format string should be any type coercible to an `&str`, and will not
-/// be consumed.
+/// The format string may be any type implementing `AsRef<str>`, such as
+/// `&str`, `String`, or `Cow<str>`, and will not be consumed.
 ///
 /// Returns a `Result<String, Error>`. See the module-level docs for more
 /// information.
 #[macro_export]
 macro_rules! rt_format {
     ($($rest:tt)*) => {
-        rt_format_args!($($rest)*).map(|x| x.with(::std::fmt::format))
+        rt_format_args!($($rest)*).map(|x| x.format())
+    }
+}
+
+/// Panic with a message formatted with a runtime format string.
+///
+/// Accepts the same arguments as `rt_format!`. If the format string is
+/// invalid or does not match the arguments, panics with the text of the
+/// `Error` instead, so that the mistake is not hidden.
+#[macro_export]
+macro_rules! rt_panic {
+    ($($rest:tt)*) => {
+        $crate::_panic(rt_format_args!($($rest)*))
     }
 }
 
@@ -54,6 +79,7 @@ macro_rules! rt_format {
 ///
 /// Returns a `Result<(), Error>`. Panics if writing to stdout fails. See the
 /// module-level docs for more information.
+#[cfg(feature = "std")]
 #[macro_export]
 macro_rules! rt_print {
     ($($rest:tt)*) => {
@@ -65,6 +91,7 @@ macro_rules! rt_print {
 ///
 /// Returns a `Result<(), Error>`. Panics if writing to stdout fails. See the
 /// module-level docs for more information.
+#[cfg(feature = "std")]
 #[macro_export]
 macro_rules! rt_println {
     ($($rest:tt)*) => {
@@ -72,6 +99,30 @@ macro_rules! rt_println {
     }
 }
 
+/// Print to standard error with a runtime format string.
+///
+/// Returns a `Result<(), Error>`. Panics if writing to stderr fails. See the
+/// module-level docs for more information.
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! rt_eprint {
+    ($($rest:tt)*) => {
+        rt_format_args!($($rest)*).map(|x| x.with($crate::_eprint))
+    }
+}
+
+/// Print to standard error with a runtime format string and trailing newline.
+///
+/// Returns a `Result<(), Error>`. Panics if writing to stderr fails. See the
+/// module-level docs for more information.
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! rt_eprintln {
+    ($($rest:tt)*) => {
+        rt_format_args!($($rest)*).map(|mut x| x.newln().with($crate::_eprint))
+    }
+}
+
 /// Write runtime-formatted data into a buffer.
 ///
 /// Like `write!`, implementations of either `std::fmt::Write` or
@@ -84,7 +135,7 @@ macro_rules! rt_println {
 macro_rules! rt_write {
     ($dest:expr, $($rest:tt)*) => {
         rt_format_args!($($rest)*).and_then(|x|
-            x.with(|args| $dest.write_fmt(args)).map_err(::std::convert::From::from)
+            x.with(|args| $dest.write_fmt(args)).map_err($crate::Error::from)
         )
     }
 }
@@ -101,7 +152,114 @@ macro_rules! rt_write {
 macro_rules! rt_writeln {
     ($dest:expr, $($rest:tt)*) => {
         rt_format_args!($($rest)*).and_then(|mut x|
-            x.newln().with(|args| $dest.write_fmt(args)).map_err(::std::convert::From::from)
+            x.newln().with(|args| $dest.write_fmt(args)).map_err($crate::Error::from)
         )
     }
 }
+
+/// Write runtime-formatted data into a `std::fmt::Write`, returning the number
+/// of bytes written.
+///
+/// The count includes only what this call wrote, not anything already in the
+/// buffer, and is useful for building length-prefixed messages. Unlike
+/// `rt_write!`, implementations of `std::io::Write` are not accepted.
+///
+/// Returns a `Result<usize, Error>`. See the module-level docs for more
+/// information.
+#[macro_export]
+macro_rules! rt_write_counted {
+    ($dest:expr, $($rest:tt)*) => {
+        $crate::_write_counted(&mut $dest, rt_format_args!($($rest)*))
+    }
+}
+
+/// Log a message with a runtime format string through the `log` crate.
+///
+/// The first argument is a `log::Level`, optionally preceded by
+/// `target: "..."`; the rest are as for `rt_format_args!`. The message is
+/// formatted only if a record of that level would be logged. If the format
+/// string is invalid, the error is logged at `Error` level in its place.
+#[cfg(feature = "log")]
+#[macro_export]
+macro_rules! rt_log {
+    (target: $target:expr, $lvl:expr, $($rest:tt)*) => {{
+        let (target, lvl) = ($target, $lvl);
+        if $crate::_log_enabled(lvl, target) {
+            $crate::_log(lvl, target, &(module_path!(), file!(), line!()),
+                         rt_format_args!($($rest)*))
+        }
+    }};
+    ($lvl:expr, $($rest:tt)*) => {
+        rt_log!(target: module_path!(), $lvl, $($rest)*)
+    };
+}
+
+/// Log a message at `Error` level with a runtime format string.
+///
+/// See `rt_log!` for more information.
+#[cfg(feature = "log")]
+#[macro_export]
+macro_rules! rt_error {
+    (target: $target:expr, $($rest:tt)*) => {
+        rt_log!(target: $target, $crate::_log_level::Error, $($rest)*)
+    };
+    ($($rest:tt)*) => {
+        rt_log!($crate::_log_level::Error, $($rest)*)
+    };
+}
+
+/// Log a message at `Warn` level with a runtime format string.
+///
+/// See `rt_log!` for more information.
+#[cfg(feature = "log")]
+#[macro_export]
+macro_rules! rt_warn {
+    (target: $target:expr, $($rest:tt)*) => {
+        rt_log!(target: $target, $crate::_log_level::Warn, $($rest)*)
+    };
+    ($($rest:tt)*) => {
+        rt_log!($crate::_log_level::Warn, $($rest)*)
+    };
+}
+
+/// Log a message at `Info` level with a runtime format string.
+///
+/// See `rt_log!` for more information.
+#[cfg(feature = "log")]
+#[macro_export]
+macro_rules! rt_info {
+    (target: $target:expr, $($rest:tt)*) => {
+        rt_log!(target: $target, $crate::_log_level::Info, $($rest)*)
+    };
+    ($($rest:tt)*) => {
+        rt_log!($crate::_log_level::Info, $($rest)*)
+    };
+}
+
+/// Log a message at `Debug` level with a runtime format string.
+///
+/// See `rt_log!` for more information.
+#[cfg(feature = "log")]
+#[macro_export]
+macro_rules! rt_debug {
+    (target: $target:expr, $($rest:tt)*) => {
+        rt_log!(target: $target, $crate::_log_level::Debug, $($rest)*)
+    };
+    ($($rest:tt)*) => {
+        rt_log!($crate::_log_level::Debug, $($rest)*)
+    };
+}
+
+/// Log a message at `Trace` level with a runtime format string.
+///
+/// See `rt_log!` for more information.
+#[cfg(feature = "log")]
+#[macro_export]
+macro_rules! rt_trace {
+    (target: $target:expr, $($rest:tt)*) => {
+        rt_log!(target: $target, $crate::_log_level::Trace, $($rest)*)
+    };
+    ($($rest:tt)*) => {
+        rt_log!($crate::_log_level::Trace, $($rest)*)
+    };
+}
@@ -0,0 +1,101 @@
+//! `DynFormatArgs` implementations for maps with string keys.
+//!
+//! Their values may be referred to by key only, as with `{name}`. Since the
+//! order of a `HashMap` is unspecified, no value may be referred to by
+//! position, as with `{0}` or `{}`.
+use std::borrow::Borrow;
+use std::collections::BTreeMap;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+#[cfg(feature = "std")]
+use std::hash::{BuildHasher, Hash};
+use std::fmt::ArgumentV1;
+use codegen::{as_usize, as_char, as_str, as_f64, DynFormatArgs, Join};
+use erase::Format;
+use Error;
+
+#[inline]
+fn identity<V>(v: &V) -> &V {
+    v
+}
+
+macro_rules! maps {
+    ($($(#[$attr:meta])* impl<$($param:ident),*> for $map:ty where ($($bounds:tt)*);)*) => {
+        $(
+            $(#[$attr])*
+            impl<$($param),*> DynFormatArgs for $map where $($bounds)* {
+                fn validate_name(&self, name: &str) -> Option<usize> {
+                    self.keys().position(|k| k.borrow() == name)
+                }
+
+                #[inline]
+                fn validate_index(&self, _: usize) -> bool {
+                    false
+                }
+
+                #[inline]
+                fn arg_count(&self) -> usize {
+                    0
+                }
+
+                fn format_child<'a, 's>(&'a self, spec: &'s str, index: usize)
+                    -> Result<ArgumentV1<'a>, Error<'s>>
+                {
+                    self.child(index).by_name(spec, index)
+                }
+
+                fn as_usize(&self, index: usize) -> Option<&usize> {
+                    as_usize::<V, V>(identity).map(|f| f(self.child(index)))
+                }
+
+                fn as_char(&self, index: usize) -> Option<&char> {
+                    as_char::<V, V>(identity).map(|f| f(self.child(index)))
+                }
+
+                fn as_str(&self, index: usize) -> Option<&str> {
+                    as_str(self.child(index))
+                }
+
+                fn as_f64(&self, index: usize) -> Option<f64> {
+                    as_f64(self.child(index))
+                }
+
+                fn is_float(&self, index: usize) -> bool {
+                    self.child(index).is_float()
+                }
+
+                fn nested(&self, index: usize) -> Option<&DynFormatArgs> {
+                    self.child(index).as_dyn_args()
+                }
+
+                fn joinable(&self, index: usize) -> Option<&Join> {
+                    self.child(index).as_join()
+                }
+            }
+        )*
+    }
+}
+
+// The value at `index` in iteration order.
+trait Child<V> {
+    fn child(&self, index: usize) -> &V;
+}
+
+impl<K, V> Child<V> for BTreeMap<K, V> {
+    fn child(&self, index: usize) -> &V {
+        self.values().nth(index).expect("invalid index")
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K, V, S> Child<V> for HashMap<K, V, S> where K: Eq + Hash, S: BuildHasher {
+    fn child(&self, index: usize) -> &V {
+        self.values().nth(index).expect("invalid index")
+    }
+}
+
+maps! {
+    impl<K, V> for BTreeMap<K, V> where (K: Borrow<str> + Ord);
+    #[cfg(feature = "std")]
+    impl<K, V, S> for HashMap<K, V, S> where (K: Borrow<str> + Eq + Hash, S: BuildHasher);
+}
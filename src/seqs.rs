@@ -0,0 +1,81 @@
+//! `DynFormatArgs` implementations for slices and vectors.
+//!
+//! Their elements may be referred to by position only, as with `{0}`. Unlike
+//! arrays, their lengths are not known statically, so they cannot implement
+//! `FormatArgs`. An index is instead checked against the length of the value
+//! being formatted, and one past the end is reported as `BadIndex`.
+//!
+//! The implementation is for `&[T]` rather than `[T]`, since an unsized
+//! slice cannot be viewed as a `&DynFormatArgs`.
+use std::fmt::ArgumentV1;
+use std::vec::Vec;
+use codegen::{as_usize, as_char, as_str, as_f64, DynFormatArgs, Join};
+use erase::Format;
+use Error;
+
+#[inline]
+fn identity<T>(t: &T) -> &T {
+    t
+}
+
+macro_rules! seqs {
+    ($(impl<$($param:tt),*> for $seq:ty;)*) => {
+        $(
+            impl<$($param),*> DynFormatArgs for $seq {
+                #[inline]
+                fn validate_name(&self, _: &str) -> Option<usize> {
+                    None
+                }
+
+                #[inline]
+                fn validate_index(&self, index: usize) -> bool {
+                    index < self.len()
+                }
+
+                #[inline]
+                fn arg_count(&self) -> usize {
+                    self.len()
+                }
+
+                fn format_child<'a, 's>(&'a self, spec: &'s str, index: usize)
+                    -> Result<ArgumentV1<'a>, Error<'s>>
+                {
+                    self[index].by_name(spec, index)
+                }
+
+                fn as_usize(&self, index: usize) -> Option<&usize> {
+                    as_usize::<T, T>(identity).map(|f| f(&self[index]))
+                }
+
+                fn as_char(&self, index: usize) -> Option<&char> {
+                    as_char::<T, T>(identity).map(|f| f(&self[index]))
+                }
+
+                fn as_str(&self, index: usize) -> Option<&str> {
+                    as_str(&self[index])
+                }
+
+                fn as_f64(&self, index: usize) -> Option<f64> {
+                    as_f64(&self[index])
+                }
+
+                fn is_float(&self, index: usize) -> bool {
+                    self[index].is_float()
+                }
+
+                fn nested(&self, index: usize) -> Option<&DynFormatArgs> {
+                    self[index].as_dyn_args()
+                }
+
+                fn joinable(&self, index: usize) -> Option<&Join> {
+                    self[index].as_join()
+                }
+            }
+        )*
+    }
+}
+
+seqs! {
+    impl<'b, T> for &'b [T];
+    impl<T> for Vec<T>;
+}
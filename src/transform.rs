@@ -0,0 +1,535 @@
+//! Post-processing of formatted arguments.
+//!
+//! Some format specifiers request changes to an argument's output which the
+//! standard library cannot perform itself. Such arguments are formatted
+//! without padding into a buffer, transformed, and then padded by hand.
+//! Styling, if any, surrounds the padded result.
+use std::borrow::Cow;
+use std::fmt::{self, Arguments, ArgumentV1, Formatter, Write};
+use std::fmt::rt::v1;
+use std::string::String;
+use ast::FormatTraitKind;
+use codegen::Join;
+#[cfg(feature = "unicode-width")]
+use unicode_width::UnicodeWidthStr;
+
+/// Settings which apply to every argument of a format string.
+#[derive(Copy, Clone)]
+pub struct Options {
+    pub group_separator: char,
+    pub styling: bool,
+    pub display_width: bool,
+    // The fill and width of one level of indentation in `{:#?}` output, if
+    // not the standard library's four spaces.
+    pub indent: Option<(char, usize)>,
+    pub decimal_separator: char,
+    pub synthesize_sign: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            group_separator: ',',
+            styling: true,
+            display_width: false,
+            indent: None,
+            decimal_separator: '.',
+            synthesize_sign: false,
+        }
+    }
+}
+
+impl Options {
+    // The group and decimal separators are exchanged rather than allowed to
+    // become the same.
+    pub fn set_group_separator(&mut self, separator: char) {
+        if separator == self.decimal_separator {
+            self.decimal_separator = self.group_separator;
+        }
+        self.group_separator = separator;
+    }
+
+    pub fn set_decimal_separator(&mut self, separator: char) {
+        if separator == self.group_separator {
+            self.group_separator = self.decimal_separator;
+        }
+        self.decimal_separator = separator;
+    }
+}
+
+/// The transformations requested for a single argument.
+#[derive(Copy, Clone)]
+pub struct Transform {
+    pub align: v1::Alignment,
+    pub grouping: bool,
+    pub style: Option<Style>,
+    // Whether a width was requested, which is measured here rather than by
+    // the standard library when `Options::display_width` is set.
+    pub width: bool,
+    // Whether pretty `Debug` output, as from `{:#?}`, was requested.
+    pub pretty: bool,
+    // Whether the argument is a floating-point number, the decimal point of
+    // which is replaced by `Options::decimal_separator`.
+    pub decimal: bool,
+    // Whether the `+` flag was given to `Display`, which may not honor it.
+    pub plus: bool,
+    pub escape: Option<Escape>,
+    // The number of `char`s to which the output is truncated, if any.
+    pub maxlen: Option<usize>,
+    // Whether the argument is a number, which as in the standard library is
+    // right-aligned by default and zero-padded after its sign.
+    pub numeric: bool,
+    // The index of this argument's `join(...)` separator, if any, among
+    // the texts kept alongside the transforms.
+    pub join: Option<usize>,
+    // The index of this argument's `or(...)` default, if any, among the
+    // same texts.
+    pub or: Option<usize>,
+}
+
+impl Transform {
+    /// Whether this transform changes the output at all under `options`.
+    #[inline]
+    pub fn is_active(&self, options: &Options) -> bool {
+        self.grouping || (self.style.is_some() && options.styling) ||
+            (self.width && options.display_width) ||
+            (self.pretty && options.indent.is_some()) ||
+            (self.decimal && options.decimal_separator != '.') ||
+            (self.plus && options.synthesize_sign) ||
+            self.escape.is_some() || self.maxlen.is_some() || self.join.is_some() ||
+            self.or.is_some()
+    }
+}
+
+/// A set of SGR attributes, stored as a bitmask of their codes.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Style(u64);
+
+const COLORS: [&'static str; 8] = [
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+
+impl Style {
+    /// Parse a comma-separated list of attributes, such as `red,bold`.
+    /// Colors may be prefixed with `on_` to select the background.
+    ///
+    /// Returns the first unrecognized attribute on failure.
+    pub fn parse(list: &str) -> Result<Style, &str> {
+        let mut bits = 0u64;
+        for attr in list.split(',').map(str::trim) {
+            let code = match attr {
+                "bold" => 1,
+                "dim" => 2,
+                "italic" => 3,
+                "underline" => 4,
+                "blink" => 5,
+                "reverse" => 7,
+                "hidden" => 8,
+                "strikethrough" => 9,
+                _ => {
+                    let (base, color) = if attr.starts_with("on_") {
+                        (40, &attr[3..])
+                    } else {
+                        (30, attr)
+                    };
+                    match COLORS.iter().position(|&c| c == color) {
+                        Some(i) => base + i,
+                        None => return Err(attr),
+                    }
+                }
+            };
+            bits |= 1 << code;
+        }
+        Ok(Style(bits))
+    }
+
+    // Write the escape sequence which enables this style.
+    fn start(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str("\x1b[")?;
+        let mut first = true;
+        for code in (0..64).filter(|&code| self.0 & (1 << code) != 0) {
+            if !first {
+                f.write_char(';')?;
+            }
+            first = false;
+            write!(f, "{}", code)?;
+        }
+        f.write_char('m')
+    }
+}
+
+/// A kind of escaping applied to an argument's `Display` output.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Escape {
+    /// Replace `&`, `<`, `>`, `"`, and `'` with character references.
+    Html,
+    /// Quote as a single POSIX shell word.
+    Shell,
+    /// Quote as a JSON string.
+    Json,
+}
+
+/// Interpret a format type which requests escaping, such as `html`.
+pub fn escape(ty: &str) -> Option<Escape> {
+    match ty {
+        "html" => Some(Escape::Html),
+        "shell" => Some(Escape::Shell),
+        "json" => Some(Escape::Json),
+        _ => None,
+    }
+}
+
+impl Escape {
+    fn apply(&self, s: &str) -> String {
+        let mut result = String::with_capacity(s.len() + 2);
+        match *self {
+            Escape::Html => for c in s.chars() {
+                match c {
+                    '&' => result.push_str("&amp;"),
+                    '<' => result.push_str("&lt;"),
+                    '>' => result.push_str("&gt;"),
+                    '"' => result.push_str("&quot;"),
+                    '\'' => result.push_str("&#39;"),
+                    c => result.push(c),
+                }
+            },
+            Escape::Shell => {
+                // A single-quoted word may contain anything but `'`, which
+                // is written by closing the quotes, escaping it, and
+                // reopening them.
+                result.push('\'');
+                for c in s.chars() {
+                    match c {
+                        '\'' => result.push_str("'\\''"),
+                        c => result.push(c),
+                    }
+                }
+                result.push('\'');
+            }
+            Escape::Json => {
+                result.push('"');
+                for c in s.chars() {
+                    match c {
+                        '"' => result.push_str("\\\""),
+                        '\\' => result.push_str("\\\\"),
+                        '\n' => result.push_str("\\n"),
+                        '\r' => result.push_str("\\r"),
+                        '\t' => result.push_str("\\t"),
+                        c if c < ' ' => {
+                            let _ = write!(result, "\\u{:04x}", c as u32);
+                        }
+                        c => result.push(c),
+                    }
+                }
+                result.push('"');
+            }
+        }
+        result
+    }
+}
+
+/// An extension directive, such as `style(red)`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Directive<'s> {
+    /// `style(...)`: surround the output with escape sequences.
+    Style(Style),
+    /// `maxlen(n)`: truncate the output to `n` characters and an ellipsis.
+    MaxLen(usize),
+    /// `join(sep)`: format each element of a sequence, separated by `sep`.
+    Join(Cow<'s, str>),
+    /// `or(text)`: substitute `text` for empty output.
+    Or(Cow<'s, str>),
+}
+
+/// The format type standing in for a `join(...)` directive, whose arguments
+/// are viewed as `Join` rather than formatted through a trait.
+pub const JOIN: &'static str = "join()";
+
+/// The trait required by a `join(...)` directive.
+pub const JOIN_TRAIT: &'static str = "Join";
+
+/// The standard trait, if any, and the name of the type with which to format
+/// an argument of type `ty` requesting `kind`, given its directive.
+pub fn trait_spec<'s>(kind: Option<FormatTraitKind>, ty: &'s str,
+                      directive: Option<&Directive<'s>>)
+    -> Result<(Option<FormatTraitKind>, &'s str), ::Error<'s>>
+{
+    match directive {
+        Some(&Directive::Join(_)) if ty.is_empty() => Ok((None, JOIN)),
+        Some(&Directive::Join(_)) => Err(::Error::NoSuchFormat(ty)),
+        _ if escape(ty).is_some() => Ok((Some(FormatTraitKind::Display), "")),
+        _ => Ok((kind, ty)),
+    }
+}
+
+/// Interpret an extension directive, such as `style(red)`.
+pub fn directive<'s>(directive: Option<(&'s str, &'s str)>)
+    -> Result<Option<Directive<'s>>, ::Error<'s>>
+{
+    match directive {
+        None => Ok(None),
+        Some(("style", list)) => match Style::parse(list) {
+            Ok(style) => Ok(Some(Directive::Style(style))),
+            Err(attr) => Err(::Error::NoSuchFormat(attr)),
+        },
+        Some(("maxlen", len)) => match len.trim().parse() {
+            Ok(len) => Ok(Some(Directive::MaxLen(len))),
+            Err(_) => Err(::Error::NoSuchFormat(len)),
+        },
+        Some(("join", separator)) => Ok(Some(Directive::Join(unquote(separator)))),
+        Some(("or", default)) => Ok(Some(Directive::Or(unquote(default)))),
+        Some((name, _)) => Err(::Error::NoSuchFormat(name)),
+    }
+}
+
+// Strip the quotes from a directive's text, which may be quoted so that it
+// can begin or end with spaces or contain `)` or `}`, and resolve the `\"`
+// and `\\` escapes within them.
+fn unquote(text: &str) -> Cow<str> {
+    if text.len() >= 2 && text.starts_with('"') && text.ends_with('"') {
+        ::unquote(&text[1..text.len() - 1])
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+/// An argument ready to be formatted.
+#[derive(Copy, Clone)]
+pub enum Value<'a> {
+    /// A value together with the function formatting it.
+    Normal(ArgumentV1<'a>),
+    /// A sequence, the elements of which `join(...)` formats in turn.
+    Join(&'a Join),
+}
+
+// A sequence together with its separator, formatting as the joined elements.
+struct Joined<'a> {
+    value: &'a Join,
+    separator: &'a str,
+}
+
+impl<'a> fmt::Display for Joined<'a> {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Join::fmt(self.value, self.separator, f)
+    }
+}
+
+/// An argument together with the transformations to apply to it.
+pub struct Transformed<'a> {
+    value: Value<'a>,
+    transform: Transform,
+    options: &'a Options,
+    separator: &'a str,
+    default: Option<&'a str>,
+}
+
+impl<'a> Transformed<'a> {
+    /// `texts` holds the texts referred to by `Transform::join` and
+    /// `Transform::or`.
+    #[inline]
+    pub fn new<S: AsRef<str>>(value: Value<'a>,
+                              transform: Transform,
+                              options: &'a Options,
+                              texts: &'a [S]) -> Self {
+        Transformed {
+            value: value,
+            transform: transform,
+            options: options,
+            separator: transform.join.map_or("", |i| texts[i].as_ref()),
+            default: transform.or.map(|i| texts[i].as_ref()),
+        }
+    }
+
+    pub fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        // Format the argument with everything but its width, which is
+        // applied once the output has been transformed.
+        let spec = v1::Argument {
+            position: v1::Position::At(0),
+            format: v1::FormatSpec {
+                fill: ' ',
+                align: v1::Alignment::Unknown,
+                flags: f.flags(),
+                precision: match f.precision() {
+                    Some(p) => v1::Count::Is(p),
+                    None => v1::Count::Implied,
+                },
+                width: v1::Count::Implied,
+            },
+        };
+        let mut buf = String::new();
+        let joined;
+        let arg = match self.value {
+            Value::Normal(arg) => arg,
+            Value::Join(value) => {
+                joined = Joined { value: value, separator: self.separator };
+                ArgumentV1::new(&joined, fmt::Display::fmt)
+            }
+        };
+        let (arg, spec) = ([arg], [spec]);
+        fmt::write(&mut buf, Arguments::new_v1_formatted(&[""], &arg, &spec))?;
+        if let (true, Some(default)) = (buf.is_empty(), self.default) {
+            buf.push_str(default);
+        }
+
+        // The decimal point is replaced first so that it cannot be confused
+        // with a group separator.
+        if self.transform.decimal && self.options.decimal_separator != '.' {
+            buf = replace_point(&buf, self.options.decimal_separator);
+        }
+        if self.transform.plus && self.options.synthesize_sign && digits_len(&buf, 0) > 0 {
+            buf.insert(0, '+');
+        }
+        if self.transform.grouping {
+            buf = group(&buf, self.options.group_separator);
+        }
+        if let (true, Some((fill, width))) = (self.transform.pretty, self.options.indent) {
+            buf = reindent(&buf, fill, width);
+        }
+        if let Some(len) = self.transform.maxlen {
+            truncate(&mut buf, len);
+        }
+        // Escaping precedes padding, so that widths count escaped characters.
+        if let Some(escape) = self.transform.escape {
+            buf = escape.apply(&buf);
+        }
+
+        match self.transform.style {
+            Some(style) if self.options.styling => {
+                style.start(f)?;
+                pad(f, &buf, &self.transform, self.options)?;
+                f.write_str("\x1b[0m")
+            }
+            _ => pad(f, &buf, &self.transform, self.options),
+        }
+    }
+}
+
+// Length of a leading `+` or `-`.
+fn sign_len(s: &str) -> usize {
+    if s.starts_with('+') || s.starts_with('-') { 1 } else { 0 }
+}
+
+// Length of the run of ASCII digits starting at `start`.
+fn digits_len(s: &str, start: usize) -> usize {
+    s[start..].bytes().take_while(|&b| b >= b'0' && b <= b'9').count()
+}
+
+// Insert `separator` between every three digits of the leading integer.
+fn group(s: &str, separator: char) -> String {
+    let start = sign_len(s);
+    let digits = digits_len(s, start);
+    let end = start + digits;
+
+    let mut result = String::with_capacity(s.len() + digits / 3 * separator.len_utf8());
+    result.push_str(&s[..start]);
+    for (i, c) in s[start..end].char_indices() {
+        if i > 0 && (digits - i) % 3 == 0 {
+            result.push(separator);
+        }
+        result.push(c);
+    }
+    result.push_str(&s[end..]);
+    result
+}
+
+// The number of columns `s` occupies.
+#[cfg(feature = "unicode-width")]
+fn columns(s: &str, options: &Options) -> usize {
+    if options.display_width {
+        s.width()
+    } else {
+        s.chars().count()
+    }
+}
+
+#[cfg(not(feature = "unicode-width"))]
+fn columns(s: &str, _: &Options) -> usize {
+    s.chars().count()
+}
+
+// Replace the first `.` in `s`, which for a floating-point number is its
+// decimal point. Exponents and special values such as `NaN` have none.
+fn replace_point(s: &str, separator: char) -> String {
+    match s.find('.') {
+        Some(i) => {
+            let mut result = String::with_capacity(s.len() + separator.len_utf8());
+            result.push_str(&s[..i]);
+            result.push(separator);
+            result.push_str(&s[i + 1..]);
+            result
+        }
+        None => String::from(s),
+    }
+}
+
+// Truncate `s` to its first `len` chars followed by an ellipsis, if it is
+// longer than that.
+fn truncate(s: &mut String, len: usize) {
+    if let Some((i, _)) = s.char_indices().nth(len) {
+        s.truncate(i);
+        s.push('\u{2026}');
+    }
+}
+
+// The indentation unit of pretty `Debug` output.
+const STD_INDENT: usize = 4;
+
+// Replace each `STD_INDENT` spaces of leading indentation with `width`
+// copies of `fill`. Any leftover spaces are kept as they are.
+fn reindent(s: &str, fill: char, width: usize) -> String {
+    let mut result = String::with_capacity(s.len());
+    for (i, line) in s.split('\n').enumerate() {
+        if i > 0 {
+            result.push('\n');
+        }
+        let spaces = line.bytes().take_while(|&b| b == b' ').count();
+        for _ in 0..spaces / STD_INDENT * width {
+            result.push(fill);
+        }
+        result.push_str(&line[spaces / STD_INDENT * STD_INDENT..]);
+    }
+    result
+}
+
+// Write `s` padded according to the formatter's width and fill, and the
+// alignment and numeric-ness recorded in `transform`.
+fn pad(f: &mut Formatter, s: &str, transform: &Transform, options: &Options) -> fmt::Result {
+    let len = columns(s, options);
+    let padding = match f.width() {
+        Some(width) if width > len => width - len,
+        _ => return f.write_str(s),
+    };
+    let numeric = transform.numeric;
+
+    if numeric && f.sign_aware_zero_pad() {
+        let sign = sign_len(s);
+        // zeroes follow the sign and any radix prefix, as with `{:#06x}`
+        let prefix = sign + match s[sign..].get(..2) {
+            Some("0x") | Some("0o") | Some("0b") if f.alternate() => 2,
+            _ => 0,
+        };
+        f.write_str(&s[..prefix])?;
+        for _ in 0..padding {
+            f.write_char('0')?;
+        }
+        return f.write_str(&s[prefix..]);
+    }
+
+    let (pre, post) = match transform.align {
+        v1::Alignment::Left => (0, padding),
+        v1::Alignment::Right => (padding, 0),
+        v1::Alignment::Center => (padding / 2, (padding + 1) / 2),
+        v1::Alignment::Unknown if numeric => (padding, 0),
+        v1::Alignment::Unknown => (0, padding),
+    };
+    let fill = f.fill();
+    for _ in 0..pre {
+        f.write_char(fill)?;
+    }
+    f.write_str(s)?;
+    for _ in 0..post {
+        f.write_char(fill)?;
+    }
+    Ok(())
+}
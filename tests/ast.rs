@@ -0,0 +1,168 @@
+extern crate runtime_fmt;
+
+use runtime_fmt::Error;
+use runtime_fmt::ast::{parse, pieces, referenced_names, required_traits, Piece, Position,
+                       Alignment, Count, FormatTraitKind};
+
+#[test]
+fn literal_only() {
+    assert_eq!(parse("Hello, world!").unwrap(), vec![Piece::Literal("Hello, world!")]);
+    assert_eq!(parse("").unwrap(), vec![]);
+}
+
+#[test]
+fn positions() {
+    let positions: Vec<_> = parse("{} {2} {name} {}").unwrap().into_iter()
+        .filter_map(|piece| match piece {
+            Piece::Argument { position, .. } => Some(position),
+            Piece::Literal(_) => None,
+        })
+        .collect();
+    assert_eq!(positions, vec![
+        Position::Next(0),
+        Position::Index(2),
+        Position::Named("name"),
+        Position::Next(1),
+    ]);
+}
+
+#[test]
+fn format_spec() {
+    let pieces = parse("x = {x:*^+#10.prec$?}").unwrap();
+    assert_eq!(pieces.len(), 2);
+    assert_eq!(pieces[0], Piece::Literal("x = "));
+    match pieces[1] {
+        Piece::Argument { position, format } => {
+            assert_eq!(position, Position::Named("x"));
+            assert_eq!(format.fill, Some('*'));
+            assert_eq!(format.align, Alignment::Center);
+            assert_eq!(format.width, Count::Is(10));
+            assert_eq!(format.precision, Count::Named("prec"));
+            assert_eq!(format.ty, "?");
+            assert_eq!(format.format_trait(), Some("Debug"));
+            assert!(format.sign_plus());
+            assert!(format.alternate());
+            assert!(!format.sign_minus());
+            assert!(!format.sign_aware_zero_pad());
+        }
+        _ => panic!("expected an argument"),
+    }
+}
+
+#[test]
+fn unknown_trait() {
+    match parse("{:q}").unwrap()[0] {
+        Piece::Argument { format, .. } => {
+            assert_eq!(format.ty, "q");
+            assert_eq!(format.format_trait(), None);
+        }
+        _ => panic!("expected an argument"),
+    }
+}
+
+#[test]
+fn bad_syntax() {
+    assert!(parse("{").is_err());
+    assert!(parse("}").is_err());
+}
+
+#[test]
+fn grouping() {
+    match parse("{:>12_}").unwrap()[0] {
+        Piece::Argument { format, .. } => {
+            assert_eq!(format.width, Count::Is(12));
+            assert!(format.grouping());
+        }
+        _ => panic!("expected an argument"),
+    }
+}
+
+#[test]
+fn traits() {
+    assert_eq!(required_traits("{} {x:?} {0:#x} {:w$e}").unwrap(), vec![
+        (Position::Next(0), "Display"),
+        (Position::Named("x"), "Debug"),
+        (Position::Index(0), "LowerHex"),
+        (Position::Next(1), "LowerExp"),
+    ]);
+    assert_eq!(required_traits("{items:join(\", \")}").unwrap(), vec![
+        (Position::Named("items"), "Join"),
+    ]);
+    assert_eq!(required_traits("no arguments").unwrap(), vec![]);
+    match required_traits("{} {:q}") {
+        Err(Error::NoSuchFormat("q")) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn names() {
+    assert_eq!(referenced_names("{b} {a:w$} {0} {} {b:.a$} {user.name}").unwrap(),
+        vec!["b", "a", "w", "user.name"]);
+    assert_eq!(referenced_names("{val:fill$^w$.p$} {\"a:b\"}").unwrap(),
+        vec!["val", "fill", "w", "p", "a:b"]);
+    assert_eq!(referenced_names("{0} {:1$}").unwrap(), Vec::<&str>::new());
+    assert!(referenced_names("{unclosed").is_err());
+}
+
+#[test]
+fn debug_hex() {
+    match parse("{:X?}").unwrap()[0] {
+        Piece::Argument { format, .. } => {
+            assert_eq!(format.ty, "?");
+            assert_eq!(format.format_trait(), Some("Debug"));
+            assert!(format.debug_upper_hex());
+            assert!(!format.debug_lower_hex());
+        }
+        _ => panic!("expected an argument"),
+    }
+}
+
+#[test]
+fn trait_kinds() {
+    let spec = "{} {:?} {:x?} {:e} {:E} {:o} {:p} {:b} {:x} {:X} {:%} {:html} {:join(\", \")}";
+    let kinds: Vec<_> = parse(spec)
+        .unwrap()
+        .into_iter()
+        .filter_map(|piece| match piece {
+            Piece::Argument { format, .. } => Some(format.trait_),
+            Piece::Literal(_) => None,
+        })
+        .collect();
+    assert_eq!(kinds, vec![
+        Some(FormatTraitKind::Display),
+        Some(FormatTraitKind::Debug),
+        Some(FormatTraitKind::Debug),
+        Some(FormatTraitKind::LowerExp),
+        Some(FormatTraitKind::UpperExp),
+        Some(FormatTraitKind::Octal),
+        Some(FormatTraitKind::Pointer),
+        Some(FormatTraitKind::Binary),
+        Some(FormatTraitKind::LowerHex),
+        Some(FormatTraitKind::UpperHex),
+        None,
+        None,
+        None,
+    ]);
+}
+
+#[test]
+fn lazy_pieces() {
+    let spec = "a{}b{x}c";
+    let lazy: Vec<_> = pieces(spec).map(Result::unwrap).collect();
+    assert_eq!(lazy, parse(spec).unwrap());
+
+    // only as much as is consumed is parsed
+    let mut iter = pieces("ok {} then } and {");
+    assert_eq!(iter.next().unwrap().unwrap(), Piece::Literal("ok "));
+    match iter.next().unwrap().unwrap() {
+        Piece::Argument { position, .. } => assert_eq!(position, Position::Next(0)),
+        _ => panic!("expected an argument"),
+    }
+    assert_eq!(iter.next().unwrap().unwrap(), Piece::Literal(" then "));
+    match iter.next() {
+        Some(Err(Error::BadSyntax(ref errors))) => assert_eq!(errors[0].span, 11..12),
+        other => panic!("expected a syntax error, got {:?}", other),
+    }
+    assert!(iter.next().is_none());
+}
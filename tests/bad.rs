@@ -27,6 +27,33 @@ fn bad_index() {
 #[test]
 fn bad_usize() {
     err_with!(BadCount(0); "{:.*}", "Not A Usize", "aaaa");
+    err_with!(BadNamedCount { count: "width", name: "w" }; "{x:w$}", x=3, w="ten");
+    err_with!(BadNamedCount { count: "width", name: "w" }; "{w:w$}", w="ten");
+    err_with!(BadNamedCount { count: "precision", name: "w" }; "{w:.w$}", w=2.5);
+}
+
+#[test]
+fn bad_star_precision() {
+    // `.*` takes two implicit arguments: the precision, then the value
+    err_with!(BadIndex(1); "{:.*}", 3);
+    err_with!(BadCount(0); "{:.*}", 0.5, 3.14159);
+    err_with!(BadIndex(2); "{:.*} {}", 1, 2.5);
+    err_with!(BadIndex(3); "{3:.*}", 1, 2.5);
+}
+
+#[test]
+fn bad_named_count() {
+    err_with!(BadName("w"); "{x:w$}", x=3);
+    err_with!(BadName("w"); "{:w$}", 3);
+    err_with!(BadName("p"); "{v:.p$}", v=3.14159);
+    err_with!(BadNamedCount { count: "precision", name: "p" }; "{v:.p$}", v=3.14159, p=2.5);
+    err_with!(BadIndex(5); "{v:.5$}", v=3.14159, p=2);
+    err_with!(BadNamedCount { count: "width", name: "pad" };
+        "{v:pad$.p$}", v=1.5, pad="wide", p=2);
+    err_with!(BadNamedCount { count: "precision", name: "v" };
+        "{v:pad$.v$}", v=1.5, pad=8);
+    assert_eq!(rt_format!("{:pad$}", 1, pad="wide").unwrap_err().to_string(),
+        "width argument 'pad' is not a usize");
 }
 
 #[test]
@@ -42,3 +69,109 @@ fn bad_format() {
     err_with!(UnsatisfiedFormat { idx: 0, must_implement: "Debug" };
         "{:?}", Foo);
 }
+
+#[test]
+fn display() {
+    struct Foo;
+
+    assert_eq!(rt_format!("{foo}").unwrap_err().to_string(), "no argument named 'foo'");
+    assert_eq!(rt_format!("{3}", 0).unwrap_err().to_string(), "index 3 out of range");
+    assert_eq!(rt_format!("{:?}", Foo).unwrap_err().to_string(),
+        "argument 0 does not implement Debug");
+    assert_eq!(rt_format!("}").unwrap_err().to_string(),
+        "unmatched `}` found at offset 0 \
+        (if you intended to print `}`, you can escape it using `}}`)");
+}
+
+#[test]
+fn display_with_source() {
+    let spec = "first line\nvalue: {x:>4} }";
+    let err = rt_format!(spec, x = 1).unwrap_err();
+    assert_eq!(err.with_source(spec).to_string(),
+        "unmatched `}` found at offset 25 \
+        (if you intended to print `}`, you can escape it using `}}`)\n\
+        value: {x:>4} }\n              ^");
+
+    let err = rt_format!("{foo}").unwrap_err();
+    assert_eq!(err.with_source("{foo}").to_string(), "no argument named 'foo'");
+}
+
+#[test]
+fn into_boxed_error() {
+    fn run() -> Result<String, Box<std::error::Error>> {
+        Ok(rt_format!("{} {}", 1)?)
+    }
+
+    assert_eq!(run().unwrap_err().to_string(), "index 1 out of range");
+}
+
+#[test]
+fn syntax_spans() {
+    fn span(spec: &str) -> std::ops::Range<usize> {
+        match rt_format!(spec) {
+            Err(BadSyntax(errors)) => errors[0].span.clone(),
+            other => panic!("expected a syntax error, got {:?}", other),
+        }
+    }
+
+    assert_eq!(span("{"), 1..1);
+    assert_eq!(span("}"), 0..1);
+    assert_eq!(span("ab}cd"), 2..3);
+    assert_eq!(span("{0 x}"), 3..4);
+    assert_eq!(span("{:>>>}"), 4..5);
+    assert_eq!(span("{0é}"), 2..4);
+    // integers which overflow a `usize` are syntax errors
+    assert_eq!(span("{:999999999999999999999999}"), 2..26);
+    assert_eq!(span("ab{:.100000000000000000000000000000}"), 5..35);
+    assert_eq!(span("{99999999999999999999999999999999999}"), 1..36);
+    assert_eq!(span("{:99999999999999999999999$}"), 2..25);
+    // escapes are consumed in pairs before an unmatched brace is found
+    assert_eq!(span("}}}"), 2..3);
+    assert_eq!(span("{{}"), 2..3);
+    assert_eq!(span("a}}b}"), 4..5);
+    // not a syntax error, but an unknown trait
+    err_with!(NoSuchFormat("zzz"); "{:zzz}", 1);
+}
+
+#[test]
+fn bad_named_fill() {
+    err_with!(BadName("f"); "{x:f$>5}", x=3);
+    err_with!(BadFill(1); "{x:f$>5}", x=3, f="*");
+    err_with!(BadFill(0); "{f:f$>5}", f=3);
+}
+
+#[test]
+fn bad_indexed_counts() {
+    err_with!(BadIndex(3); "{:1$.3$}", 3.14159, 10, 2);
+    err_with!(BadIndex(2); "{:2$.1$}", 3.14159, 2);
+    err_with!(BadCount(1); "{:1$.2$}", 3.14159, "ten", 2);
+    err_with!(BadCount(2); "{:1$.2$}", 3.14159, 10, 2.5);
+}
+
+#[test]
+fn bad_index_tuple_and_array() {
+    use runtime_fmt::{FormatArgs, PreparedFormat};
+
+    fn bad_index<T: FormatArgs>(spec: &str) -> usize {
+        match PreparedFormat::<T>::prepare(spec) {
+            Err(BadIndex(idx)) => idx,
+            Err(e) => panic!("expected BadIndex for {:?}, got {:?}", spec, e),
+            Ok(_) => panic!("expected BadIndex for {:?}", spec),
+        }
+    }
+
+    assert_eq!(bad_index::<(i32,)>("{0} {1}"), 1);
+    assert_eq!(bad_index::<(i32, &str)>("{} {} {}"), 2);
+    assert_eq!(bad_index::<(i32,)>("{0:1$}"), 1);
+    assert_eq!(bad_index::<(i32,)>("{:.*}"), 1);
+    assert_eq!(bad_index::<[u8; 1]>("{0} {1}"), 1);
+    assert_eq!(bad_index::<[u8; 3]>("{0:.5$}"), 5);
+
+    assert!(runtime_fmt::validate_all::<(i32,)>("{1} {2:3$}").unwrap_err().len() == 3);
+    assert!(<(i32,)>::try_get_child::<std::fmt::Display>(1).is_err());
+    err_with!(BadIndex(1); "{0} {1}", 5);
+    match PreparedFormat::<(i32,)>::prepare("{-2}") {
+        Err(BadIndexFromEnd(2)) => {}
+        other => panic!("expected BadIndexFromEnd, got {:?}", other.err()),
+    }
+}
@@ -1,106 +1,224 @@
+//! Tests of the `codegen` module, and of the `FormatArgs` and `DynFormatArgs`
+//! implementations the crate provides.
 #[macro_use] extern crate runtime_fmt_derive;
-extern crate runtime_fmt;
+#[macro_use] extern crate runtime_fmt;
+
+mod common;
 
 use runtime_fmt::{FormatArgs, PreparedFormat};
+use common::{row, user, Row, User};
 
 #[test]
-#[allow(dead_code)]
-fn test_struct() {
-    #[derive(FormatArgs)]
-    struct Struct {
-        #[format_args(aliases = "alias1,alias2")]
-        field1: &'static str,
-        field2: &'static str,
-        field3: usize,
-        #[format_args(rename = "renamed")]
-        field4: &'static str,
-        #[format_args(ignore)]
-        ignored: &'static str
-    }
+fn test_tuples_and_arrays() {
+    type Triple = (i32, &'static str, f64);
+    assert_eq!(Triple::validate_index(2), true);
+    assert_eq!(Triple::validate_index(3), false);
+    assert_eq!(Triple::validate_name("0"), None);
+
+    let prepared = PreparedFormat::<Triple>::prepare("{1}: {0:+} ({2:.1})").unwrap();
+    assert_eq!(prepared.format(&(5, "score", 0.25)), "score: +5 (0.2)");
+
+    // the `usize` element of a tuple may be used as a count
+    let prepared = PreparedFormat::<(&str, usize)>::prepare("[{0:>1$}]").unwrap();
+    assert_eq!(prepared.format(&("ab", 4)), "[  ab]");
+    assert!(PreparedFormat::<(&str, u8)>::prepare("[{0:>1$}]").is_err());
+    assert!(PreparedFormat::<(&str, u8)>::prepare("{0:x}").is_err());
+
+    let prepared = PreparedFormat::<[u8; 3]>::prepare("{2}.{1}.{0}").unwrap();
+    assert_eq!(prepared.format(&[1, 2, 3]), "3.2.1");
+    assert!(PreparedFormat::<[u8; 3]>::prepare("{3}").is_err());
+    let prepared = PreparedFormat::<[usize; 2]>::prepare("{1:0$}|").unwrap();
+    assert_eq!(prepared.format(&[3, 7]), "  7|");
+}
 
-    assert_eq!(Struct::validate_name("field1").is_some(), true);
-    assert_eq!(Struct::validate_name("field1"), Struct::validate_name("alias1"));
-    assert_eq!(Struct::validate_name("field1"), Struct::validate_name("alias2"));
+#[test]
+fn test_option_result() {
+    let prepared = PreparedFormat::<Option<i32>>::prepare("[{0:>4}]").unwrap();
+    assert_eq!(prepared.format(&Some(7)), "[   7]");
+    assert_eq!(prepared.format(&None), "[]");
 
-    assert_eq!(Struct::validate_name("field2").is_some(), true);
-    assert_eq!(Struct::validate_name("field3").is_some(), true);
+    let prepared = PreparedFormat::<Result<i32, &str>>::prepare("{0}{1:?}").unwrap();
+    assert_eq!(prepared.format(&Ok(7)), "7");
+    assert_eq!(prepared.format(&Err("bad")), "\"bad\"");
 
-    assert_eq!(Struct::validate_name("field4").is_some(), false);
-    assert_eq!(Struct::validate_name("renamed").is_some(), true);
+    assert!(PreparedFormat::<Option<i32>>::prepare("{1}").is_err());
+    assert!(PreparedFormat::<Result<i32, ()>>::prepare("{1}").is_err());
+}
 
-    assert_eq!(Struct::validate_name("ignored").is_some(), false);
+#[test]
+fn test_or_with_option() {
+    let prepared = PreparedFormat::<Option<i32>>::prepare("[{0:>4or(-)}]").unwrap();
+    assert_eq!(prepared.format(&Some(7)), "[   7]");
+    assert_eq!(prepared.format(&None), "[   -]");
+}
 
-    assert_eq!(Struct::validate_index(0), true);
-    assert_eq!(Struct::validate_index(1), true);
-    assert_eq!(Struct::validate_index(2), true);
-    assert_eq!(Struct::validate_index(3), true);
-    assert_eq!(Struct::validate_index(4), false);
+#[test]
+fn test_try_get_child() {
+    use std::fmt::{Debug, LowerHex};
+    use runtime_fmt::IndexError;
+
+    type Pair = (u8, &'static str);
+    assert!(Pair::try_get_child::<Debug>(0).unwrap().is_some());
+    assert!(Pair::try_get_child::<LowerHex>(1).unwrap().is_none());
+    assert_eq!(Pair::try_get_child::<Debug>(2).err(), Some(IndexError(2)));
+    assert_eq!(IndexError(2).to_string(), "index 2 out of range");
+}
+
+#[test]
+fn test_combine_boxed() {
+    use std::fmt;
+    use runtime_fmt::codegen;
+
+    struct Show<'a, T: 'a>(&'a Fn(&T, &mut fmt::Formatter) -> fmt::Result, &'a T);
+    impl<'a, T> fmt::Display for Show<'a, T> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            (self.0)(self.1, f)
+        }
+    }
 
-    assert_eq!(Struct::as_usize(0).is_some(), false);
-    assert_eq!(Struct::as_usize(1).is_some(), false);
-    assert_eq!(Struct::as_usize(2).is_some(), true);
-    assert_eq!(Struct::as_usize(3).is_some(), false);
+    let values = vec![10u32, 20, 30];
+    let formatters: Vec<_> = (0..values.len()).map(|i| {
+        codegen::combine_boxed::<fmt::LowerHex, Vec<u32>, u32, _>(move |v| &v[i]).unwrap()
+    }).collect();
+    let output: Vec<_> = formatters.iter().map(|f| format!("{:>4}", Show(&**f, &values))).collect();
+    assert_eq!(output, ["   a", "  14", "  1e"]);
 
-    let value = Struct {
-        field1: "value1",
-        field2: "value2",
-        field3: 123456,
-        field4: "value4",
-        ignored: "ignored"
-    };
+    assert!(codegen::combine_boxed::<fmt::LowerHex, Vec<u32>, String, _>(|_| unreachable!()).is_none());
+}
 
-    let fmt = PreparedFormat::prepare("{field1} {alias1} {alias2} {field2} {field3} {renamed}").unwrap().format(&value);
-    assert_eq!(fmt, "value1 value1 value1 value2 123456 value4");
+#[test]
+fn test_combine_constructs_nothing() {
+    use std::fmt;
+    use std::mem;
+    use runtime_fmt::codegen::{self, Child};
+
+    // Only its destructor could tell whether one was made up by `combine`.
+    struct Witness(u32);
+    impl Drop for Witness {
+        fn drop(&mut self) {
+            panic!("a witness was dropped");
+        }
+    }
+    impl fmt::Display for Witness {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "witness {}", self.0)
+        }
+    }
 
-    let fmt = PreparedFormat::prepare("{0} {1} {2} {3}").unwrap().format(&value);
-    assert_eq!(fmt, "value1 value2 123456 value4");
+    struct First;
+    impl Child<(Witness,)> for First {
+        type Target = Witness;
+        fn get(this: &(Witness,)) -> Option<&Witness> {
+            Some(&this.0)
+        }
+    }
+
+    struct Formatted((Witness,), fn(&(Witness,), &mut fmt::Formatter) -> fmt::Result);
+    impl fmt::Display for Formatted {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            (self.1)(&self.0, f)
+        }
+    }
+
+    let func = codegen::combine::<fmt::Display, (Witness,), First>().unwrap();
+    assert!(codegen::combine::<fmt::LowerHex, (Witness,), First>().is_none());
+    let formatted = Formatted((Witness(7),), func);
+    assert_eq!(formatted.to_string(), "witness 7");
+    mem::forget(formatted);
 }
 
 #[test]
-fn test_tuple() {
-    #[derive(FormatArgs)]
-    struct Tuple(
-        #[format_args(aliases = "alias1,alias2")]
-        &'static str,
-        &'static str,
-        usize,
-        #[format_args(rename = "renamed")]
-        &'static str,
-        #[format_args(ignore)]
-        &'static str
-    );
-
-    assert_eq!(Tuple::validate_name("alias1").is_some(), true);
-    assert_eq!(Tuple::validate_name("alias1"), Tuple::validate_name("alias2"));
-    assert_eq!(Tuple::validate_name("renamed").is_some(), true);
-
-    assert_eq!(Tuple::validate_index(0), true);
-    assert_eq!(Tuple::validate_index(1), true);
-    assert_eq!(Tuple::validate_index(2), true);
-    assert_eq!(Tuple::validate_index(3), true);
-    assert_eq!(Tuple::validate_index(4), false);
-
-    assert_eq!(Tuple::as_usize(0).is_some(), false);
-    assert_eq!(Tuple::as_usize(1).is_some(), false);
-    assert_eq!(Tuple::as_usize(2).is_some(), true);
-    assert_eq!(Tuple::as_usize(3).is_some(), false);
-
-    let value = Tuple("value1", "value2", 123456, "value3", "ignored");
-
-    let fmt = PreparedFormat::prepare("{alias1} {alias2} {renamed}").unwrap().format(&value);
-    assert_eq!(fmt, "value1 value1 value3");
-
-    let fmt = PreparedFormat::prepare("{0} {1} {2} {3}").unwrap().format(&value);
-    assert_eq!(fmt, "value1 value2 123456 value3");
+fn test_through_references() {
+    let user = user();
+    let prepared = PreparedFormat::<&User>::prepare(
+        "{name:>5} {1} {scores:join(\"/\")} {address.city}").unwrap();
+    assert_eq!(prepared.format(&&user), "  ann 37 7/8/9 Oslo");
+    assert_eq!(<&User>::arg_count(), 4);
+    assert_eq!(<&User>::validate_name("address"), Some(2));
+    assert!(PreparedFormat::<&User>::prepare("{address}").is_err());
+
+    let row = row();
+    let prepared = PreparedFormat::<&Row>::prepare("[{name:>width$}] {ratio:.1}").unwrap();
+    assert_eq!(prepared.format(&&row), "[  widget] 0.2");
+    assert!(<&Row>::is_float(3));
+    let rows = vec![&row, &row];
+    let all: Vec<_> = rows.iter().map(|r| prepared.format(r)).collect();
+    assert_eq!(all, ["[  widget] 0.2", "[  widget] 0.2"]);
+
+    // A format prepared for `User` still accepts references to one.
+    let prepared = PreparedFormat::<User>::prepare("{name}: {age}").unwrap();
+    let by_ref = &user;
+    assert_eq!(prepared.format(&by_ref), "ann: 37");
 }
 
 #[test]
-fn test_unit() {
+fn test_dyn_format_args() {
+    use runtime_fmt::{DynFormatArgs, FormatBuf};
+
     #[derive(FormatArgs)]
-    struct Unit;
+    struct Host {
+        name: String,
+        #[format_args(rename = "age")]
+        uptime: usize,
+    }
+
+    let items: Vec<Box<DynFormatArgs>> = vec![
+        Box::new(user()),
+        Box::new(Host { name: "db1".to_owned(), uptime: 4 }),
+        Box::new(("tuple", 9)),
+    ];
+    let output: Vec<_> = items.iter()
+        .map(|item| FormatBuf::new_dyn("{0}/{1:>3}", &**item).unwrap().format())
+        .collect();
+    assert_eq!(output, ["ann/ 37", "db1/  4", "tuple/  9"]);
+
+    // index 1 is a `usize` only for `Host`
+    assert!(FormatBuf::new_dyn("{0:1$}", &*items[0]).is_err());
+    assert_eq!(FormatBuf::new_dyn("{0:1$}|", &*items[1]).unwrap().format(), "db1 |");
+    assert!(FormatBuf::new_dyn("{name}", &*items[2]).is_err());
+}
 
-    assert_eq!(Unit::validate_index(0), false);
+#[test]
+fn test_nested_paths() {
+    use runtime_fmt::{Error, FormatBuf, PreparedFormat};
+
+    let user = user();
+    assert_eq!(rt_format!("{u.name} lives in {u.address.city:>6} ({u.address.zip:04})",
+                          u = user).unwrap(),
+               "ann lives in   Oslo (0150)");
+    assert_eq!(rt_format!("{u.scores.2}/{u.scores.0:?}", u = user).unwrap(), "9/7");
+    assert_eq!(FormatBuf::new_dyn("{address.city}", &user).unwrap().format(), "Oslo");
+    assert_eq!(rt_format!("{p.1.0}", p = (1, (2, 3))).unwrap(), "2");
+
+    match rt_format!("{u.name.first}", u = user) {
+        Err(Error::BadName("u.name.first")) => {}
+        _ => panic!("descended into a non-nested field"),
+    }
+    match rt_format!("{u.address.country}", u = user) {
+        Err(Error::BadName("u.address.country")) => {}
+        _ => panic!("found a non-existent field"),
+    }
+    let prepared = PreparedFormat::<User>::prepare("{name}: {address.city:>6} {scores.1:02}")
+        .unwrap();
+    assert_eq!(prepared.format(&user), "ann:   Oslo 08");
+    assert_eq!(prepared.clone().into_owned().format(&user), "ann:   Oslo 08");
+    let prepared = PreparedFormat::<User>::prepare("[{address.country}|{name.first}]").unwrap();
+    assert_eq!(prepared.format(&user), "[|]");
+    match PreparedFormat::<User>::prepare("{nobody.city}") {
+        Err(Error::BadName("nobody.city")) => {}
+        _ => panic!("prepared a path from a non-existent field"),
+    }
+}
 
-    let fmt = PreparedFormat::prepare("").unwrap().format(&Unit);
-    assert_eq!(fmt, "");
-}
\ No newline at end of file
+#[test]
+fn test_disp_or_debug() {
+    #[derive(Debug)]
+    struct Point(i32, i32);
+    struct Opaque;
+
+    let prepared = PreparedFormat::<(&str, Point)>::prepare(
+        "{0:disp_or_debug} {1:disp_or_debug}").unwrap();
+    assert_eq!(prepared.format(&("at", Point(1, 2))), "at Point(1, 2)");
+    assert!(PreparedFormat::<(Point, ())>::prepare("{1:disp_or_debug}").is_ok());
+    assert!(PreparedFormat::<(Point, Opaque)>::prepare("{1:disp_or_debug}").is_err());
+}
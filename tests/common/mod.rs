@@ -0,0 +1,58 @@
+//! Derived fixtures shared between test files. Not every file uses each one.
+#![allow(dead_code)]
+
+use std::fmt;
+
+#[derive(FormatArgs)]
+pub struct Point {
+    pub x: i32,
+    pub y: i32,
+}
+
+#[derive(FormatArgs)]
+pub struct Row {
+    pub id: u32,
+    pub name: &'static str,
+    pub width: usize,
+    pub ratio: f64,
+    pub unused: u8,
+}
+
+pub fn row() -> Row {
+    Row { id: 7, name: "widget", width: 8, ratio: 0.25, unused: 0 }
+}
+
+#[derive(FormatArgs)]
+pub struct Address {
+    pub city: &'static str,
+    pub zip: u32,
+}
+
+#[derive(FormatArgs)]
+pub struct User {
+    pub name: &'static str,
+    pub age: u32,
+    pub address: Address,
+    pub scores: [u32; 3],
+}
+
+pub fn user() -> User {
+    User {
+        name: "ann",
+        age: 37,
+        address: Address { city: "Oslo", zip: 150 },
+        scores: [7, 8, 9],
+    }
+}
+
+/// A value which always fails to format.
+pub struct Failing;
+
+impl fmt::Display for Failing {
+    fn fmt(&self, _: &mut fmt::Formatter) -> fmt::Result {
+        Err(fmt::Error)
+    }
+}
+
+#[derive(FormatArgs)]
+pub struct Wrapper(pub Failing);
@@ -0,0 +1,78 @@
+#![feature(specialization)]
+#[macro_use] extern crate runtime_fmt_derive;
+extern crate runtime_fmt;
+
+use std::fmt::{self, Formatter};
+use runtime_fmt::{Error, FormatTrait, PrepareOptions};
+
+// A domain-specific formatting trait.
+trait Currency {
+    fn fmt_money(&self, f: &mut Formatter) -> fmt::Result;
+}
+
+struct Cents(u64);
+
+impl Currency for Cents {
+    fn fmt_money(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "${}.{:02}", self.0 / 100, self.0 % 100)
+    }
+}
+
+// The marker registered for `Currency`, dispatching by specialization.
+struct Money;
+
+trait MoneyImpl {
+    fn allowed() -> bool;
+    fn perform(&self, f: &mut Formatter) -> fmt::Result;
+}
+
+impl<T> MoneyImpl for T {
+    default fn allowed() -> bool { false }
+    default fn perform(&self, _: &mut Formatter) -> fmt::Result { panic!() }
+}
+
+impl<T: Currency> MoneyImpl for T {
+    fn allowed() -> bool { true }
+    fn perform(&self, f: &mut Formatter) -> fmt::Result { self.fmt_money(f) }
+}
+
+impl FormatTrait for Money {
+    fn allowed<T>() -> bool { <T as MoneyImpl>::allowed() }
+    fn perform<T>(t: &T, f: &mut Formatter) -> fmt::Result { MoneyImpl::perform(t, f) }
+}
+
+#[derive(FormatArgs)]
+struct Item {
+    name: &'static str,
+    price: Cents,
+}
+
+#[test]
+fn custom_trait() {
+    let prepared = PrepareOptions::<Item>::new()
+        .custom_trait::<Money>("money")
+        .prepare("{name}: {price:money}")
+        .unwrap();
+    let item = Item { name: "tea", price: Cents(250) };
+    assert_eq!(prepared.format(&item), "tea: $2.50");
+}
+
+#[test]
+fn custom_trait_errors() {
+    let mut options = PrepareOptions::<Item>::new();
+    options.custom_trait::<Money>("money");
+
+    match options.prepare("{name:money}") {
+        Err(Error::UnsatisfiedFormat { idx: 0, must_implement: "money" }) => {}
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("unexpectedly succeeded"),
+    }
+    // the standard traits remain available
+    assert!(options.prepare("{name:?}").is_ok());
+
+    match PrepareOptions::<Item>::new().prepare("{price:money}") {
+        Err(Error::NoSuchFormat("money")) => {}
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("unexpectedly succeeded"),
+    }
+}
@@ -0,0 +1,410 @@
+//! Tests of `#[derive(FormatArgs)]` and the accessors it generates.
+#[macro_use] extern crate runtime_fmt_derive;
+#[macro_use] extern crate runtime_fmt;
+
+use runtime_fmt::{FormatArgs, PreparedFormat};
+
+#[test]
+#[allow(dead_code)]
+fn test_struct() {
+    #[derive(FormatArgs)]
+    struct Struct {
+        #[format_args(aliases = "alias1,alias2")]
+        field1: &'static str,
+        field2: &'static str,
+        field3: usize,
+        #[format_args(rename = "renamed")]
+        field4: &'static str,
+        #[format_args(ignore)]
+        ignored: &'static str
+    }
+
+    assert_eq!(Struct::validate_name("field1").is_some(), true);
+    assert_eq!(Struct::validate_name("field1"), Struct::validate_name("alias1"));
+    assert_eq!(Struct::validate_name("field1"), Struct::validate_name("alias2"));
+
+    assert_eq!(Struct::validate_name("field2").is_some(), true);
+    assert_eq!(Struct::validate_name("field3").is_some(), true);
+
+    assert_eq!(Struct::validate_name("field4").is_some(), false);
+    assert_eq!(Struct::validate_name("renamed").is_some(), true);
+
+    assert_eq!(Struct::validate_name("ignored").is_some(), false);
+
+    assert_eq!(Struct::validate_index(0), true);
+    assert_eq!(Struct::validate_index(1), true);
+    assert_eq!(Struct::validate_index(2), true);
+    assert_eq!(Struct::validate_index(3), true);
+    assert_eq!(Struct::validate_index(4), false);
+
+    assert_eq!(Struct::as_usize(0).is_some(), false);
+    assert_eq!(Struct::as_usize(1).is_some(), false);
+    assert_eq!(Struct::as_usize(2).is_some(), true);
+    assert_eq!(Struct::as_usize(3).is_some(), false);
+
+    let value = Struct {
+        field1: "value1",
+        field2: "value2",
+        field3: 123456,
+        field4: "value4",
+        ignored: "ignored"
+    };
+
+    let fmt = PreparedFormat::prepare("{field1} {alias1} {alias2} {field2} {field3} {renamed}").unwrap().format(&value);
+    assert_eq!(fmt, "value1 value1 value1 value2 123456 value4");
+
+    let fmt = PreparedFormat::prepare("{0} {1} {2} {3}").unwrap().format(&value);
+    assert_eq!(fmt, "value1 value2 123456 value4");
+}
+
+#[test]
+fn test_tuple() {
+    #[derive(FormatArgs)]
+    struct Tuple(
+        #[format_args(aliases = "alias1,alias2")]
+        &'static str,
+        &'static str,
+        usize,
+        #[format_args(rename = "renamed")]
+        &'static str,
+        #[format_args(ignore)]
+        &'static str
+    );
+
+    assert_eq!(Tuple::validate_name("alias1").is_some(), true);
+    assert_eq!(Tuple::validate_name("alias1"), Tuple::validate_name("alias2"));
+    assert_eq!(Tuple::validate_name("renamed").is_some(), true);
+
+    assert_eq!(Tuple::validate_index(0), true);
+    assert_eq!(Tuple::validate_index(1), true);
+    assert_eq!(Tuple::validate_index(2), true);
+    assert_eq!(Tuple::validate_index(3), true);
+    assert_eq!(Tuple::validate_index(4), false);
+
+    assert_eq!(Tuple::as_usize(0).is_some(), false);
+    assert_eq!(Tuple::as_usize(1).is_some(), false);
+    assert_eq!(Tuple::as_usize(2).is_some(), true);
+    assert_eq!(Tuple::as_usize(3).is_some(), false);
+
+    let value = Tuple("value1", "value2", 123456, "value3", "ignored");
+
+    let fmt = PreparedFormat::prepare("{alias1} {alias2} {renamed}").unwrap().format(&value);
+    assert_eq!(fmt, "value1 value1 value3");
+
+    let fmt = PreparedFormat::prepare("{0} {1} {2} {3}").unwrap().format(&value);
+    assert_eq!(fmt, "value1 value2 123456 value3");
+}
+
+#[test]
+fn test_unit() {
+    #[derive(FormatArgs)]
+    struct Unit;
+
+    assert_eq!(Unit::validate_index(0), false);
+
+    let fmt = PreparedFormat::prepare("").unwrap().format(&Unit);
+    assert_eq!(fmt, "");
+}
+
+#[test]
+#[allow(dead_code)]
+fn test_enum() {
+    #[derive(FormatArgs)]
+    enum Event {
+        Login { user: &'static str },
+        Transfer { from: &'static str, to: &'static str, amount: usize },
+        Tick(#[format_args(rename = "count")] u64),
+        Idle,
+        Logout { user: &'static str, code: char },
+    }
+
+    assert_eq!(Event::validate_name("user"), Some(0));
+    assert_eq!(Event::validate_name("to"), Some(2));
+    assert_eq!(Event::validate_name("count"), Some(4));
+    assert_eq!(Event::validate_name("code"), Some(5));
+    assert_eq!(Event::validate_index(5), true);
+    assert_eq!(Event::validate_index(6), false);
+    assert_eq!(Event::arg_names(), &["user", "from", "to", "amount", "count", "code"]);
+
+    let prepared = PreparedFormat::prepare("[{user}{from}>{to}:{amount}{count}]").unwrap();
+    assert_eq!(prepared.format(&Event::Login { user: "alice" }), "[alice>:]");
+    assert_eq!(prepared.format(&Event::Transfer { from: "a", to: "b", amount: 5 }), "[a>b:5]");
+    assert_eq!(prepared.format(&Event::Tick(7)), "[>:7]");
+    assert_eq!(prepared.format(&Event::Idle), "[>:]");
+    assert_eq!(prepared.format(&Event::Logout { user: "bob", code: '!' }), "[bob>:]");
+
+    // accessors see the field of the active variant, and a default otherwise
+    let logout = Event::Logout { user: "bob", code: '!' };
+    assert_eq!(Event::as_str(0).map(|f| f(&logout)), Some("bob"));
+    assert_eq!(Event::as_str(0).map(|f| f(&Event::Idle)), Some(""));
+    assert!(Event::as_usize(0).is_none());
+    assert_eq!(Event::as_f64(4).map(|f| f(&Event::Tick(7))), Some(7.0));
+    let prepared = PreparedFormat::prepare("[{to:>amount$}|{user:code$^5}]").unwrap();
+    assert_eq!(prepared.format(&Event::Transfer { from: "a", to: "b", amount: 3 }), "[  b|]");
+    assert_eq!(prepared.format(&logout), "[|!bob!]");
+}
+
+#[test]
+fn test_rename_positions() {
+    #[derive(FormatArgs)]
+    struct Record {
+        #[format_args(rename = "userId")]
+        user_id: u32,
+        #[format_args(rename = "type")]
+        kind: &'static str,
+        name: &'static str,
+    }
+
+    assert_eq!(Record::validate_name("userId"), Some(0));
+    assert_eq!(Record::validate_name("user_id"), None);
+    assert_eq!(Record::validate_name("type"), Some(1));
+    assert_eq!(Record::validate_name("kind"), None);
+    assert_eq!(Record::validate_name("name"), Some(2));
+
+    let value = Record { user_id: 7, kind: "admin", name: "root" };
+    let fmt = PreparedFormat::prepare("{0} {1} {2} {userId} {type}").unwrap().format(&value);
+    assert_eq!(fmt, "7 admin root 7 admin");
+}
+
+#[test]
+fn test_skip() {
+    #[derive(FormatArgs)]
+    #[allow(dead_code)]
+    struct Cached {
+        name: &'static str,
+        #[format_args(skip)]
+        hash: u64,
+        size: usize,
+    }
+
+    assert_eq!(Cached::validate_name("name"), Some(0));
+    assert_eq!(Cached::validate_name("hash"), None);
+    assert_eq!(Cached::validate_name("size"), Some(1));
+    assert_eq!(Cached::validate_index(1), true);
+    assert_eq!(Cached::validate_index(2), false);
+
+    let value = Cached { name: "blob", hash: 0xdead, size: 12 };
+    let fmt = PreparedFormat::prepare("{0} {1} {size}").unwrap().format(&value);
+    assert_eq!(fmt, "blob 12 12");
+}
+
+#[test]
+fn test_generic_struct() {
+    use runtime_fmt::Error;
+
+    #[derive(FormatArgs)]
+    struct Pair<A, B> {
+        a: A,
+        b: B,
+    }
+
+    #[derive(Debug)]
+    struct DebugOnly;
+
+    let pair = Pair { a: 1i32, b: String::from("two") };
+    let prepared = PreparedFormat::<Pair<i32, String>>::prepare("{0} {1:?} {a:03} {b:>5}").unwrap();
+    assert_eq!(prepared.format(&pair), "1 \"two\" 001   two");
+
+    // no bounds are placed on the parameters, so traits are checked per use
+    let pair = Pair { a: 2.5f64, b: DebugOnly };
+    let prepared = PreparedFormat::<Pair<f64, DebugOnly>>::prepare("{a:.2} {b:?}").unwrap();
+    assert_eq!(prepared.format(&pair), "2.50 DebugOnly");
+    match PreparedFormat::<Pair<f64, DebugOnly>>::prepare("{b}") {
+        Err(Error::UnsatisfiedFormat { idx: 1, must_implement: "Display" }) => {}
+        other => panic!("expected an unsatisfied format, got {:?}", other.err()),
+    }
+}
+
+#[test]
+fn test_arg_names() {
+    #[derive(FormatArgs)]
+    #[allow(dead_code)]
+    struct Named {
+        first: u8,
+        #[format_args(rename = "second")]
+        middle: u8,
+        #[format_args(skip)]
+        hidden: u8,
+        #[format_args(aliases = "third,last")]
+        end: u8,
+    }
+
+    #[derive(FormatArgs)]
+    #[allow(dead_code)]
+    struct Positional(u8, &'static str);
+
+    assert_eq!(Named::arg_names(), &["first", "second", "end"]);
+    assert_eq!(Named::arg_names().len(), Named::arg_count());
+    assert_eq!(Positional::arg_names(), &["", ""]);
+    assert_eq!(<(u8, u8, u8)>::arg_names(), &["", "", ""]);
+    assert_eq!(<[u8; 2]>::arg_names(), &["", ""]);
+}
+
+#[test]
+fn test_every_trait_reachable() {
+    #[derive(FormatArgs)]
+    struct Everything {
+        int: u32,
+        float: f64,
+        ptr: &'static u8,
+    }
+
+    static BYTE: u8 = 7;
+    let value = Everything { int: 1234, float: 0.5, ptr: &BYTE };
+    let cases: &[(&str, String)] = &[
+        ("{int}", format!("{}", value.int)),
+        ("{int:?}", format!("{:?}", value.int)),
+        ("{int:o}", format!("{:o}", value.int)),
+        ("{int:b}", format!("{:b}", value.int)),
+        ("{int:x}", format!("{:x}", value.int)),
+        ("{int:X}", format!("{:X}", value.int)),
+        ("{float:e}", format!("{:e}", value.float)),
+        ("{float:E}", format!("{:E}", value.float)),
+        ("{ptr:p}", format!("{:p}", value.ptr)),
+    ];
+    for &(spec, ref expected) in cases {
+        let prepared = PreparedFormat::<Everything>::prepare(spec).unwrap();
+        assert_eq!(&prepared.format(&value), expected, "for {:?}", spec);
+    }
+
+    // traits a field does not implement are reported as unsatisfied
+    for spec in &["{float:x}", "{float:p}", "{int:p}"] {
+        assert!(PreparedFormat::<Everything>::prepare(spec).is_err(), "for {:?}", spec);
+    }
+}
+
+#[test]
+fn test_as_str() {
+    #[derive(FormatArgs)]
+    struct Entry {
+        name: String,
+        label: &'static str,
+        count: usize,
+        code: char,
+    }
+
+    let row = Entry { name: "ann".into(), label: "admin", count: 3, code: 'a' };
+    assert_eq!(Entry::as_str(0).map(|f| f(&row)), Some("ann"));
+    assert_eq!(Entry::as_str(1).map(|f| f(&row)), Some("admin"));
+    assert!(Entry::as_str(2).is_none());
+    assert!(Entry::as_str(3).is_none());
+    assert_eq!(Entry::as_char(3).map(|f| *f(&row)), Some('a'));
+    assert!(Entry::as_char(0).is_none());
+
+    let tuple = ("x", 5, String::from("y"));
+    assert_eq!(<(&str, i32, String)>::as_str(0).map(|f| f(&tuple)), Some("x"));
+    assert!(<(&str, i32, String)>::as_str(1).is_none());
+    assert_eq!(<(&str, i32, String)>::as_str(2).map(|f| f(&tuple)), Some("y"));
+    assert_eq!(<[&str; 2]>::as_str(1).map(|f| f(&["a", "b"])), Some("b"));
+
+    let dynamic: &runtime_fmt::DynFormatArgs = &row;
+    assert_eq!(dynamic.as_str(1), Some("admin"));
+    assert_eq!(dynamic.as_str(2), None);
+}
+
+#[test]
+fn test_as_f64() {
+    #[derive(FormatArgs)]
+    struct Stats {
+        hits: u32,
+        ratio: f32,
+        name: &'static str,
+        total: &'static i64,
+    }
+
+    static TOTAL: i64 = -40;
+    let stats = Stats { hits: 7, ratio: 0.25, name: "cache", total: &TOTAL };
+    assert_eq!(Stats::as_f64(0).map(|f| f(&stats)), Some(7.0));
+    assert_eq!(Stats::as_f64(1).map(|f| f(&stats)), Some(0.25));
+    assert!(Stats::as_f64(2).is_none());
+    assert_eq!(Stats::as_f64(3).map(|f| f(&stats)), Some(-40.0));
+
+    assert_eq!(<(u8, &str)>::as_f64(0).map(|f| f(&(200, "x"))), Some(200.0));
+    assert!(<(u8, &str)>::as_f64(1).is_none());
+    assert_eq!(runtime_fmt::codegen::as_f64(&&3usize), Some(3.0));
+    assert!(runtime_fmt::codegen::is_number::<&f64>());
+    assert!(!runtime_fmt::codegen::is_number::<char>());
+
+    let prepared = PreparedFormat::<Stats>::prepare("{name}: {hits:%} {ratio:.1%}").unwrap();
+    assert_eq!(prepared.format(&stats), "cache: 700% 25.0%");
+}
+
+#[test]
+fn test_trait_objects() {
+    use std::fmt::{Debug, Display};
+    use std::rc::Rc;
+
+    #[derive(FormatArgs)]
+    struct Erased {
+        shown: Box<Display>,
+        debugged: Box<Debug>,
+        shared: Rc<Display>,
+        borrowed: &'static Display,
+    }
+
+    let erased = Erased {
+        shown: Box::new(1.5),
+        debugged: Box::new("quoted"),
+        shared: Rc::new('c'),
+        borrowed: &7,
+    };
+    let prepared = PreparedFormat::<Erased>::prepare(
+        "{shown:>5.2} {debugged:?} {shared} {borrowed:03}").unwrap();
+    assert_eq!(prepared.format(&erased), " 1.50 \"quoted\" c 007");
+
+    // only the erased trait is available
+    assert!(PreparedFormat::<Erased>::prepare("{shown:?}").is_err());
+    assert!(PreparedFormat::<Erased>::prepare("{debugged}").is_err());
+
+    let values: Vec<Box<Display>> = vec![Box::new("a"), Box::new(2)];
+    assert_eq!(rt_format!("{}-{}", values[0], values[1]).unwrap(), "a-2");
+}
+
+#[test]
+fn test_decimal_separator() {
+    #[derive(FormatArgs)]
+    struct Reading {
+        celsius: f32,
+        offset: &'static f64,
+        sensor: &'static str,
+    }
+
+    static OFFSET: f64 = -0.25;
+    assert!(Reading::is_float(0));
+    assert!(Reading::is_float(1));
+    assert!(!Reading::is_float(2));
+
+    let mut prepared = PreparedFormat::<Reading>::prepare("{sensor}: {celsius:.1} ({offset})")
+        .unwrap();
+    prepared.decimal_separator(',');
+    let reading = Reading { celsius: 21.5, offset: &OFFSET, sensor: "t.1" };
+    assert_eq!(prepared.format(&reading), "t.1: 21,5 (-0,25)");
+}
+
+#[test]
+fn test_named_fill() {
+    #[derive(FormatArgs)]
+    struct Cell {
+        text: &'static str,
+        fill: char,
+        width: usize,
+    }
+
+    assert!(Cell::as_char(0).is_none());
+    assert!(Cell::as_char(1).is_some());
+    assert!(<(char, u8)>::as_char(0).is_some());
+
+    let prepared = PreparedFormat::<Cell>::prepare("[{text:fill$^width$}]").unwrap();
+    assert_eq!(prepared.format(&Cell { text: "ab", fill: '.', width: 6 }), "[..ab..]");
+    assert_eq!(prepared.format(&Cell { text: "ab", fill: '#', width: 3 }), "[ab#]");
+    prepared.write_chunks(&Cell { text: "a", fill: '~', width: 3 }, |chunk| {
+        if let runtime_fmt::Chunk::Formatted(text) = chunk {
+            assert_eq!(text, "~a~");
+        }
+    }).unwrap();
+
+    match PreparedFormat::<Cell>::prepare("{text:width$>5}") {
+        Err(runtime_fmt::Error::BadFill(2)) => {}
+        _ => panic!("expected BadFill"),
+    }
+}
@@ -30,4 +30,229 @@ fn simple_equivalence() {
     case!("Hex: {:.>4x}", 17);
     case!("{:p}", "Hello");
     case!("{}{}{}", "(A)", "_ _", "(B)");
-}
\ No newline at end of file
+}
+
+#[test]
+fn exp_equivalence() {
+    for &x in &[0.0f64, 1.0, -1.5, 1234.5678, -0.000321, 6.02e23] {
+        case!("{:e}|{:E}", x, x);
+        case!("{:+e}|{:+E}", x, x);
+        case!("{:.3e}|{:.3E}", x, x);
+        case!("{:+.3e}|{:+.3E}", x, x);
+        case!("{:012e}|{:012E}", x, x);
+        case!("{:+08.2e}|{:+08.2E}", x, x);
+        case!("{:>14.1e}|{:<14.1E}|{:^14e}", x, x, x);
+        case!("{:*^+16.4e}", x);
+        case!("{:+0w$.p$e}", x, w=15, p=2);
+    }
+}
+
+#[test]
+fn mixed_equivalence() {
+    let (a, b, c) = ("a", "b", "c");
+    case!("{} {name} {}", a, c, name = b);
+    case!("{name} {} {name} {}", a, c, name = b);
+    case!("{} {0} {name} {} {1} {}", a, c, "d", name = b);
+    case!("{1} {} {name} {0} {}", a, c, name = b);
+    case!("{:>4} {name:<3}|{:^5}|{0:?}", a, c, name = b);
+    case!("{x} {} {y} {} {x}", 1, 2, x = 3, y = 4);
+}
+
+#[test]
+fn escape_equivalence() {
+    case!("{{}}");
+    case!("{{{}}}", 1);
+    case!("{{{{}}}}");
+    case!("}}{{");
+    case!("100%");
+    case!("{}%", 100);
+    case!("{{{}%}}", 100);
+    case!("{{{x}}}{{}}{}", 'y', x = "x");
+    case!("{{:{:>3}}}", 7);
+}
+
+#[test]
+fn unicode_fill_equivalence() {
+    case!("{:—<10}|", "abc");
+    case!("{:—>10}|", 42);
+    case!("{:中^9}|", "mid");
+    case!("{:中^w$}|", "mid", w = 8);
+    case!("{:字>6}|{:字<6}", "中文", "é");
+    // combining characters count as chars of their own
+    case!("{:*<6}|", "e\u{301}e\u{301}");
+    case!("{:é^7.2}|", "e\u{301}xyz");
+}
+
+#[test]
+fn unicode_fill_grouping() {
+    // grouping pads by itself and must count chars rather than bytes
+    assert_eq!(rt_format!("{:中>8_}|", 12345).unwrap(), "中中12,345|");
+    assert_eq!(rt_format!("{:—<8_}|", 12345).unwrap(), "12,345——|");
+}
+
+#[test]
+fn debug_hex_equivalence() {
+    case!("{:x?}", vec![10, 11, 12]);
+    case!("{:X?}", vec![10, 11, 12]);
+    case!("{:#x?}", [255u8, 16]);
+    case!("{:04X?}|{:>6x?}", Some(171), 171);
+    case!("{0:x?} {0:?} {0:x}", 200);
+}
+
+#[test]
+fn implicit_counter_equivalence() {
+    // explicit indices never move the counter used by `{}`
+    let (a, b, c) = ("a", "b", "c");
+    case!("{} {2} {} {0}", a, b, c);
+    case!("{2} {1} {0} {} {} {}", a, b, c);
+    case!("{0} {0} {} {} {}", a, b, c);
+    case!("{} {} {1} {} {2}", a, b, c);
+    case!("{} {x} {1} {} {0}", a, b, x = c);
+    case!("{:>1$} {} {}", a, 5, c);
+    case!("{2:.*} {}", 3, "x", 1.23456);
+}
+
+#[test]
+fn sign_width_equivalence() {
+    for &x in &[0i32, 7, 42, -42, 12345, -123456, i32::min_value()] {
+        case!("{:+5}|{:+05}|{:<+5}|{:>+5}|{:^+5}", x, x, x, x, x);
+        case!("{:+5}|{:+05}|{:<+5}|{:>+5}|{:^+5}", x as i64, x as i64, x as i64, x as i64, x as i64);
+    }
+    for &x in &[0u8, 9, 255] {
+        case!("{:+5}|{:+05}|{:<+5}|{:>+5}", x, x, x, x);
+    }
+}
+
+#[test]
+fn special_float_equivalence() {
+    use std::f64::{INFINITY, NAN, NEG_INFINITY};
+
+    for &x in &[NAN, INFINITY, NEG_INFINITY, 0.0, -0.0] {
+        case!("{}|{:?}|{:e}|{:E}", x, x, x, x);
+        case!("{:+}|{:+?}|{:+e}", x, x, x);
+        case!("{:8}|{:<8}|{:^8}|{:>8}", x, x, x, x);
+        case!("{:08}|{:+08}|{:08.2}|{:+08.2e}", x, x, x, x);
+        case!("{:.3}|{:+.1}|{:*^9.2}", x, x, x);
+    }
+    for &x in &[::std::f32::NAN, ::std::f32::INFINITY, -0.0f32] {
+        case!("{}|{:+}|{:08.1}|{:e}", x, x, x, x);
+    }
+}
+
+#[test]
+fn star_precision_equivalence() {
+    case!("{:.*}", 3, 3.14159);
+    case!("{:.*} {:.*}", 1, 2.55, 0, 9.5);
+    case!("{:.*}|{}", 2, "abcdef", "next");
+    case!("{:>8.*}|", 2, 1.0f64 / 3.0);
+    case!("{1:.*} {0}", 2, 2.71828);
+    case!("{:.*e} {x}", 2, 12345.678, x = "done");
+}
+
+#[test]
+fn zero_pad_prefix_equivalence() {
+    case!("{:#06x}", 42);
+    case!("{:#06X}", 42);
+    case!("{:#08b}", 5);
+    case!("{:#010o}", 8);
+    case!("{:#04x}", 255);
+    case!("{:#x}|{:06x}|{:<#6x}|", 42, 42, 42);
+    case!("{:+#08x}", 42);
+    case!("{:#0w$x}", 42, w = 9);
+}
+
+#[test]
+fn zero_pad_prefix_grouping() {
+    // the grouping path pads by itself, and must agree with std
+    assert_eq!(rt_format!("{:#06_x}", 42).unwrap(), format!("{:#06x}", 42));
+    assert_eq!(rt_format!("{:#010_b}", 5).unwrap(), format!("{:#010b}", 5));
+    assert_eq!(rt_format!("{:08_}", 1234).unwrap(), "0001,234");
+}
+
+#[test]
+fn pretty_debug_equivalence() {
+    #[derive(Debug)]
+    struct Inner {
+        values: Vec<u8>,
+        label: &'static str,
+    }
+    #[derive(Debug)]
+    struct Outer {
+        inner: Inner,
+        pair: (i32, Option<char>),
+    }
+    let value = Outer {
+        inner: Inner { values: vec![1, 2], label: "  spaced\n" },
+        pair: (-3, Some('x')),
+    };
+    case!("{:#?}", value);
+    case!("{:#?}|{:?}", value.pair, value.pair);
+    case!("{:#x?}", value.inner.values);
+}
+
+#[test]
+fn formatter_state_equivalence() {
+    use std::fmt;
+
+    // Reports everything the formatter was configured with, and branches on
+    // the precision as a real implementation might.
+    struct Probe;
+    impl fmt::Display for Probe {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "w={:?} p={:?} fill={:?} +{} -{} #{} 0{}",
+                   f.width(), f.precision(), f.fill(), f.sign_plus(), f.sign_minus(),
+                   f.alternate(), f.sign_aware_zero_pad())?;
+            match f.precision() {
+                Some(0) => f.write_str(" whole"),
+                Some(p) => write!(f, " {} places", p),
+                None => f.write_str(" exact"),
+            }
+        }
+    }
+
+    case!("{}", Probe);
+    case!("{:.0}|{:.3}", Probe, Probe);
+    case!("{:*^12.2}", Probe);
+    case!("{:>+#08.1}", Probe);
+    case!("{:-<1$.2$}", Probe, 9, 4);
+    case!("{:w$.p$}", Probe, w = 5, p = 1);
+    case!("{:.*}", 2, Probe);
+}
+
+#[test]
+fn align_without_width_equivalence() {
+    case!("[{:<}] [{:>}] [{:^}]", "text", "text", "text");
+    case!("[{:<}] [{:>}] [{:^}]", 42, -42, 0);
+    case!("[{:*<}] [{:->}] [{:_^}]", "x", 7, 2.5);
+    case!("[{0:<?}] [{0:>x}] [{0:^#b}]", 10);
+    case!("[{name:<}] [{name:^.2}]", name = "abcdef");
+}
+
+#[test]
+fn indexed_width_and_precision_equivalence() {
+    case!("{:1$.2$}", 3.14159, 10, 2);
+    case!("[{0:>2$.1$}] [{0:<1$.2$}]", 2.71828, 3, 9);
+    case!("{:2$.1$}|{}", "truncated", 4, 7);
+    case!("{2:0$.1$}", 6, 1, 0.125);
+}
+
+#[test]
+fn mixed_count_equivalence() {
+    let (v, w, p) = (3.14159, 10, 3);
+    case!("[{v:8.2}]", v = v);
+    case!("[{v:8.p$}]", v = v, p = p);
+    case!("[{v:w$.2}]", v = v, w = w);
+    case!("[{v:w$.p$}]", v = v, w = w, p = p);
+    case!("[{:>8.1$}] [{:<2$.3}]", v, p, w);
+    case!("[{s:^8.p$}] [{s:w$.1}]", s = "truncate", w = w, p = p);
+}
+
+#[test]
+fn string_precision_equivalence() {
+    case!("[{:.3}]", "hello");
+    case!("[{:.0}]", "hello");
+    case!("[{:.10}]", "hello");
+    case!("[{:.2}] [{:.3}]", "héllo", "日本語テキスト");
+    case!("[{:>6.2}] [{:-<5.1}]", "ñandú", String::from("óle"));
+    case!("[{:.*}] [{s:.p$}]", 1, "🦀🦀", s = "e\u{301}e", p = 1);
+}
@@ -32,3 +32,533 @@ fn unused_is_fine() {
     t!("2"; rt_format!("{b}", a=1, b=2).unwrap());
     t!("3 1"; rt_format!("{} {a}", 3, 4, a=1, b=2).unwrap());
 }
+
+#[test]
+fn named_width() {
+    t!("    3"; "{x:w$}", x=3, w=5);
+    t!("3    "; "{x:<w$}", x=3, w=5);
+    t!("     3|"; "{:w$}|", 3, w=6);
+    // the same name may serve as both a value and a width
+    t!("   4"; "{w:w$}", w=4);
+    t!("2 2222"; "{w} {:>0w$}", 2222, w=2);
+}
+
+#[test]
+fn named_precision() {
+    t!("3.14"; "{v:.p$}", v=3.14159, p=2);
+    t!("3"; "{v:.p$}", v=3.14159, p=0);
+    t!("hel"; "{:.p$}", "hello", p=3);
+    t!("  3.1"; "{v:5.p$}", v=3.14159, p=1);
+}
+
+#[test]
+fn runtime_names() {
+    use std::collections::HashMap;
+    use runtime_fmt::{Error, FormatBuf, Param};
+
+    let mut map = HashMap::new();
+    map.insert("user".to_owned(), "alice".to_owned());
+    map.insert("time".to_owned(), "noon".to_owned());
+    let params: Vec<Param> = map.iter().map(|(k, v)| Param::named(k, v)).collect();
+
+    let buf = FormatBuf::new("{user} logged in at {time}", &params).unwrap();
+    assert_eq!(buf.format(), "alice logged in at noon");
+    match FormatBuf::new("{user} logged in from {place}", &params) {
+        Err(Error::BadName("place")) => {}
+        other => panic!("expected BadName, got {:?}", other),
+    }
+}
+
+#[test]
+fn eprint() {
+    assert!(rt_eprint!("{} ", "to stderr").is_ok());
+    assert!(rt_eprintln!("{x}", x = "with newline").is_ok());
+    match rt_eprintln!("{") {
+        Err(runtime_fmt::Error::BadSyntax(_)) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn writeln_fmt_write() {
+    use std::fmt::{self, Write};
+
+    let mut string = String::new();
+    rt_writeln!(string, "{}-{x}", 1, x = 2).unwrap();
+    rt_writeln!(&mut string, "{}", "tail").unwrap();
+    assert_eq!(string, "1-2\ntail\n");
+
+    // a failed parse writes nothing, not even the newline
+    assert!(rt_writeln!(string, "{").is_err());
+    assert_eq!(string, "1-2\ntail\n");
+
+    struct Lines(Vec<String>);
+    impl Write for Lines {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            for (i, part) in s.split('\n').enumerate() {
+                if i > 0 || self.0.is_empty() {
+                    self.0.push(String::new());
+                }
+                self.0.last_mut().unwrap().push_str(part);
+            }
+            Ok(())
+        }
+    }
+
+    let mut lines = Lines(Vec::new());
+    rt_writeln!(lines, "a{}", 'b').unwrap();
+    rt_write!(lines, "{:>3}", "c").unwrap();
+    assert_eq!(lines.0, ["ab", "  c"]);
+}
+
+#[test]
+fn write_counted() {
+    let mut message = String::from("prefix:");
+    assert_eq!(rt_write_counted!(message, "{} {:>4}", "h\u{e9}", 7).unwrap(), 8);
+    assert_eq!(rt_write_counted!(&mut message, "{{}}").unwrap(), 2);
+    assert_eq!(message, "prefix:h\u{e9}    7{}");
+    assert!(rt_write_counted!(message, "{").is_err());
+    assert_eq!(message.len(), 17);
+}
+
+#[test]
+fn grouping() {
+    t!("1,234,567"; "{:_}", 1234567);
+    t!("-1,234,567"; "{:_}", -1234567);
+    t!("123"; "{:_}", 123);
+    t!("+1,000"; "{:+_}", 1000);
+    t!("   1,234,567"; "{:12_}", 1234567);
+    t!("   1,234,567"; "{:>12_}", 1234567);
+    t!("1,234,567   "; "{:<12_}", 1234567);
+    t!("*1,234,567**"; "{:*^12_}", 1234567);
+    t!("-001,234"; "{:08_}", -1234);
+    t!("1,234,567.891"; "{:_}", 1234567.891);
+    t!("   -1,234.50"; "{:12_.2}", -1234.5);
+    t!("  1,234"; "{:w$_}", 1234, w = 7);
+    t!("text  "; "{:6_}", "text");
+}
+
+#[test]
+fn group_separator() {
+    let value = 1234567;
+    let mut buf = runtime_fmt::FormatBuf::new("{:_}|{}", &[
+        runtime_fmt::Param::normal(&value),
+        runtime_fmt::Param::normal(&value),
+    ]).unwrap();
+    buf.group_separator('.');
+    assert_eq!(buf.format(), "1.234.567|1234567");
+}
+
+#[test]
+fn style() {
+    let name = "ok";
+    assert_eq!(rt_format!("{:style(red,bold)}", name).unwrap(), "\x1b[1;31mok\x1b[0m");
+    assert_eq!(rt_format!("[{:>4style(on_blue)}]", name).unwrap(), "[\x1b[44m  ok\x1b[0m]");
+    assert_eq!(rt_format!("{:?style(green)}", name).unwrap(), "\x1b[32m\"ok\"\x1b[0m");
+    assert!(rt_format!("{:style(mauve)}", name).is_err());
+    assert!(rt_format!("{:frobnicate(red)}", name).is_err());
+
+    let mut buf = runtime_fmt::FormatBuf::new("[{:>4style(red)}]", &[
+        runtime_fmt::Param::normal(&name),
+    ]).unwrap();
+    buf.styling(false);
+    assert_eq!(buf.format(), "[  ok]");
+}
+
+#[test]
+fn literal_only() {
+    assert_eq!(rt_format!("").unwrap(), "");
+    assert_eq!(rt_format!("plain text").unwrap(), "plain text");
+    assert_eq!(rt_format!("{{escaped}} braces").unwrap(), "{escaped} braces");
+
+    let mut buf = runtime_fmt::FormatBuf::new("line", &[]).unwrap();
+    buf.newln();
+    assert_eq!(buf.format(), "line\n");
+    assert_eq!(buf.to_string(), "line\n");
+}
+
+#[test]
+fn format_args_display() {
+    use std::fmt::{self, Write};
+
+    struct Greeting(&'static str, &'static str);
+    impl fmt::Display for Greeting {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match rt_format_args!(self.0, self.1) {
+                Ok(args) => write!(f, "<{}>", args),
+                Err(_) => Err(fmt::Error),
+            }
+        }
+    }
+    assert_eq!(Greeting("Hello, {}!", "world").to_string(), "<Hello, world!>");
+    let mut out = String::new();
+    assert!(write!(out, "{}", Greeting("{", "world")).is_err());
+
+    // one buffer, several sinks
+    match rt_format_args!("{}-{x:?}", 1, x = "two") {
+        Ok(args) => {
+            let mut first = String::new();
+            write!(first, "{}", args).unwrap();
+            let second = format!("{} {:?}", args, args);
+            assert_eq!(first, "1-\"two\"");
+            assert_eq!(second, "1-\"two\" 1-\"two\"");
+        }
+        Err(e) => panic!("{}", e),
+    }
+}
+
+#[test]
+fn no_implicit_capture() {
+    let x = 5;
+    match rt_format!("{x}") {
+        Err(runtime_fmt::Error::BadName("x")) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+    t!("5"; "{x}", x = x);
+}
+
+#[cfg(feature = "unicode-width")]
+#[test]
+fn display_width() {
+    fn padded(spec: &str, value: &str, display_width: bool) -> String {
+        let mut buf = runtime_fmt::FormatBuf::new(spec, &[
+            runtime_fmt::Param::normal(&value),
+        ]).unwrap();
+        buf.display_width(display_width);
+        buf.format()
+    }
+
+    // CJK characters and emoji occupy two columns each
+    assert_eq!(padded("[{:>6}]", "日本", true), "[  日本]");
+    assert_eq!(padded("[{:>6}]", "日本", false), "[    日本]");
+    assert_eq!(padded("[{:^6}]", "😀", true), "[  😀  ]");
+    // a combining mark occupies none
+    assert_eq!(padded("[{:<3}]", "e\u{301}", true), "[e\u{301}  ]");
+    assert_eq!(padded("[{:<3}]", "e\u{301}", false), "[e\u{301} ]");
+    // plain ASCII is unaffected
+    assert_eq!(padded("[{:*^7}]", "abc", true), "[**abc**]");
+    // text which begins with digits is still left-aligned
+    assert_eq!(padded("[{:6}]", "42x", true), format!("[{:6}]", "42x"));
+    assert_eq!(padded("[{:06}]", "42x", true), format!("[{:06}]", "42x"));
+}
+
+#[test]
+fn pretty_indent() {
+    let value = vec![(1, "a")];
+    let mut buf = runtime_fmt::FormatBuf::new("{:#?} {:?}", &[
+        runtime_fmt::Param::normal(&value),
+        runtime_fmt::Param::normal(&value),
+    ]).unwrap();
+    let pretty = format!("{:#?}", value);
+    assert!(pretty.contains("\n        1,"));
+
+    buf.indent('\t', 1);
+    assert_eq!(buf.format(), format!("{} [(1, \"a\")]", pretty.replace("    ", "\t")));
+    buf.indent(' ', 2);
+    assert_eq!(buf.format(), format!("{} [(1, \"a\")]", pretty.replace("    ", "  ")));
+}
+
+#[test]
+fn decimal_separator() {
+    let value = 1234.5f64;
+    let count = 1234567u32;
+    let label = "v1.2";
+    let params = [
+        runtime_fmt::Param::normal(&value),
+        runtime_fmt::Param::normal(&count),
+        runtime_fmt::Param::normal(&label),
+    ];
+    let spec = "{0}|{0:_.2}|{0:e}|{0:>9?}|{1:_}|{2}";
+
+    let mut buf = runtime_fmt::FormatBuf::new(spec, &params).unwrap();
+    assert_eq!(buf.format(), "1234.5|1,234.50|1.2345e3|   1234.5|1,234,567|v1.2");
+
+    // the group separator gives way to the decimal separator
+    buf.decimal_separator(',');
+    assert_eq!(buf.format(), "1234,5|1.234,50|1,2345e3|   1234,5|1.234.567|v1.2");
+
+    buf.group_separator(' ');
+    assert_eq!(buf.format(), "1234,5|1 234,50|1,2345e3|   1234,5|1 234 567|v1.2");
+}
+
+#[test]
+fn comments() {
+    t!("     1,234 ok"; "{:>10_ ; right aligned total} {status;the outcome}", 1234, status = "ok");
+    t!("{; not a comment}"; "{{; not a comment}}");
+    match rt_format!("{0:>4; never closed", 1) {
+        Err(runtime_fmt::Error::BadSyntax(errors)) => assert_eq!(errors[0].span, 5..19),
+        other => panic!("expected a syntax error, got {:?}", other),
+    }
+}
+
+#[test]
+fn synthesize_sign() {
+    use std::fmt;
+
+    // a fixed-point number with two decimal places which ignores `+`
+    struct Fixed(i32);
+    impl fmt::Display for Fixed {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            let sign = if self.0 < 0 { "-" } else { "" };
+            write!(f, "{}{}.{:02}", sign, self.0.abs() / 100, self.0.abs() % 100)
+        }
+    }
+
+    let (pos, neg, int, text) = (Fixed(150), Fixed(-25), 5, "text");
+    let params = [
+        runtime_fmt::Param::normal(&pos),
+        runtime_fmt::Param::normal(&neg),
+        runtime_fmt::Param::normal(&int),
+        runtime_fmt::Param::normal(&text),
+    ];
+    let mut buf = runtime_fmt::FormatBuf::new("{:+}|{:+}|{:+}|{:+}|{0}", &params).unwrap();
+    assert_eq!(buf.format(), "1.50|-0.25|+5|text|1.50");
+    buf.synthesize_sign(true);
+    assert_eq!(buf.format(), "+1.50|-0.25|+5|text|1.50");
+
+    let mut buf = runtime_fmt::FormatBuf::new("{0:+07}|{0:>+7}|{2:+04}", &params).unwrap();
+    buf.synthesize_sign(true);
+    assert_eq!(buf.format(), "+001.50|  +1.50|+005");
+}
+
+#[test]
+fn escaping() {
+    t!("<b>&lt;i&gt;Tom &amp; &quot;Jerry&#39;s&quot;&lt;/i&gt;</b>";
+       "<b>{:html}</b>", "<i>Tom & \"Jerry's\"</i>");
+    t!("rm -- 'it'\\''s here'"; "rm -- {:shell}", "it's here");
+    t!("''"; "{:shell}", "");
+    t!(r#"{"name": "a \"b\"\\c\nd\u0001"}"#; "{{\"name\": {:json}}}", "a \"b\"\\c\nd\u{1}");
+    t!("1.5 & 3"; "{:html} & {:html}", 1.5, 3);
+
+    // precision applies before escaping and width after
+    t!("&lt;a&gt;"; "{:.3html}", "<a>bcd");
+    t!("  &lt;|"; "{:>6html}|", "<");
+    t!("'ab'  |"; "{:<6.2shell}|", "abc");
+}
+
+#[test]
+fn maxlen() {
+    let items: Vec<u32> = (1..21).collect();
+    t!("[1, 2, 3, \u{2026}"; "{:?maxlen(10)}", items);
+    t!("h\u{e9}llo w\u{2026}"; "{:maxlen(7)}", "h\u{e9}llo w\u{f6}rld");
+    t!("short"; "{:maxlen(5)}", "short");
+    t!("    abc\u{2026}|"; "{:>8maxlen(3)}|", "abcdef");
+    match rt_format!("{:maxlen(many)}", "x") {
+        Err(runtime_fmt::Error::NoSuchFormat("many")) => {}
+        other => panic!("expected a bad directive, got {:?}", other),
+    }
+}
+
+#[test]
+fn format_string_types() {
+    use std::borrow::Cow;
+
+    let owned = String::from("{}-{}");
+    let borrowed: &str = &owned;
+    let cow: Cow<str> = Cow::Owned(owned.clone());
+    t!("1-2"; owned, 1, 2);
+    t!("1-2"; &owned, 1, 2);
+    t!("1-2"; borrowed, 1, 2);
+    t!("1-2"; cow, 1, 2);
+    t!("1-2"; Cow::Borrowed("{}-{}"), 1, 2);
+
+    let prepared = runtime_fmt::PreparedFormat::<(i32, i32)>::prepare(&owned).unwrap();
+    assert_eq!(prepared.format(&(1, 2)), "1-2");
+    let buf = runtime_fmt::FormatBuf::new(&cow, &[]);
+    assert!(buf.is_err());
+}
+
+#[test]
+fn format_builder() {
+    let mut builder = runtime_fmt::FormatBuilder::new();
+    builder.arg("ann").named("visits", 3usize).arg(2.5);
+    assert_eq!(builder.format("{} {visits} {2:.1}").unwrap(), "ann 3 2.5");
+    assert_eq!(builder.format("{0:>visits$}|{1}|{2:?}").unwrap(), "ann|3|2.5");
+    assert!(builder.format("{visits:>w$}").is_err());
+
+    builder.named("w", 4usize).named("who", String::from("bo"));
+    assert_eq!(builder.format("[{visits:>w$}] {who:?}").unwrap(), "[   3] \"bo\"");
+    assert_eq!(builder.format(&String::from("{3}{4}")).unwrap(), "4bo");
+    match builder.format("{missing}") {
+        Err(runtime_fmt::Error::BadName("missing")) => {}
+        other => panic!("expected a bad name, got {:?}", other),
+    }
+
+    use runtime_fmt::DynFormatArgs;
+    assert_eq!(builder.as_str(4), Some("bo"));
+    assert_eq!(builder.as_f64(2), Some(2.5));
+    assert_eq!(builder.as_str(2), None);
+}
+
+#[test]
+fn trailing_commas() {
+    t!("1 2"; "{} {}", 1, 2,);
+    t!("a=1 b=2"; "a={a} b={b}", a = 1, b = 2,);
+    t!("1 b=2"; "{} b={b}", 1, b = 2,);
+    t!("none"; "none",);
+
+    use std::fmt::Write;
+    let mut buf = String::new();
+    rt_write!(buf, "{}-{x}", 1, x = 2,).unwrap();
+    rt_writeln!(buf, "!",).unwrap();
+    assert_eq!(buf, "1-2!\n");
+}
+
+#[test]
+fn or_default() {
+    t!("N/A"; "{:or(N/A)}", "");
+    t!("set"; "{:or(N/A)}", "set");
+    t!("[  N/A]"; "[{:>5or(N/A)}]", "");
+    t!("[ab   ]"; "[{:<5or(N/A)}]", "ab");
+    t!("[ - ]"; "[{:or(\" - \")}]", "");
+    t!("0"; "{:or(none)}", 0);
+    t!("nick: none"; "nick: {nick:or(none)}", nick = String::new());
+}
+
+#[test]
+fn numeric_padding() {
+    // Only numbers are right-aligned and zero-padded by default, however
+    // their output begins.
+    let text = format!("[{:6}]", "42x");
+    t!("[\x1b[1m42x   \x1b[0m]"; "[{:6style(bold)}]", "42x");
+    t!(text; "[{:6html}]", "42x");
+    t!(text; "[{:06maxlen(5)}]", "42x");
+    t!(text; "[{:6join(,)}]", ["42x"]);
+    t!(text; "[{:06or(-)}]", "42x");
+    t!("[    42]"; "[{:6html}]", 42);
+    t!("[-00042]"; "[{:06maxlen(9)}]", -42);
+}
+
+#[test]
+fn join() {
+    let items = vec![1, 2, 3];
+    t!("1, 2, 3"; "{:join(\", \")}", items);
+    t!("1/2/3"; "{:join(/)}", items);
+    t!("a - b"; "{:join(\" - \")}", ["a", "b"]);
+    t!("123"; "{:join()}", &items[..]);
+    t!(""; "{:join(,)}", Vec::<u8>::new());
+    t!("[1.50|2.25]  "; "[{:.2join(|)}]  ", [1.5, 2.25]);
+    t!("  1,2,3|"; "{:>7join(,)}|", items);
+    t!("x: a+b"; "x: {list:join(+)}", list = vec!["a", "b"]);
+    match rt_format!("{:join(,)}", 5) {
+        Err(runtime_fmt::Error::UnsatisfiedFormat { idx: 0, must_implement: "Join" }) => {}
+        other => panic!("expected an unsatisfied format, got {:?}", other),
+    }
+    match rt_format!("{:?join(,)}", items) {
+        Err(runtime_fmt::Error::NoSuchFormat("?")) => {}
+        other => panic!("expected a bad format, got {:?}", other),
+    }
+}
+
+#[test]
+fn named_fill() {
+    t!("**3.14**"; "{val:fill$^w$.p$}", val = 3.14159, fill = '*', w = 8, p = 2);
+    t!("--x"; "{:pad$>3}", "x", pad = '-');
+    t!("é_é_|"; "{0:f$<2}{0:f$<2}|", "é", f = '_');
+    t!("<<42"; "{n:c$>w$_}", n = 42, c = '<', w = 4);
+}
+
+#[test]
+fn self_referential_counts() {
+    t!("   4"; "{w:w$}", w = 4);
+    t!("4   |"; "{w:<w$}|", w = 4);
+    t!("    3|  5"; "{a:b$}|{b:a$}", a = 3, b = 5);
+    t!("  3"; "{0:0$}", 3usize);
+}
+
+#[test]
+fn percent() {
+    t!("12.3%"; "{:.1%}", 0.1234);
+    t!("50%"; "{:%}", 0.5);
+    t!("-2.50%"; "{:.2%}", -0.025f32);
+    t!("10%"; "{:%}", 0.1f32);
+    t!("10\u{2030}"; "{:permille}", 0.01f32);
+    t!("   +75%|"; "{:>+7%}|", 0.75);
+    t!("0012.5%"; "{:07.1%}", 0.125);
+    t!("ratio: 12.5%"; "ratio: {r:%}", r = &0.125);
+    t!("12.3\u{2030}"; "{:.1permille}", 0.01234);
+    match rt_format!("{:%}", "half") {
+        Err(runtime_fmt::Error::UnsatisfiedFormat { idx: 0, must_implement: "Percent" }) => {}
+        other => panic!("expected an unsatisfied format, got {:?}", other),
+    }
+}
+
+#[test]
+fn disp_or_debug() {
+    #[derive(Debug)]
+    struct OnlyDebug(u8);
+    struct Neither;
+
+    t!("text"; "{:disp_or_debug}", "text");
+    t!("OnlyDebug(7)"; "{:disp_or_debug}", OnlyDebug(7));
+    t!("[  1.5]"; "[{:>5disp_or_debug}]", 1.5);
+    t!("Some(2)"; "{:disp_or_debug}", Some(2));
+    match rt_format!("{:disp_or_debug}", Neither) {
+        Err(runtime_fmt::Error::UnsatisfiedFormat {
+            idx: 0, must_implement: "Display or Debug"
+        }) => {}
+        other => panic!("expected an unsatisfied format, got {:?}", other),
+    }
+}
+
+#[test]
+fn raw() {
+    t!("<b>x</b>"; "{:raw}", "<b>x</b>");
+    t!("[  ab]"; "[{:>4raw}]", String::from("ab"));
+    t!("[ab  ]"; "[{:<4raw}]", std::borrow::Cow::Borrowed("ab"));
+    t!("ab"; "{:.2raw}", "abc");
+    match rt_format!("{:raw}", 5) {
+        Err(runtime_fmt::Error::UnsatisfiedFormat { idx: 0, must_implement: "Raw" }) => {}
+        other => panic!("expected an unsatisfied format, got {:?}", other),
+    }
+}
+
+#[test]
+fn hexdump() {
+    t!("00000000  48 69 0a                                         |Hi.|";
+        "{:hexdump}", b"Hi\n");
+    t!("00000000  00 01 02 03  |....|\n00000004  ff 41        |.A|";
+        "{:.4hexdump}", vec![0u8, 1, 2, 3, 0xff, b'A']);
+    t!("00000000  61 62  |ab|"; "{s:.2hexdump}", s = "ab");
+    t!("[]"; "[{:hexdump}]", &[] as &[u8]);
+    match rt_format!("{:hexdump}", 42) {
+        Err(runtime_fmt::Error::UnsatisfiedFormat { idx: 0, must_implement: "HexDump" }) => {}
+        other => panic!("expected an unsatisfied format, got {:?}", other),
+    }
+}
+
+#[test]
+#[should_panic(expected = "invalid state idle at 3")]
+fn panic_message() {
+    let template = String::from("invalid state {state} at {pos}");
+    rt_panic!(template, state = "idle", pos = 3);
+}
+
+#[test]
+#[should_panic(expected = "no argument named 'pos'")]
+fn panic_bad_template() {
+    rt_panic!("invalid state {state} at {pos}", state = "idle");
+}
+
+#[test]
+fn quoted_names() {
+    let params = [
+        runtime_fmt::Param::named("weird:name", &5),
+        runtime_fmt::Param::named("a.b", &"dotted"),
+        runtime_fmt::Param::named("say \"hi\"", &'x'),
+    ];
+    let format = |spec: &'static str| {
+        runtime_fmt::FormatBuf::new(spec, &params).map(|buf| buf.format())
+    };
+    assert_eq!(format("{\"weird:name\":?}|{\"weird:name\":>3}").unwrap(), "5|  5");
+    assert_eq!(format("{\"a.b\"}").unwrap(), "dotted");
+    assert_eq!(format("{\"say \\\"hi\\\"\"}").unwrap(), "x");
+    match format("{\"missing\"}") {
+        Err(runtime_fmt::Error::BadName("missing")) => {}
+        other => panic!("expected a bad name, got {:?}", other),
+    }
+    match format("{\"weird:name}") {
+        Err(runtime_fmt::Error::BadSyntax(_)) => {}
+        other => panic!("expected bad syntax, got {:?}", other),
+    }
+}
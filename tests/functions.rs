@@ -0,0 +1,155 @@
+//! Tests of the free functions beside `PreparedFormat`: validation, lossy
+//! formatting, sinks, and the format cache.
+#[macro_use] extern crate runtime_fmt_derive;
+extern crate runtime_fmt;
+
+#[test]
+fn test_validate_all() {
+    #[derive(FormatArgs)]
+    #[allow(dead_code)]
+    struct Form {
+        name: &'static str,
+        width: usize,
+        ratio: f64,
+    }
+
+    assert!(runtime_fmt::validate_all::<Form>("{name:>width$} {ratio:.3e}").is_ok());
+
+    let errors = runtime_fmt::validate_all::<Form>(
+        "{nmae} {name:x} {7} {ratio:w$} {name:.ratio$} {:q}"
+    ).unwrap_err();
+    assert_eq!(format!("{:?}", errors), format!("{:?}", vec![
+        runtime_fmt::Error::BadName("nmae"),
+        runtime_fmt::Error::UnsatisfiedFormat { idx: 0, must_implement: "LowerHex" },
+        runtime_fmt::Error::BadIndex(7),
+        runtime_fmt::Error::BadName("w"),
+        runtime_fmt::Error::BadNamedCount { count: "precision", name: "ratio" },
+        runtime_fmt::Error::NoSuchFormat("q"),
+    ]));
+
+    #[derive(FormatArgs)]
+    #[allow(dead_code)]
+    struct Order {
+        id: u32,
+        form: Form,
+    }
+
+    assert!(runtime_fmt::validate_all::<Order>("{form.name} {form.ratio:.2}").is_ok());
+    let errors = runtime_fmt::validate_all::<Order>("{from.name} {id.x}").unwrap_err();
+    assert_eq!(format!("{:?}", errors), format!("{:?}", vec![
+        runtime_fmt::Error::BadName("from.name"),
+        runtime_fmt::Error::BadName("id.x"),
+    ]));
+
+    let errors = runtime_fmt::validate_all::<Form>("{name} {").unwrap_err();
+    assert_eq!(errors.len(), 1);
+    match errors[0] {
+        runtime_fmt::Error::BadSyntax(_) => {}
+        ref other => panic!("unexpected error: {}", other),
+    }
+}
+
+#[test]
+fn test_format_lossy() {
+    #[derive(FormatArgs)]
+    struct Line {
+        level: &'static str,
+        message: &'static str,
+        width: usize,
+    }
+
+    let line = Line { level: "warn", message: "disk full", width: 6 };
+    let lossy = |spec| runtime_fmt::format_lossy(spec, &line, "{!error}");
+
+    assert_eq!(lossy("[{level:>width$}] {message}"), "[  warn] disk full");
+    assert_eq!(lossy("[{level}] {mesage}!"), "[warn] {!error}!");
+    assert_eq!(lossy("{1} {9} {level:x} {message:w$} {:.message$}|"),
+        "disk full {!error} {!error} {!error} {!error}|");
+    assert_eq!(lossy("{} {} {} {}"), "warn disk full 6 {!error}");
+    assert_eq!(lossy("{nope}{level:>width$}{nope}"), "{!error}  warn{!error}");
+    // invalid syntax is left as-is
+    assert_eq!(lossy("{level"), "{level");
+}
+
+#[test]
+fn test_format_with_sink() {
+    use runtime_fmt::{Chunk, Error};
+
+    #[derive(FormatArgs)]
+    struct Comment {
+        user: &'static str,
+        score: i32,
+    }
+
+    let comment = Comment { user: "<b>ann</b>", score: 7 };
+    let mut chunks = Vec::new();
+    runtime_fmt::format_with_sink("{{{user}}} scored {score:>3}{score:_}!", &comment, |chunk| {
+        chunks.push(match chunk {
+            Chunk::Literal(text) => format!("L({})", text),
+            Chunk::Formatted(text) => format!("F({})", text),
+        })
+    }).unwrap();
+    assert_eq!(chunks, ["L({)", "F(<b>ann</b>)", "L(} scored )", "F(  7)", "F(7)", "L(!)"]);
+
+    // only the arguments need be escaped
+    let mut html = String::new();
+    runtime_fmt::format_with_sink("<i>{user}</i>", &comment, |chunk| match chunk {
+        Chunk::Literal(text) => html.push_str(text),
+        Chunk::Formatted(text) => html.push_str(&text.replace('<', "&lt;").replace('>', "&gt;")),
+    }).unwrap();
+    assert_eq!(html, "<i>&lt;b&gt;ann&lt;/b&gt;</i>");
+
+    match runtime_fmt::format_with_sink("{nobody}", &comment, |_| panic!("no output expected")) {
+        Err(Error::BadName("nobody")) => {}
+        other => panic!("expected a bad name, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_format_cache() {
+    use std::sync::Arc;
+    use std::thread;
+    use runtime_fmt::{CacheStats, FormatCache};
+
+    #[derive(FormatArgs)]
+    struct Page {
+        title: &'static str,
+        views: u32,
+    }
+
+    let page = Page { title: "home", views: 12 };
+    let cache = FormatCache::<Page>::new();
+    assert_eq!(cache.format("short", "{title}", &page).unwrap(), "home");
+    assert_eq!(cache.format("long", "{title}: {views} views", &page).unwrap(),
+               "home: 12 views");
+    // a hit uses the cached format, not the one given
+    assert_eq!(cache.format("short", "ignored", &page).unwrap(), "home");
+    assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 2 });
+    assert_eq!(cache.len(), 2);
+    // failures are not cached
+    assert!(cache.format("bad", "{nope}", &page).is_err());
+    assert_eq!(cache.len(), 2);
+
+    // the least recently used entry is evicted
+    let cache = FormatCache::<Page>::with_limit(2);
+    cache.format("a", "a{views}", &page).unwrap();
+    cache.format("b", "b{views}", &page).unwrap();
+    cache.format("a", "", &page).unwrap();
+    cache.format("c", "c{views}", &page).unwrap();
+    assert_eq!(cache.len(), 2);
+    assert_eq!(cache.format("a", "", &page).unwrap(), "a12");
+    assert_eq!(cache.format("b", "new{views}", &page).unwrap(), "new12");
+
+    // concurrent misses on the same key agree
+    let cache = Arc::new(FormatCache::<(u32,)>::new());
+    let threads: Vec<_> = (0..4).map(|i| {
+        let cache = cache.clone();
+        thread::spawn(move || cache.format("shared", "<{0}>", &(i,)).unwrap())
+    }).collect();
+    let mut outputs: Vec<String> = threads.into_iter().map(|t| t.join().unwrap()).collect();
+    outputs.sort();
+    assert_eq!(outputs, ["<0>", "<1>", "<2>", "<3>"]);
+    assert_eq!(cache.len(), 1);
+    let stats = cache.stats();
+    assert_eq!(stats.hits + stats.misses, 4);
+}
@@ -0,0 +1,102 @@
+//! Feed pseudo-random format strings to every entry point, checking that
+//! each returns rather than panicking.
+#[macro_use] extern crate runtime_fmt;
+
+use runtime_fmt::{ast, PreparedFormat};
+
+// Fragments from which format strings are assembled, chosen to exercise
+// every part of the specifier syntax.
+const FRAGMENTS: &'static [&'static str] = &[
+    "{", "}", "{{", "}}", ":", ".", "*", "$", "-", "+", "#", "0", "1", "2", "9",
+    "x", "X", "?", "e", "_", "<", ">", "^", "(", ")", ";", ",", " ", "a", "w",
+    "name", "\u{e9}", "\u{30b3}", "\u{345}", "style", "red", "maxlen", "html",
+    "json", "18446744073709551616",
+];
+
+// A linear congruential generator, so that failures are reproducible.
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self, bound: usize) -> usize {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        ((self.0 >> 33) % bound as u64) as usize
+    }
+}
+
+// Whether formatting `spec` would write only a modest amount of padding.
+fn small_counts(spec: &str) -> bool {
+    let small = |count| match count {
+        ast::Count::Is(n) => n <= 64,
+        _ => true,
+    };
+    match ast::parse(spec) {
+        Ok(pieces) => pieces.iter().all(|piece| match *piece {
+            ast::Piece::Argument { ref format, .. } => small(format.width) && small(format.precision),
+            ast::Piece::Literal(_) => true,
+        }),
+        Err(_) => true,
+    }
+}
+
+#[test]
+fn random_specs() {
+    let mut rng = Lcg(0x5eed);
+    for _ in 0..20000 {
+        let mut spec = String::new();
+        for _ in 0..rng.next(16) {
+            spec.push_str(FRAGMENTS[rng.next(FRAGMENTS.len())]);
+        }
+
+        let _ = ast::parse(&spec);
+        let _ = runtime_fmt::validate_all::<(usize, &str, f64)>(&spec);
+        if !small_counts(&spec) {
+            continue;
+        }
+        let value = (3usize, "two", 2.5f64);
+        if let Ok(prepared) = PreparedFormat::<(usize, &str, f64)>::prepare(&spec) {
+            prepared.format(&value);
+        }
+        runtime_fmt::format_lossy(&spec, &value, "?");
+        let _ = rt_format!(spec, 3usize, "two", 2.5, w = 4usize, name = "n");
+    }
+}
+
+#[test]
+fn arbitrary_bytes() {
+    // Each input pads its output by at most the counts `small_counts` allows.
+    // Inputs grow from 32 bytes to 4096 as they become fewer, as for
+    // `long_specs`, so that work growing faster than the input stalls the
+    // test rather than passing unnoticed.
+    let mut rng = Lcg(0xb17e5);
+    let value = (3usize, "two", 2.5f64);
+    for shift in 0..8 {
+        for _ in 0..20000 >> shift {
+            let len = rng.next(32 << shift);
+            let bytes: Vec<u8> = (0..len).map(|_| rng.next(256) as u8).collect();
+            let spec = String::from_utf8_lossy(&bytes).into_owned();
+            if !small_counts(&spec) {
+                continue;
+            }
+
+            let _ = ast::parse(&spec);
+            if let Ok(prepared) = PreparedFormat::<(usize, &str, f64)>::prepare(&spec) {
+                let output = prepared.format(&value);
+                assert!(output.len() <= 80 * (spec.len() + 1), "{:?} wrote {:?}", spec, output);
+            }
+            let output = runtime_fmt::format_lossy(&spec, &value, "?");
+            assert!(output.len() <= 80 * (spec.len() + 1), "{:?} wrote {:?}", spec, output);
+        }
+    }
+}
+
+#[test]
+fn long_specs() {
+    // Parsing keeps up with long inputs of each kind of fragment.
+    for fragment in FRAGMENTS {
+        let spec = fragment.repeat(20000);
+        let _ = ast::parse(&spec);
+        let _ = runtime_fmt::validate_all::<(usize,)>(&spec);
+    }
+    let nested = "{:".repeat(20000);
+    assert!(ast::parse(&nested).is_err());
+}
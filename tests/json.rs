@@ -0,0 +1,58 @@
+#![cfg(feature = "serde_json")]
+#[macro_use] extern crate serde_json;
+extern crate runtime_fmt;
+
+use runtime_fmt::{Error, FormatBuf};
+
+#[test]
+fn json_object() {
+    let value = json!({
+        "name": "ann",
+        "age": 37,
+        "admin": true,
+        "tags": ["a", "b"],
+        "manager": null,
+    });
+    let buf = FormatBuf::new_dyn("{name:>5}/{age}/{admin}/{tags}/{name:?}", &value).unwrap();
+    assert_eq!(buf.format(), "  ann/37/true/[\"a\",\"b\"]/\"ann\"");
+
+    match FormatBuf::new_dyn("{manager}", &value) {
+        Err(Error::MissingValue("manager")) => {}
+        _ => panic!("null key was not rejected"),
+    }
+    match FormatBuf::new_dyn("{missing}", &value) {
+        Err(Error::BadName("missing")) => {}
+        _ => panic!("missing key was not rejected"),
+    }
+    assert!(FormatBuf::new_dyn("{0}", &value).is_err());
+}
+
+#[test]
+fn json_array() {
+    let value = json!([1.5, "two", null]);
+    let buf = FormatBuf::new_dyn("{0}|{1}|{2}|{}", &value).unwrap();
+    assert_eq!(buf.format(), "1.5|two|null|1.5");
+    assert!(FormatBuf::new_dyn("{3}", &value).is_err());
+    assert!(FormatBuf::new_dyn("{0:x}", &value).is_err());
+}
+
+#[test]
+fn json_missing_value() {
+    let value = json!({
+        "user": { "name": "ann", "email": null },
+        "width": null,
+    });
+    match FormatBuf::new_dyn("{user.email}", &value) {
+        Err(Error::MissingValue("user.email")) => {}
+        other => panic!("expected a missing value, got {:?}", other.err()),
+    }
+    match FormatBuf::new_dyn("{user.phone}", &value) {
+        Err(Error::BadName("user.phone")) => {}
+        other => panic!("expected an unknown name, got {:?}", other.err()),
+    }
+    match FormatBuf::new_dyn("{user:width$}", &value) {
+        Err(Error::MissingValue("width")) => {}
+        other => panic!("expected a missing value, got {:?}", other.err()),
+    }
+    assert_eq!(Error::MissingValue("width").to_string(), "no value for 'width'");
+}
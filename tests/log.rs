@@ -0,0 +1,79 @@
+#![cfg(feature = "log")]
+#[macro_use] extern crate runtime_fmt;
+extern crate log;
+
+use std::cell::RefCell;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+thread_local! {
+    static RECORDS: RefCell<Vec<(Level, String)>> = RefCell::new(Vec::new());
+}
+
+struct Capture;
+
+impl Log for Capture {
+    fn enabled(&self, _: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        RECORDS.with(|r| r.borrow_mut().push((record.level(), record.args().to_string())));
+    }
+
+    fn flush(&self) {}
+}
+
+static CAPTURE: Capture = Capture;
+
+static FORMATTED: AtomicUsize = ATOMIC_USIZE_INIT;
+
+struct Counted;
+
+impl fmt::Display for Counted {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        FORMATTED.fetch_add(1, Ordering::SeqCst);
+        f.write_str("counted")
+    }
+}
+
+fn take() -> Vec<(Level, String)> {
+    RECORDS.with(|r| r.borrow_mut().drain(..).collect())
+}
+
+#[test]
+fn log_macros() {
+    log::set_logger(&CAPTURE).unwrap();
+    log::set_max_level(LevelFilter::Info);
+
+    rt_info!("user {id} did {action}", id = 7, action = "login");
+    rt_log!(Level::Warn, "{} of {}", 3, 4);
+    rt_warn!(target: "audit", "{:>5}", "x");
+    assert_eq!(take(), vec![
+        (Level::Info, "user 7 did login".to_owned()),
+        (Level::Warn, "3 of 4".to_owned()),
+        (Level::Warn, "    x".to_owned()),
+    ]);
+
+    // disabled levels are not formatted at all
+    rt_debug!("{}", Counted);
+    rt_trace!("{}", Counted);
+    assert_eq!(FORMATTED.load(Ordering::SeqCst), 0);
+    assert!(take().is_empty());
+    rt_error!("{}", Counted);
+    assert_eq!(FORMATTED.load(Ordering::SeqCst), 1);
+    assert_eq!(take(), vec![(Level::Error, "counted".to_owned())]);
+
+    // the target is evaluated only once
+    let mut targets = 0;
+    rt_warn!(target: { targets += 1; "audit" }, "{}", 1);
+    assert_eq!(targets, 1);
+    assert_eq!(take(), vec![(Level::Warn, "1".to_owned())]);
+
+    // template errors are logged rather than swallowed
+    rt_info!("{missing}", present = 1);
+    assert_eq!(take(), vec![
+        (Level::Error, "invalid format string: no argument named 'missing'".to_owned()),
+    ]);
+}
@@ -0,0 +1,50 @@
+extern crate runtime_fmt;
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Display;
+use runtime_fmt::{Error, FormatBuf};
+
+#[test]
+fn hash_map() {
+    let mut map = HashMap::new();
+    map.insert(String::from("user"), String::from("ann"));
+    map.insert(String::from("action"), String::from("login"));
+    let buf = FormatBuf::new_dyn("{user:>5} did {action:?}", &map).unwrap();
+    assert_eq!(buf.format(), "  ann did \"login\"");
+
+    match FormatBuf::new_dyn("{missing}", &map) {
+        Err(Error::BadName("missing")) => {}
+        _ => panic!("missing key was not rejected"),
+    }
+    assert!(FormatBuf::new_dyn("{0}", &map).is_err());
+    assert!(FormatBuf::new_dyn("{}", &map).is_err());
+    assert!(FormatBuf::new_dyn("{user:x}", &map).is_err());
+}
+
+#[test]
+fn boxed_values() {
+    let mut map: HashMap<String, Box<Display>> = HashMap::new();
+    map.insert(String::from("user"), Box::new("ann"));
+    map.insert(String::from("time"), Box::new(9.5));
+    let buf = FormatBuf::new_dyn("{user} logged in at {time:.2}", &map).unwrap();
+    assert_eq!(buf.format(), "ann logged in at 9.50");
+
+    match FormatBuf::new_dyn("{user} logged out at {until}", &map) {
+        Err(Error::BadName("until")) => {}
+        _ => panic!("missing key was not rejected"),
+    }
+}
+
+#[test]
+fn btree_map() {
+    let mut map = BTreeMap::new();
+    map.insert("width", 6usize);
+    map.insert("id", 42);
+    let buf = FormatBuf::new_dyn("[{id:>width$}] [{id:#x}]", &map).unwrap();
+    assert_eq!(buf.format(), "[    42] [0x2a]");
+
+    let mut nested = BTreeMap::new();
+    nested.insert("point", (1.5, 2));
+    let buf = FormatBuf::new_dyn("{point.0:.2}, {point.1}", &nested).unwrap();
+    assert_eq!(buf.format(), "1.50, 2");
+}
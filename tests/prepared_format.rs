@@ -0,0 +1,355 @@
+//! Tests of `PreparedFormat` and `PrepareOptions`.
+#[macro_use] extern crate runtime_fmt_derive;
+#[macro_use] extern crate runtime_fmt;
+
+mod common;
+
+use runtime_fmt::{FormatArgs, PreparedFormat};
+use runtime_fmt::ast::Position;
+use common::{row, Failing, Point, Row, Wrapper};
+
+#[test]
+fn test_write_to() {
+    use std::io;
+
+    #[derive(FormatArgs)]
+    struct Pair(&'static str, u32);
+
+    let prepared = PreparedFormat::prepare("{0}={1:x};").unwrap();
+    let mut buf: Vec<u8> = Vec::new();
+    prepared.write_to(&Pair("a", 10), &mut buf).unwrap();
+    prepared.write_to(&Pair("b", 255), &mut buf).unwrap();
+    assert_eq!(buf, b"a=a;b=ff;");
+    rt_format_args!("{}={:x};", "c", 12).unwrap().write_to(&mut buf).unwrap();
+    assert_eq!(buf, b"a=a;b=ff;c=c;");
+
+    // bytes written before an error remain written
+    let mut small = [0u8; 4];
+    let err = prepared.write_to(&Pair("long", 1), &mut &mut small[..]).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::WriteZero);
+    assert_eq!(&small, b"long");
+
+    let mut buf: Vec<u8> = Vec::new();
+    let err = PreparedFormat::prepare("ok {0}").unwrap().write_to(&Wrapper(Failing), &mut buf);
+    assert_eq!(err.unwrap_err().kind(), io::ErrorKind::Other);
+    assert_eq!(buf, b"ok ");
+}
+
+#[test]
+fn test_into_owned() {
+    let prepared = {
+        let spec = String::from("({x}, {y})");
+        PreparedFormat::prepare(&spec).unwrap().into_owned()
+    };
+    assert_eq!(prepared.format(&Point { x: 1, y: 2 }), "(1, 2)");
+    assert_eq!(prepared.format(&Point { x: -3, y: 4 }), "(-3, 4)");
+}
+
+#[test]
+fn test_render() {
+    let prepared = {
+        let spec = String::from("{{{x}, {y}}}");
+        PreparedFormat::prepare(&spec).unwrap().into_owned()
+    };
+    assert_eq!(prepared.render(&Point { x: 1, y: 2 }).unwrap(), "{1, 2}");
+
+    match PreparedFormat::prepare("{0}").unwrap().render(&Wrapper(Failing)) {
+        Err(runtime_fmt::Error::Fmt(_)) => {}
+        other => panic!("expected a formatting error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_render_into() {
+    use runtime_fmt::Error;
+
+    let prepared = PreparedFormat::<(&str, u32)>::prepare("{0} = {1:>4}").unwrap();
+    let mut buf = String::from("stale contents");
+    prepared.render_into(&mut buf, &("x", 12)).unwrap();
+    assert_eq!(buf, "x =   12");
+
+    let capacity = buf.capacity();
+    prepared.render_into(&mut buf, &("y", 3)).unwrap();
+    assert_eq!(buf, "y =    3");
+    assert_eq!(buf.capacity(), capacity);
+
+    let prepared = PreparedFormat::<(u32, Failing, u32)>::prepare("{0}, {1}, {2}").unwrap();
+    match prepared.render_into(&mut buf, &(1, Failing, 2)) {
+        Err(Error::Fmt(_)) => {}
+        other => panic!("expected a formatting error, got {:?}", other),
+    }
+    assert_eq!(buf, "1, ");
+
+    // more pieces and arguments than are gathered on the stack
+    let spec: String = (0..20).map(|i| format!("{{{}:x}}.", i % 2)).collect();
+    let prepared = PreparedFormat::<(u32, u32)>::prepare(&spec).unwrap();
+    prepared.render_into(&mut buf, &(10, 11)).unwrap();
+    assert_eq!(buf, "a.b.".repeat(10));
+}
+
+#[test]
+fn test_render_fmt() {
+    use std::fmt;
+
+    struct Greeting {
+        template: PreparedFormat<'static, (&'static str, u32)>,
+        name: &'static str,
+        visits: u32,
+    }
+
+    impl fmt::Display for Greeting {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("<")?;
+            self.template.render_fmt(f, &(self.name, self.visits))?;
+            f.write_str(">")
+        }
+    }
+
+    let greeting = Greeting {
+        template: PreparedFormat::prepare("{0}: {1:>3}").unwrap(),
+        name: "ann",
+        visits: 7,
+    };
+    assert_eq!(greeting.to_string(), "<ann:   7>");
+    assert_eq!(format!("{:>20}", greeting), "<ann:   7>");
+}
+
+#[test]
+fn test_render_spans() {
+    use runtime_fmt::PieceRef::{Argument, Literal};
+
+    let prepared = PreparedFormat::<(&str, i32)>::prepare("Hi {0}, {1:>3}{0}!").unwrap();
+    let (output, spans) = prepared.render_spans(&("al", 7)).unwrap();
+    assert_eq!(output, "Hi al,   7al!");
+    assert_eq!(spans, vec![
+        (0..3, Literal(0)),
+        (3..5, Argument(0)),
+        (5..7, Literal(1)),
+        (7..10, Argument(1)),
+        (10..12, Argument(2)),
+        (12..13, Literal(3)),
+    ]);
+
+    // arguments producing nothing have no span
+    let prepared = PreparedFormat::<(&str, &str)>::prepare("{0}{1}").unwrap();
+    let (output, spans) = prepared.render_spans(&("", "x")).unwrap();
+    assert_eq!(output, "x");
+    assert_eq!(spans, vec![(0..1, Argument(1))]);
+}
+
+#[test]
+fn test_prepare_static() {
+    use std::thread;
+
+    static TEMPLATE: &'static str = "{0}: {1:>3}";
+    let prepared: PreparedFormat<'static, (&str, u8)> = PreparedFormat::prepare(TEMPLATE).unwrap();
+    let output = thread::spawn(move || prepared.format(&("id", 7))).join().unwrap();
+    assert_eq!(output, "id:   7");
+}
+
+#[test]
+fn test_prepare_borrowed() {
+    static SMALL: &'static str = "{0} = {1:>4}";
+    static LARGE: &'static str = "{0}, {1}, {0:?}, {1:x}, {{{0}}}, {1:X}";
+
+    let small: PreparedFormat<'static, (&str, u32)> =
+        PreparedFormat::prepare_borrowed(SMALL).unwrap();
+    assert_eq!(small.format(&("x", 12)), "x =   12");
+    assert_eq!(small.clone().format(&("y", 3)), "y =    3");
+
+    let large: PreparedFormat<'static, (&str, u32)> =
+        PreparedFormat::prepare_borrowed(LARGE).unwrap();
+    assert_eq!(large.format(&("a", 255)), "a, 255, \"a\", ff, {a}, FF");
+}
+
+#[test]
+fn test_send_sync() {
+    use std::sync::Arc;
+    use std::thread;
+
+    fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+    let spec = String::from("{id:>3}: {name}");
+    let prepared = PreparedFormat::<Row>::prepare(&spec).unwrap().into_owned();
+    drop(spec);
+    assert_send_sync(&prepared);
+
+    let shared = Arc::new(prepared);
+    let handles: Vec<_> = (0..4).map(|id| {
+        let shared = shared.clone();
+        thread::spawn(move || shared.format(&Row { id: id, ..row() }))
+    }).collect();
+    for (id, handle) in handles.into_iter().enumerate() {
+        assert_eq!(handle.join().unwrap(), format!("{:>3}: widget", id));
+    }
+}
+
+#[test]
+fn test_estimate_len() {
+    let prepared = PreparedFormat::<(&str, u32)>::prepare("name: {0}, id: {1:>12}!").unwrap();
+    let literal_len = "name: , id: !".len();
+    assert!(prepared.estimate_len() >= literal_len + 12);
+    let output = prepared.format(&("ann", 7));
+    assert_eq!(output, "name: ann, id:            7!");
+    assert!(output.capacity() >= prepared.estimate_len());
+
+    let prepared = PreparedFormat::<(u8,)>::prepare("no arguments").unwrap();
+    assert_eq!(prepared.estimate_len(), "no arguments".len());
+}
+
+#[test]
+fn test_referenced_indices() {
+    let prepared = PreparedFormat::<Row>::prepare("{name:>width$} #{0} {id}").unwrap();
+    let referenced = prepared.referenced_indices();
+    assert_eq!(referenced.iter().cloned().collect::<Vec<_>>(), vec![0, 1, 2]);
+    let unused: Vec<usize> = (0..Row::arg_count()).filter(|i| !referenced.contains(i)).collect();
+    assert_eq!(unused, vec![3, 4]);
+
+    let prepared = PreparedFormat::<Row>::prepare("no arguments").unwrap();
+    assert!(prepared.referenced_indices().is_empty());
+}
+
+#[test]
+fn test_required_traits() {
+    let expected = vec![
+        (Position::Named("id"), 0, "LowerHex"),
+        (Position::Named("ratio"), 3, "LowerExp"),
+        (Position::Index(0), 0, "Display"),
+    ];
+    let prepared = PreparedFormat::<Row>::prepare("{id:#x} {ratio:>width$e} {0}").unwrap();
+    assert_eq!(prepared.required_traits(), expected);
+    let owned = {
+        let spec = String::from("{id:#x} {ratio:>width$e} {0}");
+        PreparedFormat::<Row>::prepare(&spec).unwrap().into_owned()
+    };
+    assert_eq!(owned.required_traits(), expected);
+    let prepared = PreparedFormat::<Row>::prepare("no arguments").unwrap();
+    assert!(prepared.required_traits().is_empty());
+}
+
+#[test]
+fn test_from_end() {
+    use runtime_fmt::Error;
+
+    #[derive(FormatArgs)]
+    struct Item {
+        id: u32,
+        name: &'static str,
+        #[format_args(skip)]
+        _secret: u8,
+        total: f64,
+    }
+
+    assert_eq!(Item::arg_count(), 3);
+    assert_eq!(<(u8, u8)>::arg_count(), 2);
+    assert_eq!(<[u8; 5]>::arg_count(), 5);
+
+    let row = Item { id: 7, name: "widget", _secret: 0, total: 2.5 };
+    let prepared = PreparedFormat::<Item>::prepare("{-1:.2} for {-2} (#{-3})").unwrap();
+    assert_eq!(prepared.format(&row), "2.50 for widget (#7)");
+    assert_eq!(rt_format!("{-1}/{-2}", "a", "b", "c").unwrap(), "c/b");
+
+    match PreparedFormat::<Item>::prepare("{-4}") {
+        Err(Error::BadIndexFromEnd(4)) => {}
+        _ => panic!("out-of-range index from the end was accepted"),
+    }
+    assert!(PreparedFormat::<Item>::prepare("{-0}").is_err());
+    assert!(runtime_fmt::validate_all::<Item>("{-3}{-4}").is_err());
+}
+
+#[test]
+fn test_group_separator() {
+    #[derive(FormatArgs)]
+    struct Balance {
+        amount: i64,
+    }
+
+    let mut prepared = PreparedFormat::prepare("[{amount:>10_}]").unwrap();
+    assert_eq!(prepared.format(&Balance { amount: -9876543 }), "[-9,876,543]");
+    prepared.group_separator('\'');
+    assert_eq!(prepared.format(&Balance { amount: 1234 }), "[     1'234]");
+}
+
+#[test]
+fn test_map_literals() {
+    let mut prepared = PreparedFormat::<(&str, i32)>::prepare("Hello, {0}! {{{1:>3}}}{0}").unwrap();
+    prepared.map_literals(|text| format!("\u{27e6}{}\u{27e7}", text));
+    assert_eq!(prepared.format(&("world", 7)),
+        "\u{27e6}Hello, \u{27e7}world\u{27e6}! {\u{27e7}  7\u{27e6}}\u{27e7}world");
+
+    let mut prepared = PreparedFormat::<(i32,)>::prepare("plain text").unwrap();
+    prepared.map_literals(|text| text.to_uppercase());
+    assert_eq!(prepared.format(&(1,)), "PLAIN TEXT");
+}
+
+#[test]
+fn test_default_trait() {
+    use runtime_fmt::{DefaultTrait, PrepareOptions};
+
+    let value = ("ann", Some(3), 1.5);
+    // `Option` has no `Display` implementation
+    assert!(PreparedFormat::<(&str, Option<i32>, f64)>::prepare("{1}").is_err());
+
+    let mut options = PrepareOptions::<(&str, Option<i32>, f64)>::new();
+    options.default_trait(DefaultTrait::Debug);
+    let prepared = options.prepare("{0} {1} {2:>5} {1:?} {0:html}").unwrap();
+    assert_eq!(prepared.format(&value), "\"ann\" Some(3)   1.5 Some(3) ann");
+    let prepared = options.prepare("{1:#}").unwrap();
+    assert_eq!(prepared.format(&value), format!("{:#?}", value.1));
+}
+
+#[test]
+fn test_missing_names() {
+    use runtime_fmt::{Error, PrepareOptions};
+
+    #[derive(FormatArgs)]
+    struct Message {
+        user: &'static str,
+        count: usize,
+    }
+
+    let prepared = PrepareOptions::<Message>::new()
+        .missing(|name| format!("<{}?>", name))
+        .prepare("{user} has {count} {unit} from {sender:>8}.")
+        .unwrap();
+    let message = Message { user: "kim", count: 3 };
+    assert_eq!(prepared.format(&message), "kim has 3 <unit?> from <sender?>.");
+
+    let mut options = PrepareOptions::<Message>::new();
+    options.missing(|_| String::from("N/A"));
+    assert_eq!(options.prepare("{user:w$}").unwrap().format(&message), "N/A");
+    // only unknown names are replaced
+    match options.prepare("{5}") {
+        Err(Error::BadIndex(5)) => {}
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("unexpectedly succeeded"),
+    }
+    match options.prepare("{user:x}") {
+        Err(Error::UnsatisfiedFormat { idx: 0, .. }) => {}
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("unexpectedly succeeded"),
+    }
+}
+
+#[test]
+fn test_join() {
+    #[derive(FormatArgs)]
+    struct Order {
+        id: u32,
+        items: Vec<&'static str>,
+        prices: [f64; 2],
+    }
+
+    let order = Order { id: 7, items: vec!["tea", "cake"], prices: [2.5, 4.0] };
+    let prepared = PreparedFormat::<Order>::prepare(
+        "#{id}: {items:join(\", \")} ({prices:.2join(\" + \")})").unwrap();
+    assert_eq!(prepared.format(&order), "#7: tea, cake (2.50 + 4.00)");
+    let owned = prepared.into_owned();
+    assert_eq!(owned.format(&order), "#7: tea, cake (2.50 + 4.00)");
+    assert!(runtime_fmt::validate_all::<Order>("{id:join(,)} {items:join(,)}").is_err());
+
+    // quoted separators may contain `)`, `}`, and escaped quotes
+    let prepared = PreparedFormat::<Order>::prepare(
+        r#"{items:join(")")} {items:join("a}b")} {items:join("\"\\")}"#).unwrap();
+    assert_eq!(prepared.format(&order), r#"tea)cake teaa}bcake tea"\cake"#);
+}
@@ -0,0 +1,41 @@
+extern crate runtime_fmt;
+
+use runtime_fmt::{Error, FormatBuf};
+
+#[test]
+fn vec() {
+    let items = vec![3, 14, 15];
+    let buf = FormatBuf::new_dyn("{} {:>3} {2:#x} {-1}", &items).unwrap();
+    assert_eq!(buf.format(), "3  14 0xf 15");
+
+    match FormatBuf::new_dyn("{3}", &items) {
+        Err(Error::BadIndex(3)) => {}
+        _ => panic!("out-of-range index was not rejected"),
+    }
+    match FormatBuf::new_dyn("{} {} {} {}", &items) {
+        Err(Error::BadIndex(3)) => {}
+        _ => panic!("out-of-range implicit index was not rejected"),
+    }
+    assert!(FormatBuf::new_dyn("{0}", &Vec::<i32>::new()).is_err());
+    assert!(FormatBuf::new_dyn("{name}", &items).is_err());
+}
+
+#[test]
+fn slice() {
+    let names = ["ann", "bo", "cy"];
+    let slice: &[&str] = &names[1..];
+    let buf = FormatBuf::new_dyn("{0:?} and {1:<3}|", &slice).unwrap();
+    assert_eq!(buf.format(), "\"bo\" and cy |");
+    assert!(FormatBuf::new_dyn("{2}", &slice).is_err());
+}
+
+#[test]
+fn counts_and_nesting() {
+    let counts = vec![5usize, 2];
+    let buf = FormatBuf::new_dyn("[{0:>1$}]", &counts).unwrap();
+    assert_eq!(buf.format(), "[    5]");
+
+    let rows = vec![(1, "one"), (2, "two")];
+    let buf = FormatBuf::new_dyn("{1.0}={1.1}", &rows).unwrap();
+    assert_eq!(buf.format(), "2=two");
+}
@@ -0,0 +1,61 @@
+#![cfg(all(feature = "serialize", feature = "serde_json"))]
+extern crate serde_json;
+extern crate runtime_fmt;
+
+use runtime_fmt::{PreparedFormat, Error};
+use runtime_fmt::ast::{parse, OwnedPiece, Piece};
+
+#[test]
+fn round_trip() {
+    let spec = "{{{0:>+8.2}}} {1:#x} {0:_^w$} {1:?}";
+    let pieces = parse(spec).unwrap();
+    let json = serde_json::to_string(&pieces).unwrap();
+    let loaded: Vec<Piece> = serde_json::from_str(&json).unwrap();
+    assert_eq!(loaded, pieces);
+
+    let direct = PreparedFormat::<(f64, u32)>::prepare("{{{0:>+8.2}}} {1:#x}").unwrap();
+    let loaded = parse("{{{0:>+8.2}}} {1:#x}").unwrap();
+    let json = serde_json::to_string(&loaded).unwrap();
+    let loaded: Vec<Piece> = serde_json::from_str(&json).unwrap();
+    let prepared = PreparedFormat::<(f64, u32)>::from_pieces(&loaded).unwrap();
+    assert_eq!(prepared.format(&(3.14159, 255)), direct.format(&(3.14159, 255)));
+    assert_eq!(prepared.format(&(3.14159, 255)), "{   +3.14} 0xff");
+}
+
+#[test]
+fn extensions_round_trip() {
+    let spec = "{0:_} {1:html} {1:maxlen(3)} {0:?style(red)}";
+    let loaded: Vec<Piece> = serde_json::from_str(
+        &serde_json::to_string(&parse(spec).unwrap()).unwrap()).unwrap();
+    let values = (1234567, "<a>bcde");
+    let direct = PreparedFormat::<(i32, &str)>::prepare(spec).unwrap();
+    let prepared = PreparedFormat::<(i32, &str)>::from_pieces(&loaded).unwrap();
+    assert_eq!(prepared.format(&values), direct.format(&values));
+}
+
+#[test]
+fn owned_round_trip() {
+    let spec = "say \"{0:join(\", \")}\"\n{1:>2$}\\";
+    let pieces = parse(spec).unwrap();
+    let json = serde_json::to_string(&pieces).unwrap();
+    assert!(serde_json::from_str::<Vec<Piece>>(&json).is_err());
+
+    let owned: Vec<OwnedPiece> = pieces.iter().map(|&p| OwnedPiece::from(p)).collect();
+    let json = serde_json::to_string(&owned).unwrap();
+    let loaded: Vec<OwnedPiece> = serde_json::from_str(&json).unwrap();
+    assert_eq!(loaded, owned);
+    let loaded: Vec<Piece> = loaded.iter().map(OwnedPiece::as_piece).collect();
+    assert_eq!(loaded, pieces);
+
+    let prepared = PreparedFormat::<(Vec<i32>, &str, usize)>::from_pieces(&loaded).unwrap();
+    assert_eq!(prepared.format(&(vec![1, 2], "x", 3)), "say \"1, 2\"\n  x\\");
+}
+
+#[test]
+fn pieces_are_checked() {
+    let loaded = parse("{0} {1}").unwrap();
+    match PreparedFormat::<(i32,)>::from_pieces(&loaded) {
+        Err(Error::BadIndex(1)) => {}
+        _ => panic!("expected BadIndex"),
+    }
+}